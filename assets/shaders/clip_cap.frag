@@ -0,0 +1,11 @@
+#version 450
+
+// Solid fill for the cross-sections cut by clipping planes.
+
+uniform vec3 cap_color;
+
+layout(location = 0) out vec4 o_Target;
+
+void main() {
+    o_Target = vec4(cap_color, 1.0);
+}
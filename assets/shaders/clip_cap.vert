@@ -0,0 +1,26 @@
+#version 450
+
+// Must match `MAX_CLIP_PLANES` in `clipping.rs`.
+#define MAX_CLIP_PLANES 6
+
+uniform mat4 projection_matrix;
+// Center and half extents of the rectangle covering the cut cross-section.
+uniform vec3 cap_origin;
+uniform vec3 cap_u;
+uniform vec3 cap_v;
+
+uniform vec4 clip_planes[MAX_CLIP_PLANES];
+uniform uint clip_plane_count;
+
+out float gl_ClipDistance[MAX_CLIP_PLANES];
+
+void main() {
+    // Triangle strip corners, generated without vertex buffers.
+    vec2 corner = vec2(gl_VertexID & 1, gl_VertexID >> 1) * 2.0 - 1.0;
+    vec3 position = cap_origin + cap_u * corner.x + cap_v * corner.y;
+
+    gl_Position = projection_matrix * vec4(position, 1.0);
+    for (int i = 0; i < MAX_CLIP_PLANES; i++) {
+        gl_ClipDistance[i] = uint(i) < clip_plane_count ? dot(clip_planes[i], vec4(position, 1.0)) : 1.0;
+    }
+}
@@ -0,0 +1,48 @@
+#version 450
+
+// Fast approximate anti-aliasing, after the FXAA algorithm by Timothy Lottes.
+
+#define FXAA_SPAN_MAX 8.0
+#define FXAA_REDUCE_MUL (1.0 / 8.0)
+#define FXAA_REDUCE_MIN (1.0 / 128.0)
+
+layout(binding = 0) uniform sampler2D scenetexture;
+
+layout(location = 0) out vec4 o_Target;
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(scenetexture, 0));
+    vec2 uv = gl_FragCoord.xy * texel;
+    vec3 luma = vec3(0.299, 0.587, 0.114);
+
+    vec3 rgbM = texture(scenetexture, uv).rgb;
+    float lumaM = dot(rgbM, luma);
+    float lumaNW = dot(texture(scenetexture, uv + vec2(-1.0, -1.0) * texel).rgb, luma);
+    float lumaNE = dot(texture(scenetexture, uv + vec2(1.0, -1.0) * texel).rgb, luma);
+    float lumaSW = dot(texture(scenetexture, uv + vec2(-1.0, 1.0) * texel).rgb, luma);
+    float lumaSE = dot(texture(scenetexture, uv + vec2(1.0, 1.0) * texel).rgb, luma);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    // Blur along the edge, which is perpendicular to the luminance gradient.
+    vec2 dir = vec2(
+        -((lumaNW + lumaNE) - (lumaSW + lumaSE)),
+        (lumaNW + lumaSW) - (lumaNE + lumaSE)
+    );
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * texel;
+
+    vec3 rgbA = 0.5 * (
+        texture(scenetexture, uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(scenetexture, uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (
+        texture(scenetexture, uv + dir * -0.5).rgb +
+        texture(scenetexture, uv + dir * 0.5).rgb);
+
+    // Fall back to the narrower blur if the wide one picked up samples across another edge.
+    float lumaB = dot(rgbB, luma);
+    // Alpha is kept as is, so transparent backgrounds stay transparent.
+    float alpha = texture(scenetexture, uv).a;
+    o_Target = vec4((lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB, alpha);
+}
@@ -1,11 +1,13 @@
 #version 330 core
 
 uniform uint steps;
+uniform float near_plane;
 uniform float far_plane;
 
+in float view_depth;
+
 void main(){
-    // float depth = (gl_FragCoord.z / gl_FragCoord.w) / far_plane;
     // gl_FragDepth = floor(depth * steps) / steps;
-    float depth = (gl_FragCoord.z / gl_FragCoord.w) / far_plane;
-    gl_FragDepth = depth;
+    // Measured from the near plane, as it lies behind the camera in zoomed orthographic views.
+    gl_FragDepth = (view_depth - near_plane) / (far_plane - near_plane);
 }
@@ -6,10 +6,29 @@ layout(location = 2) in vec3 vertex_color;
 
 uniform mat4 projection_matrix;
 uniform float hatching_depth;
+uniform vec3 camera_position;
+uniform vec3 view_direction;
+
+// Must match `MAX_CLIP_PLANES` in `clipping.rs`.
+#define MAX_CLIP_PLANES 6
+
+uniform vec4 clip_planes[MAX_CLIP_PLANES];
+uniform uint clip_plane_count;
+
+out float gl_ClipDistance[MAX_CLIP_PLANES];
+
+// Distance from the camera along the view direction.
+out float view_depth;
 
 void main() {
     vec3 normal = normalize(vertex_normal);
     vec3 position = vertex_position + normal * hatching_depth;
     
     gl_Position = projection_matrix * vec4(position, 1.0);
+    view_depth = dot(position - camera_position, view_direction);
+
+    // Clip the unshifted surface, so the hatching map is cut where the model is.
+    for (int i = 0; i < MAX_CLIP_PLANES; i++) {
+        gl_ClipDistance[i] = uint(i) < clip_plane_count ? dot(clip_planes[i], vec4(vertex_position, 1.0)) : 1.0;
+    }
 }
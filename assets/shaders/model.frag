@@ -4,14 +4,53 @@
 #define DSC_HUE 1
 #define DSC_SATURATION 2
 #define DSC_VALUE 3
+#define COLORMAP_HSV 0
+#define HUE_INCREASING 0
+#define HUE_DECREASING 1
+#define SHADOW_PCF 0
+#define SHADOW_PCSS 1
 #define M_PI 3.141592653589793;
 
+#define HATCHING_SCREEN 0
+#define HATCHING_OBJECT 1
+#define HATCHING_CURVATURE 2
+
+// Object-space hatching strokes follow the planes perpendicular to these axes.
+#define OBJECT_HATCHING_AXIS vec3(0.70710678, 0.70710678, 0.0)
+#define OBJECT_CROSS_HATCHING_AXIS vec3(0.70710678, -0.70710678, 0.0)
+
+#define CURVATURE_NONE 0
+#define CURVATURE_MEAN 1
+#define CURVATURE_GAUSSIAN 2
+#define CURVATURE_SHAPE_INDEX 3
+#define CURVATURE_MAX_PRINCIPAL 4
+#define CURVATURE_MIN_PRINCIPAL 5
+
+#define LIGHTING_LAMBERT 0
+#define LIGHTING_BLINN_PHONG 1
+#define LIGHTING_OREN_NAYAR 2
+
+// Must match `MAX_LIGHTS` in `lights.rs`.
+#define MAX_LIGHTS 8
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+
+#define RIDGE_COLOR vec3(0.05, 0.05, 0.05)
+#define VALLEY_COLOR vec3(0.1, 0.25, 0.7)
+
 layout(location = 0) out vec4 o_Target;
+layout(location = 1) out float o_Revealage;
 
-layout(binding = 0) uniform sampler2DShadow shadowtexture;
+layout(binding = 0) uniform sampler2D shadowtexture;
 layout(binding = 1) uniform sampler2DShadow hatchingtexture;
+layout(binding = 2) uniform sampler1D colormaptexture;
 
 uniform vec3 camera_position;
+// Direction the camera looks in, used instead of the direction to the camera when orthographic.
+uniform vec3 view_direction;
+uniform bool orthographic;
+// Window depth goes from 1 at the near plane to 0 at the far plane.
+uniform bool reversed_z;
 uniform vec3 light_vector;
 uniform vec3 color;
 uniform vec3 distance_shading_closest;
@@ -20,13 +59,51 @@ uniform float model_size;
 uniform float distance_shading_power;
 uniform uint distance_shading_channel;
 uniform float distance_shading_constrict;
+uniform uint distance_shading_colormap;
+uniform float hue_near;
+uniform float hue_far;
+uniform uint hue_direction;
+uniform float hue_saturation;
 uniform float toon_factor;
+uniform uint lighting_model;
+uniform float ambient_reflection;
+uniform float diffuse_reflection;
+uniform float specular_reflection;
+uniform float shininess;
+uniform float roughness;
+
+struct Light {
+    // World position or direction, with the light kind in w.
+    vec4 position;
+    // Colour, with the intensity in w.
+    vec4 color;
+};
+
+layout(std140, binding = 0) uniform Lights {
+    Light lights[MAX_LIGHTS];
+    uint light_count;
+};
 uniform float shadow_intensity;
+uniform uint shadow_filtering;
+uniform float shadow_penumbra_scale;
 uniform float vertex_color_mix;
-uniform uint hatching_frequency;
+uniform float hatching_spacing;
+uniform float pixels_per_point;
+uniform uint curvature_coloring;
+uniform float curvature_range;
+uniform bool ridge_valley_lines;
+uniform float ridge_threshold;
+uniform float ridge_line_width;
+uniform uint hatching_mode;
+uniform float hatching_object_spacing;
+uniform bool cross_hatching;
+uniform float cross_hatching_threshold;
 uniform float hatching_intensity;
+uniform float hatching_near_plane;
 uniform float hatching_far_plane;
 uniform bool replace_shadows_with_hatching;
+uniform float opacity;
+uniform bool oit_pass;
 
 layout(location = 0) in vec3 normal_vector;
 layout(location = 1) in vec3 toon_light_vector;
@@ -34,6 +111,9 @@ layout(location = 2) in vec3 position_vector;
 layout(location = 3) in vec3 vertex_color;
 layout(location = 4) in vec4 uv;
 layout(location = 5) in vec4 hatchpos;
+layout(location = 6) in vec3 curvature_direction;
+layout(location = 7) in vec2 principal_curvatures;
+layout(location = 8) in vec4 curvature_measures;
 
 // https://stackoverflow.com/a/17897228
 // All components are in the range [0…1], including hue.
@@ -81,56 +161,246 @@ float random(vec3 seed, int i){
 	return fract(sin(dot_product) * 43758.5453);
 }
 
-float ShadowCalculation(vec4 fragPosLightSpace)
+// Finds the hue at depth t, where 0 is nearest and 1 is furthest. Hues are in the range [0…1].
+float hueCalculation(float t)
+{
+    float hue;
+    if (hue_direction == HUE_INCREASING) {
+        hue = hue_near + mod(hue_far - hue_near, 1.0) * t;
+    } else {
+        hue = hue_near - mod(hue_near - hue_far, 1.0) * t;
+    }
+    return fract(hue);
+}
+
+float shadowBias()
 {
     float cosTheta = clamp(dot(light_vector, vec3(1)), 0.0, 1.0);
     float bias = 0.005 * tan(acos(cosTheta));
-    bias = clamp(bias, 0.0, 0.05);
+    return clamp(bias, 0.0, 0.05);
+}
+
+float ShadowCalculation(vec4 fragPosLightSpace)
+{
+    float bias = shadowBias();
 
     vec3 projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
     projCoords = projCoords * 0.5 + 0.5;
     float currentDepth = projCoords.z;
-    
+
+    // Sample within roughly three texels, whatever the shadow map resolution.
+    vec2 radius = 3.0 / vec2(textureSize(shadowtexture, 0));
     float shadow = 1.0;
     for (int i=0;i<16;i++){
         
         int index = int(16.0 * random(gl_FragCoord.xyy, i))%16;;
-        float sample_depth = texture(shadowtexture, projCoords.xyz + poissonDisk[index] / 700.0 ).r;
+        float sample_depth = texture(shadowtexture, projCoords.xy + poissonDisk[index].xy * radius).r;
         if (sample_depth < currentDepth - bias) {
             shadow-= shadow_intensity / 16.0;
         }
     }
+    // Beyond the light's far plane nothing can be in front of the fragment, so it is lit.
     if (projCoords.z > 1.0) {
-        shadow = 0.0;
+        shadow = 1.0;
+    }
+    return shadow;
+}
+
+// Percentage-closer soft shadows (Fernando, 2005) for an orthographic light. The penumbra grows
+// linearly with the depth difference between the receiver and the average blocker.
+float PCSSCalculation(vec4 fragPosLightSpace)
+{
+    float bias = shadowBias();
+
+    vec3 projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
+    projCoords = projCoords * 0.5 + 0.5;
+    float currentDepth = projCoords.z;
+    // Beyond the light's far plane nothing can be in front of the fragment, so it is lit.
+    if (currentDepth > 1.0) {
+        return 1.0;
+    }
+
+    vec2 texel = 1.0 / vec2(textureSize(shadowtexture, 0));
+    float angle = random(gl_FragCoord.xyy, 0) * 6.283185307;
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+
+    // Blocker search, in the widest region that could contain an occluder.
+    float search_radius = clamp(currentDepth * shadow_penumbra_scale, texel.x, 0.05);
+    float blocker_sum = 0.0;
+    int blockers = 0;
+    for (int i = 0; i < 16; i++) {
+        vec2 offset = rotation * poissonDisk[i].xy * search_radius;
+        float sample_depth = texture(shadowtexture, projCoords.xy + offset).r;
+        if (sample_depth < currentDepth - bias) {
+            blocker_sum += sample_depth;
+            blockers++;
+        }
+    }
+    if (blockers == 0) {
+        return 1.0;
+    }
+
+    // Filter with a kernel the size of the estimated penumbra.
+    float blocker_depth = blocker_sum / float(blockers);
+    float penumbra = max((currentDepth - blocker_depth) * shadow_penumbra_scale, texel.x);
+    float shadow = 1.0;
+    for (int i = 0; i < 16; i++) {
+        vec2 offset = rotation * poissonDisk[i].xy * penumbra;
+        float sample_depth = texture(shadowtexture, projCoords.xy + offset).r;
+        if (sample_depth < currentDepth - bias) {
+            shadow -= shadow_intensity / 16.0;
+        }
     }
     return shadow;
 }
 
-float triangle(float x) {
+float triangle(float x, float period) {
     // Put on a -1 to 1 range
-    x = float(int(floor(abs(x))) % hatching_frequency) / hatching_frequency * 2.0 - 1.0;
+    x = mod(x, period) / period * 2.0 - 1.0;
 
     // Triangle function
     return max(0, 1.0 - abs(x));
 }
 
-float hatchingCalculation()
+// Returns the hatching pattern for the primary stroke direction, or the crossing direction if
+// `crossing` is set. Strokes are where the pattern approaches zero.
+float hatchingStrokes(bool crossing)
+{
+    if (hatching_mode == HATCHING_SCREEN) {
+        // Spacing is given in logical pixels, so lines look the same on high density displays.
+        float period = max(hatching_spacing * pixels_per_point, 1.0);
+        float x = crossing ? gl_FragCoord.x + gl_FragCoord.y : gl_FragCoord.x - gl_FragCoord.y;
+        return triangle(floor(x), period);
+    }
+
+    float period = max(hatching_object_spacing * model_size, 1e-5);
+    vec3 axis = crossing ? OBJECT_CROSS_HATCHING_AXIS : OBJECT_HATCHING_AXIS;
+    if (hatching_mode == HATCHING_CURVATURE) {
+        // Strokes run along the curvature direction, so the pattern is measured across it.
+        vec3 n = normalize(normal_vector);
+        vec3 along = curvature_direction - n * dot(curvature_direction, n);
+        if (length(along) < 1e-5) {
+            return 1.0;
+        }
+        along = normalize(along);
+        axis = crossing ? along : cross(n, along);
+    }
+    return triangle(dot(position_vector, axis), period);
+}
+
+// Distance from the camera, measured along the view direction for orthographic views so that
+// depth cues match the parallel projection.
+float viewDistance(vec3 position)
+{
+    if (orthographic) {
+        return dot(position - camera_position, view_direction);
+    }
+    return length(camera_position - position);
+}
+
+float hatchingCalculation(float tone)
 {
     vec3 projCoords = vec3(0);
     projCoords.xy = hatchpos.xy / hatchpos.w * 0.5 + 0.5;
-    projCoords.z = hatchpos.z / hatching_far_plane;
+    // The hatching map stores the depth along the view direction, which doesn't depend on the
+    // projection or the depth convention.
+    float view_depth = dot(position_vector - camera_position, view_direction);
+    projCoords.z = (view_depth - hatching_near_plane) / (hatching_far_plane - hatching_near_plane);
     projCoords.z -= 0.002;
     float sample_depth = texture(hatchingtexture, projCoords).r;
 
     if (sample_depth < 0.5) {
-        return triangle(gl_FragCoord.x - gl_FragCoord.y);
+        float strokes = hatchingStrokes(false);
+        if (cross_hatching && tone > cross_hatching_threshold) {
+            strokes = min(strokes, hatchingStrokes(true));
+        }
+        return strokes;
     }
 
     return 1.0;
 }
 
+// Returns the diffuse and specular light reflected towards the viewer, given the surface normal
+// `n`, and the directions `l` to the light and `v` to the viewer.
+float reflectedLight(vec3 n, vec3 l, vec3 v) {
+    float NdotL = max(dot(n, l), 0.0);
+    float diffuse = NdotL;
+    if (lighting_model == LIGHTING_OREN_NAYAR) {
+        // Oren-Nayar (https://garykeen27.wixsite.com/portfolio/oren-nayar-shading)
+        float r2 = roughness * roughness;
+        float A = 1 - 0.5 * r2 / (r2 + 0.33);
+        float B = 0.45 * r2 / (r2 + 0.09);
+        float NdotV = clamp(dot(n, v), 0.0, 1.0);
+        float angleLN = acos(NdotL);
+        float angleVN = acos(NdotV);
+        float alpha = max(angleVN, angleLN);
+        float beta = min(angleVN, angleLN);
+        diffuse = NdotL * (A + (B * max(0, cos(angleVN - angleLN)) * sin(alpha) * tan(beta)));
+    }
+
+    float specular = 0.0;
+    if (lighting_model != LIGHTING_LAMBERT && NdotL > 0.0) {
+        vec3 h = normalize(l + v);
+        specular = pow(max(dot(n, h), 0.0), shininess);
+    }
+
+    return diffuse_reflection * diffuse + specular_reflection * specular;
+}
+
+// Blue-white-red diverging colour scale for signed values in the range [-1…1].
+vec3 diverging(float t) {
+    t = clamp(t, -1.0, 1.0);
+    vec3 white = vec3(0.87, 0.87, 0.87);
+    if (t < 0.0) {
+        return mix(white, vec3(0.23, 0.30, 0.75), -t);
+    }
+    return mix(white, vec3(0.71, 0.02, 0.15), t);
+}
+
+// Maps the chosen curvature measure to the range [-1…1]. Curvature is made relative to the model
+// size, so the range does not depend on the units of the mesh.
+float curvatureValue() {
+    float scale = model_size / curvature_range;
+    switch (curvature_coloring) {
+        case CURVATURE_MEAN: return curvature_measures.x * scale;
+        case CURVATURE_GAUSSIAN: return curvature_measures.y * scale * scale;
+        case CURVATURE_SHAPE_INDEX: return curvature_measures.z;
+        case CURVATURE_MAX_PRINCIPAL: return principal_curvatures.x * scale;
+        case CURVATURE_MIN_PRINCIPAL: return principal_curvatures.y * scale;
+    }
+    return 0.0;
+}
+
+// Returns the coverage of a ridge or valley line at this fragment, which lie where the derivative
+// of the dominant principal curvature along its direction crosses zero.
+float ridgeValleyLine(out bool ridge) {
+    // Derivatives must be taken before any non-uniform branching.
+    float derivative = curvature_measures.w;
+    float derivative_width = fwidth(derivative);
+
+    float k1 = principal_curvatures.x;
+    float k2 = principal_curvatures.y;
+    float dominant = abs(k1) >= abs(k2) ? k1 : k2;
+    ridge = dominant > 0.0;
+
+    // Interpolated directions cancel out where the orientation flips, which would give false
+    // zero crossings.
+    if (abs(dominant) * model_size < ridge_threshold || length(curvature_direction) < 0.5) {
+        return 0.0;
+    }
+
+    float pixels_from_zero = abs(derivative) / max(derivative_width, 1e-12);
+    float half_width = ridge_line_width * pixels_per_point / 2.0;
+    return 1.0 - smoothstep(half_width - 0.5, half_width + 0.5, pixels_from_zero);
+}
+
 void main() {
     vec3 color = mix(color, vertex_color, vertex_color_mix);
+    vec3 surface_color = vertex_color;
+    if (curvature_coloring != CURVATURE_NONE) {
+        color = diverging(curvatureValue());
+        surface_color = color;
+    }
 
     vec3 toonShadingColor;
     {
@@ -148,47 +418,21 @@ void main() {
 
     vec3 standardShadingColor;
     {
-        float ambientReflection = 0.2;
-        float ambientIntensity = 1;
-
-        float diffuseReflection = 0.8;
-        float diffuseIntensity = 1;
-
-        float specularReflection = 0.1;
-        float specularIntensity = 1;
-
-        float shininess = 10;
+        // Seen from inside the model, the inner side of the wall is lit.
+        vec3 n = normalize(gl_FrontFacing ? normal_vector : -normal_vector);
 
         // Vector to camera
-        vec3 v = normalize(camera_position - position_vector);
-
-        // Vector to light source
-        vec3 light_position = camera_position;
-        vec3 lm = normalize(light_position - position_vector);
-
-        // Reflected light vector
-        vec3 np = 2 * normalize(dot(lm, normal_vector) * normal_vector);
-        vec3 rm = normalize(np - lm);
-
-
-        // Oren-Nayar (https://garykeen27.wixsite.com/portfolio/oren-nayar-shading)
-        float pi = 3.141592653589793;
-        float roughness = 0.3;
-        float A = 1 - 0.5 * pow(roughness, 2) / (pow(roughness, 2) + 0.33);
-        float B = 0.45 * pow(roughness, 2) / (pow(roughness, 2) + 0.09);
-        float NdotL = clamp(dot(normal_vector, lm), 0.0, 1.0);
-        float angleLN = acos(NdotL);
-        float NdotV = clamp(dot(normal_vector, v), 0.0, 1.0);
-        float angleVN = acos(NdotV);
-        float alpha = max(angleVN, angleLN);
-        float beta = min(angleVN, angleLN);        
-        float orenNayar = NdotL * (A + (B * max(0, cos(angleVN - angleLN)) * sin(alpha) * tan(beta)));
-        diffuseIntensity = orenNayar;
-        
-        // Light intensity
-        float ip = ambientReflection * ambientIntensity + diffuseReflection * diffuseIntensity * dot(lm, normal_vector) + specularReflection * specularIntensity * pow(max(0, min(1, dot(rm, v))), shininess);
-
-        standardShadingColor = ip * vertex_color;
+        vec3 v = orthographic ? -view_direction : normalize(camera_position - position_vector);
+
+        vec3 ip = vec3(ambient_reflection);
+        for (uint i = 0u; i < min(light_count, uint(MAX_LIGHTS)); i++) {
+            // Vector to light source
+            vec3 l = int(lights[i].position.w) == LIGHT_POINT
+                ? normalize(lights[i].position.xyz - position_vector)
+                : normalize(lights[i].position.xyz);
+            ip += lights[i].color.rgb * lights[i].color.w * reflectedLight(n, l, v);
+        }
+        standardShadingColor = ip * surface_color;
     }
     color = mix(standardShadingColor, toonShadingColor, toon_factor);
 
@@ -196,8 +440,8 @@ void main() {
     // float camera_dist = length(camera_position);
     // float near_plane = camera_dist - model_size / 2.0 * distance_shading_constrict;
     // float far_plane = camera_dist + model_size / 2.0 * distance_shading_constrict;
-    float near_plane = length(camera_position - distance_shading_closest);
-    float far_plane = length(camera_position - distance_shading_furthest);
+    float near_plane = viewDistance(distance_shading_closest);
+    float far_plane = viewDistance(distance_shading_furthest);
 
     float power = distance_shading_power;
     if (distance_shading_channel == DSC_HUE) {
@@ -206,18 +450,22 @@ void main() {
 
     // Calculate magnitude of shading.
     // float z = abs(gl_FragCoord.z / gl_FragCoord.w);
-    float z = length(camera_position - position_vector);
+    float z = viewDistance(position_vector);
     float d = 1.0 - smoothstep(near_plane, far_plane, z) * power;
     color = rgb2hsv(color);
 
     // Perform shading on channel of choice.
     if (distance_shading_channel == DSC_HUE) {
-        float hue_min = 220.0;
-        float hue_max = 360.0;
-        float low = hue_min / 360.0;
-        float space = (hue_max - hue_min) / 360.0;
-        color.x = low + space * d;
-        color.y = distance_shading_power;
+        if (distance_shading_colormap == COLORMAP_HSV) {
+            color.x = hueCalculation(1.0 - d);
+            color.y = hue_saturation;
+        } else {
+            // Keep the lighting in the value channel, modulated by the colormap's own value.
+            vec3 mapped = rgb2hsv(texture(colormaptexture, d).rgb);
+            color.x = mapped.x;
+            color.y = mapped.y;
+            color.z *= mapped.z;
+        }
     }
     else if (distance_shading_channel == DSC_SATURATION) {
         // Increase base saturation before scaling it.
@@ -230,7 +478,11 @@ void main() {
 
     // Shadows
     if (!replace_shadows_with_hatching) {
-        float shadow = shadow_intensity < 0.005 ? 1.0 : ShadowCalculation(uv);
+        float shadow = 1.0;
+        // Inner walls lie in the shadow of their own outer side, so they are left unshadowed.
+        if (shadow_intensity >= 0.005 && gl_FrontFacing) {
+            shadow = shadow_filtering == SHADOW_PCSS ? PCSSCalculation(uv) : ShadowCalculation(uv);
+        }
         color.z *= shadow;
     }
 
@@ -238,7 +490,9 @@ void main() {
 
     // Hatching
     if (replace_shadows_with_hatching) {
-        float hatching = hatchingCalculation();
+        // Darker tones get cross-hatched, based on the value of the shaded colour.
+        float tone = 1.0 - max(color.r, max(color.g, color.b));
+        float hatching = hatchingCalculation(tone);
         if (hatching < 1.0) 
         {
             vec3 shadow_color = color / vec3(3, 3, 1.5);
@@ -247,5 +501,20 @@ void main() {
         }
     }
 
-    o_Target = vec4(color, 1);
+    if (ridge_valley_lines) {
+        bool ridge;
+        float line = ridgeValleyLine(ridge);
+        color = mix(color, ridge ? RIDGE_COLOR : VALLEY_COLOR, line);
+    }
+
+    if (oit_pass) {
+        // Weighted blended order-independent transparency, equation 10 from McGuire and Bavoil.
+        float depth = reversed_z ? 1.0 - gl_FragCoord.z : gl_FragCoord.z;
+        float weight = clamp(pow(min(1.0, opacity * 10.0) + 0.01, 3.0) * 1e8
+            * pow(1.0 - depth * 0.9, 3.0), 1e-2, 3e3);
+        o_Target = vec4(color * opacity, opacity) * weight;
+        o_Revealage = opacity;
+    } else {
+        o_Target = vec4(color, 1);
+    }
 }
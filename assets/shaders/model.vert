@@ -3,17 +3,31 @@
 layout(location = 0) in vec3 vertex_position;
 layout(location = 1) in vec3 vertex_normal;
 layout(location = 2) in vec3 vertex_color;
+layout(location = 3) in vec3 vertex_curvature_direction;
+layout(location = 4) in vec2 vertex_principal_curvatures;
+layout(location = 5) in vec4 vertex_curvature_measures;
 
 uniform mat4 projection_matrix;
 uniform mat4 light_space_matrix;
 uniform mat4 hatch_space_matrix;
 
+// Must match `MAX_CLIP_PLANES` in `clipping.rs`.
+#define MAX_CLIP_PLANES 6
+
+uniform vec4 clip_planes[MAX_CLIP_PLANES];
+uniform uint clip_plane_count;
+
+out float gl_ClipDistance[MAX_CLIP_PLANES];
+
 layout(location = 0) out vec3 normal_vector;
 layout(location = 1) out vec3 toon_light_vector;
 layout(location = 2) out vec3 position_vector;
 layout(location = 3) out vec3 out_vertex_color;
 layout(location = 4) out vec4 uv;
 layout(location = 5) out vec4 hatchpos;
+layout(location = 6) out vec3 curvature_direction;
+layout(location = 7) out vec2 principal_curvatures;
+layout(location = 8) out vec4 curvature_measures;
 
 void main() {
     normal_vector = normalize(vertex_normal);
@@ -22,6 +36,12 @@ void main() {
     out_vertex_color = vertex_color;
     uv = light_space_matrix * vec4(vertex_position, 1.0);
     hatchpos = hatch_space_matrix * vec4(vertex_position, 1.0);
+    curvature_direction = vertex_curvature_direction;
+    principal_curvatures = vertex_principal_curvatures;
+    curvature_measures = vertex_curvature_measures;
     
     gl_Position = projection_matrix * vec4(vertex_position, 1.0);
+    for (int i = 0; i < MAX_CLIP_PLANES; i++) {
+        gl_ClipDistance[i] = uint(i) < clip_plane_count ? dot(clip_planes[i], vec4(vertex_position, 1.0)) : 1.0;
+    }
 }
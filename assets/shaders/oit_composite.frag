@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) out vec4 o_Target;
+
+layout(binding = 0) uniform sampler2D accum_texture;
+layout(binding = 1) uniform sampler2D revealage_texture;
+
+void main() {
+    ivec2 coord = ivec2(gl_FragCoord.xy);
+    float revealage = texelFetch(revealage_texture, coord, 0).r;
+    if (revealage >= 1.0) {
+        discard;
+    }
+
+    vec4 accum = texelFetch(accum_texture, coord, 0);
+    vec3 average_color = accum.rgb / max(accum.a, 1e-5);
+    o_Target = vec4(average_color, 1.0 - revealage);
+}
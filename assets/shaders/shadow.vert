@@ -4,7 +4,18 @@ layout (location = 0) in vec3 aPos;
 uniform mat4 lightSpaceMatrix;
 uniform mat4 model;
 
+// Must match `MAX_CLIP_PLANES` in `clipping.rs`.
+#define MAX_CLIP_PLANES 6
+
+uniform vec4 clip_planes[MAX_CLIP_PLANES];
+uniform uint clip_plane_count;
+
+out float gl_ClipDistance[MAX_CLIP_PLANES];
+
 void main()
 {
     gl_Position = lightSpaceMatrix * vec4(aPos, 1.0);
+    for (int i = 0; i < MAX_CLIP_PLANES; i++) {
+        gl_ClipDistance[i] = uint(i) < clip_plane_count ? dot(clip_planes[i], vec4(aPos, 1.0)) : 1.0;
+    }
 }
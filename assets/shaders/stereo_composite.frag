@@ -0,0 +1,48 @@
+#version 450
+
+// Combines the left and right eye images for the selected stereo output.
+
+#define STEREO_ANAGLYPH 1
+#define STEREO_SIDE_BY_SIDE 2
+#define STEREO_TOP_BOTTOM 3
+#define STEREO_INTERLACED 4
+
+layout(binding = 0) uniform sampler2D lefttexture;
+layout(binding = 1) uniform sampler2D righttexture;
+
+uniform uint stereo_mode;
+
+layout(location = 0) out vec4 o_Target;
+
+vec3 eyeColor(bool left, ivec2 pixel) {
+    ivec2 size = textureSize(lefttexture, 0);
+    pixel = clamp(pixel, ivec2(0), size - 1);
+    return left ? texelFetch(lefttexture, pixel, 0).rgb : texelFetch(righttexture, pixel, 0).rgb;
+}
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    ivec2 size = textureSize(lefttexture, 0);
+    vec3 color;
+
+    if (stereo_mode == STEREO_ANAGLYPH) {
+        // Half-colour anaglyph: the red channel carries the left eye's luminance, which keeps
+        // red and cyan objects visible to both eyes and reduces retinal rivalry.
+        vec3 left = eyeColor(true, pixel);
+        vec3 right = eyeColor(false, pixel);
+        color = vec3(dot(left, vec3(0.299, 0.587, 0.114)), right.gb);
+    } else if (stereo_mode == STEREO_SIDE_BY_SIDE) {
+        bool left = pixel.x < size.x;
+        color = eyeColor(left, left ? pixel : pixel - ivec2(size.x, 0));
+    } else if (stereo_mode == STEREO_TOP_BOTTOM) {
+        // Window coordinates start at the bottom, so the left eye is the upper half.
+        bool left = pixel.y >= size.y;
+        color = eyeColor(left, left ? pixel - ivec2(0, size.y) : pixel);
+    } else {
+        // Count rows from the top, which is where interlaced displays start with the left eye.
+        bool left = (size.y - 1 - pixel.y) % 2 == 0;
+        color = eyeColor(left, pixel);
+    }
+
+    o_Target = vec4(color, 1.0);
+}
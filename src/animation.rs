@@ -0,0 +1,507 @@
+//! Contains [SequenceExport], which renders a turntable or a flythrough as a numbered PNG
+//! sequence at a fixed time step, optionally encoding the frames into a video with ffmpeg.
+//! Settings such as opacities can be keyframed to change over the sequence.
+
+use crate::{
+    camera::{Camera, CameraState},
+    capture::{self, Image},
+    flythrough::Flythrough,
+    model::Attributes,
+};
+use anyhow::{anyhow, Context, Result};
+use na::{Point3, UnitQuaternion, Vector3};
+use nalgebra as na;
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+/// Represents how the camera moves through an exported sequence.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum AnimationKind {
+    /// One full turn around the vertical axis through the camera target.
+    #[default]
+    Turntable,
+    /// From the start to the end of the current flythrough path.
+    Flythrough,
+}
+
+impl AnimationKind {
+    pub const ALL: [AnimationKind; 2] = [AnimationKind::Turntable, AnimationKind::Flythrough];
+}
+
+impl std::fmt::Display for AnimationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AnimationKind::Turntable => write!(f, "Turntable"),
+            AnimationKind::Flythrough => write!(f, "Flythrough"),
+        }
+    }
+}
+
+/// Represents a setting that can be keyframed over an exported sequence.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum AnimatedSetting {
+    /// Angle of the shadow light along its orbit, in degrees.
+    #[default]
+    LightAngle,
+    /// Opacity of the object with the given index.
+    Opacity(usize),
+    /// Position of the clipping plane with the given index, from -0.5 to 0.5 across the model.
+    ClipPlane(usize),
+}
+
+impl AnimatedSetting {
+    /// Returns the settings that can be keyframed in the given attributes.
+    pub fn all(attributes: &Attributes) -> Vec<AnimatedSetting> {
+        std::iter::once(AnimatedSetting::LightAngle)
+            .chain((0..attributes.mesh_opacity.len()).map(AnimatedSetting::Opacity))
+            .chain((0..attributes.clip_planes.len()).map(AnimatedSetting::ClipPlane))
+            .collect()
+    }
+
+    /// Returns the current value, or `None` if the object or plane doesn't exist.
+    pub fn get(self, attributes: &Attributes) -> Option<f32> {
+        match self {
+            AnimatedSetting::LightAngle => Some(attributes.light_orbit_angle),
+            AnimatedSetting::Opacity(i) => attributes.mesh_opacity.get(i).copied(),
+            AnimatedSetting::ClipPlane(i) => attributes.clip_planes.get(i).map(|p| p.offset),
+        }
+    }
+
+    /// Sets the value, unless the object or plane doesn't exist.
+    pub fn set(self, attributes: &mut Attributes, value: f32) {
+        match self {
+            AnimatedSetting::LightAngle => attributes.light_orbit_angle = value,
+            AnimatedSetting::Opacity(i) => {
+                if let Some(opacity) = attributes.mesh_opacity.get_mut(i) {
+                    *opacity = value.clamp(0.0, 1.0);
+                }
+            }
+            AnimatedSetting::ClipPlane(i) => {
+                if let Some(plane) = attributes.clip_planes.get_mut(i) {
+                    plane.offset = value.clamp(-0.5, 0.5);
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for AnimatedSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AnimatedSetting::LightAngle => write!(f, "Light angle"),
+            AnimatedSetting::Opacity(i) => write!(f, "Object {} opacity", i + 1),
+            AnimatedSetting::ClipPlane(i) => write!(f, "Clip plane {} position", i + 1),
+        }
+    }
+}
+
+/// Keyframed values of a setting. Values are interpolated linearly between keyframes, and held
+/// before the first and after the last.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AttributeTrack {
+    pub setting: AnimatedSetting,
+    /// Frame numbers and the values at them, sorted by frame.
+    pub keys: Vec<(u32, f32)>,
+}
+
+impl AttributeTrack {
+    /// Returns the value at a frame, or `None` without keyframes.
+    pub fn value_at(&self, frame: u32) -> Option<f32> {
+        let next = self.keys.partition_point(|&(key, _)| key <= frame);
+        match (
+            next.checked_sub(1).map(|i| self.keys[i]),
+            self.keys.get(next),
+        ) {
+            (Some((from, a)), Some(&(to, b))) => {
+                let t = (frame - from) as f32 / (to - from) as f32;
+                Some(a + (b - a) * t)
+            }
+            (Some((_, value)), None) | (None, Some(&(_, value))) => Some(value),
+            (None, None) => None,
+        }
+    }
+
+    /// Sorts the keyframes by frame, keeping the last of any at the same frame.
+    pub fn sort(&mut self) {
+        self.keys.reverse();
+        self.keys.sort_by_key(|&(frame, _)| frame);
+        self.keys.dedup_by_key(|&mut (frame, _)| frame);
+    }
+}
+
+/// Describes an image sequence to export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceSettings {
+    pub kind: AnimationKind,
+    pub frames: u32,
+    /// Frames per second, which sets the time step animations advance by between frames.
+    pub fps: u32,
+    /// Width and height in pixels.
+    pub size: (u32, u32),
+    pub transparent: bool,
+    /// Folder the sequence is saved in, each in a folder of its own.
+    pub folder: String,
+    /// Also pipe the frames into ffmpeg, to make an mp4 video next to them.
+    pub encode_video: bool,
+    /// Settings changing over the sequence.
+    pub tracks: Vec<AttributeTrack>,
+}
+
+impl Default for SequenceSettings {
+    fn default() -> Self {
+        Self {
+            kind: AnimationKind::Turntable,
+            frames: 240,
+            fps: 30,
+            size: (1920, 1080),
+            transparent: false,
+            folder: "animations".to_owned(),
+            encode_video: false,
+            tracks: Vec::new(),
+        }
+    }
+}
+
+/// Where the camera is for a frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CameraPose {
+    /// A viewpoint for [crate::camera::Camera::set_state].
+    Orbit(CameraState),
+    /// A position and orientation for [crate::camera::Camera::fly_to].
+    Flight(Point3<f32>, UnitQuaternion<f32>),
+}
+
+/// An image sequence being exported, one frame for each pass of the render loop.
+pub struct SequenceExport {
+    pub settings: SequenceSettings,
+    /// Frame to be rendered next.
+    pub frame: u32,
+    /// Viewpoint the turntable starts from, which the camera returns to afterwards.
+    start: CameraState,
+    /// Values the keyframed settings had before the export, restored afterwards.
+    original_values: Vec<(AnimatedSetting, f32)>,
+    flight: Option<Flythrough>,
+    /// Animation time of the first frame, in milliseconds.
+    start_ms: f32,
+    /// Folder the frames are written to.
+    folder: PathBuf,
+    encoder: Option<VideoEncoder>,
+}
+
+impl SequenceExport {
+    /// Starts an export into a new folder named `name` inside the chosen folder. `start` is the
+    /// camera's viewpoint, `attributes` the current settings and `start_ms` the animation time,
+    /// which advances by a fixed step per frame from there.
+    pub fn new(
+        mut settings: SequenceSettings,
+        name: &str,
+        start: CameraState,
+        attributes: &Attributes,
+        flight: Option<&Flythrough>,
+        start_ms: f32,
+    ) -> Result<Self> {
+        if settings.frames == 0 || settings.fps == 0 {
+            return Err(anyhow!(
+                "The sequence needs at least one frame, at one frame per second or more."
+            ));
+        }
+        let flight = match settings.kind {
+            AnimationKind::Turntable => None,
+            AnimationKind::Flythrough => Some(
+                flight
+                    .ok_or_else(|| anyhow!("Load a centerline or record keyframes to fly along."))?
+                    .clone(),
+            ),
+        };
+        for track in &mut settings.tracks {
+            track.sort();
+        }
+        let original_values = settings
+            .tracks
+            .iter()
+            .filter_map(|track| Some((track.setting, track.setting.get(attributes)?)))
+            .collect();
+        let folder = unique_folder(Path::new(&settings.folder), name);
+        fs::create_dir_all(&folder).context("Failed to create the sequence folder")?;
+        let encoder = if settings.encode_video {
+            let video = folder.with_extension("mp4");
+            Some(VideoEncoder::start(&video, settings.fps, settings.size)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            settings,
+            frame: 0,
+            start,
+            original_values,
+            flight,
+            start_ms,
+            folder,
+            encoder,
+        })
+    }
+
+    /// Time between frames.
+    pub fn frame_time(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.settings.fps as f64)
+    }
+
+    /// Animation time of the next frame, in milliseconds.
+    pub fn elapsed_ms(&self) -> f32 {
+        self.start_ms + self.frame as f32 * 1000.0 / self.settings.fps as f32
+    }
+
+    /// Camera pose for the next frame.
+    pub fn camera_pose(&self) -> CameraPose {
+        let t = progress(self.settings.kind, self.frame, self.settings.frames);
+        match &self.flight {
+            Some(flight) => {
+                let (eye, orientation) = flight.path.pose(
+                    flight.path.length() * t,
+                    flight.orientation,
+                    flight.look_ahead,
+                );
+                CameraPose::Flight(eye, orientation)
+            }
+            None => CameraPose::Orbit(turntable(&self.start, t)),
+        }
+    }
+
+    /// Sets the keyframed settings to their values for the next frame.
+    pub fn animate(&self, attributes: &mut Attributes) {
+        for track in &self.settings.tracks {
+            if let Some(value) = track.value_at(self.frame) {
+                track.setting.set(attributes, value);
+            }
+        }
+    }
+
+    /// Saves the next frame and passes it on to ffmpeg if a video is being made.
+    pub fn add_frame(&mut self, image: &Image, text: &[(&str, String)]) -> Result<()> {
+        let path = self
+            .folder
+            .join(frame_file_name(self.frame, self.settings.frames));
+        capture::save_png(&path, image, text)?;
+        if let Some(encoder) = self.encoder.as_mut() {
+            encoder.write(image)?;
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// Returns true once every frame has been added.
+    pub fn is_done(&self) -> bool {
+        self.frame >= self.settings.frames
+    }
+
+    /// Puts the camera and the keyframed settings back as they were before the export, waits for
+    /// the video to be written if one is being made, and describes where the sequence was saved.
+    pub fn finish(
+        self,
+        camera: &mut Camera,
+        attributes: Option<&mut Attributes>,
+    ) -> Result<String> {
+        camera.set_state(&self.start);
+        if let Some(attributes) = attributes {
+            for &(setting, value) in &self.original_values {
+                setting.set(attributes, value);
+            }
+        }
+        let mut saved = format!("Saved {} frames to {}", self.frame, self.folder.display());
+        if let Some(encoder) = self.encoder {
+            let video = encoder.finish()?;
+            saved.push_str(&format!(" and {}", video.display()));
+        }
+        Ok(saved)
+    }
+}
+
+/// Returns how far through the animation a frame is, from 0 to 1. A turntable stops one step
+/// short of a full turn, so the sequence loops without repeating a frame.
+fn progress(kind: AnimationKind, frame: u32, frames: u32) -> f32 {
+    match kind {
+        AnimationKind::Turntable => frame as f32 / frames.max(1) as f32,
+        AnimationKind::Flythrough if frames > 1 => frame as f32 / (frames - 1) as f32,
+        AnimationKind::Flythrough => 0.0,
+    }
+}
+
+/// Returns the viewpoint turned the fraction `t` of a full turn around the vertical axis through
+/// its target.
+fn turntable(start: &CameraState, t: f32) -> CameraState {
+    let turn = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), t * std::f32::consts::TAU);
+    CameraState {
+        orientation: turn * start.orientation,
+        ..*start
+    }
+}
+
+/// Returns the file name of a frame, numbered with enough digits for the whole sequence so the
+/// files sort in order.
+fn frame_file_name(frame: u32, frames: u32) -> String {
+    let digits = frames.saturating_sub(1).to_string().len().max(4);
+    format!("frame_{:0digits$}.png", frame, digits = digits)
+}
+
+/// Returns a path in `folder` for a new folder named after `name`, numbered if needed so no
+/// existing sequence is added to.
+fn unique_folder(folder: &Path, name: &str) -> PathBuf {
+    let path = folder.join(name);
+    if !path.exists() {
+        return path;
+    }
+    (2..)
+        .map(|i| folder.join(format!("{}_{}", name, i)))
+        .find(|path| !path.exists() && !path.with_extension("mp4").exists())
+        .unwrap_or(path)
+}
+
+/// Returns true if an `ffmpeg` binary can be run from the search path.
+pub fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// An ffmpeg process encoding raw RGBA frames written to its input into an H.264 video.
+struct VideoEncoder {
+    process: Child,
+    path: PathBuf,
+}
+
+impl VideoEncoder {
+    fn start(path: &Path, fps: u32, (w, h): (u32, u32)) -> Result<Self> {
+        let process = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-video_size", &format!("{}x{}", w, h)])
+            .args(["-framerate", &fps.to_string()])
+            .args(["-i", "-"])
+            // H.264 in yuv420p needs even dimensions.
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+            .args(["-c:v", "libx264", "-pix_fmt", "yuv420p", "-crf", "18"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .context("Failed to start ffmpeg")?;
+        Ok(Self {
+            process,
+            path: path.to_owned(),
+        })
+    }
+
+    fn write(&mut self, image: &Image) -> Result<()> {
+        self.process
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("ffmpeg's input is closed."))?
+            .write_all(&image.pixels)
+            .context("ffmpeg stopped accepting frames")
+    }
+
+    /// Closes ffmpeg's input and waits for it to finish the video, returning its path.
+    fn finish(mut self) -> Result<PathBuf> {
+        drop(self.process.stdin.take());
+        let status = self.process.wait().context("Failed to wait for ffmpeg")?;
+        if !status.success() {
+            return Err(anyhow!("ffmpeg failed to encode the video ({}).", status));
+        }
+        Ok(self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turntable_turns_once_around_target() {
+        let start = CameraState {
+            target: Point3::new(1.0, 2.0, 3.0),
+            orientation: UnitQuaternion::from_euler_angles(0.3, 0.2, 0.0),
+            dist: 5.0,
+        };
+        let half = turntable(&start, progress(AnimationKind::Turntable, 50, 100));
+        assert_eq!((half.target, half.dist), (start.target, start.dist));
+        let (forward, half_forward) = (
+            start.orientation * Vector3::z(),
+            half.orientation * Vector3::z(),
+        );
+        assert!((half_forward.y - forward.y).abs() < 1e-5);
+        assert!((half_forward.xz() + forward.xz()).norm() < 1e-5);
+        let end = turntable(&start, progress(AnimationKind::Turntable, 100, 100));
+        assert!(end.orientation.angle_to(&start.orientation) < 1e-3);
+    }
+
+    #[test]
+    fn frames_span_the_animation() {
+        assert_eq!(progress(AnimationKind::Flythrough, 0, 10), 0.0);
+        assert_eq!(progress(AnimationKind::Flythrough, 9, 10), 1.0);
+        assert_eq!(progress(AnimationKind::Flythrough, 0, 1), 0.0);
+        assert_eq!(progress(AnimationKind::Turntable, 9, 10), 0.9);
+        assert_eq!(frame_file_name(7, 240), "frame_0007.png");
+        assert_eq!(frame_file_name(12, 100_000), "frame_00012.png");
+    }
+
+    #[test]
+    fn keyframes_are_interpolated() {
+        let mut track = AttributeTrack {
+            setting: AnimatedSetting::Opacity(1),
+            keys: vec![(20, 0.0), (10, 1.0), (20, 0.2)],
+        };
+        track.sort();
+        assert_eq!(track.keys, vec![(10, 1.0), (20, 0.2)]);
+        assert_eq!(track.value_at(0), Some(1.0));
+        assert_eq!(track.value_at(15), Some(0.6));
+        assert_eq!(track.value_at(20), Some(0.2));
+        assert_eq!(track.value_at(99), Some(0.2));
+        assert_eq!(AttributeTrack::default().value_at(5), None);
+
+        let mut attributes = Attributes {
+            mesh_opacity: vec![1.0, 1.0],
+            ..Default::default()
+        };
+        track.setting.set(&mut attributes, 0.6);
+        assert_eq!(attributes.mesh_opacity, vec![1.0, 0.6]);
+        AnimatedSetting::Opacity(5).set(&mut attributes, 0.0);
+        assert_eq!(AnimatedSetting::Opacity(5).get(&attributes), None);
+    }
+
+    #[test]
+    fn finishing_restores_the_scene() {
+        let mut camera = Camera::new();
+        let start = camera.state();
+        let mut attributes = Attributes {
+            mesh_opacity: vec![1.0, 0.8],
+            ..Default::default()
+        };
+        let mut settings = SequenceSettings::default();
+        settings.tracks.push(AttributeTrack {
+            setting: AnimatedSetting::Opacity(1),
+            keys: vec![(0, 0.0), (10, 0.5)],
+        });
+        let export = SequenceExport {
+            frame: 10,
+            start,
+            original_values: vec![(AnimatedSetting::Opacity(1), 0.8)],
+            flight: None,
+            start_ms: 0.0,
+            folder: PathBuf::new(),
+            encoder: None,
+            settings,
+        };
+        export.animate(&mut attributes);
+        camera.set_state(&turntable(&start, 0.3));
+        export.finish(&mut camera, Some(&mut attributes)).unwrap();
+        assert_eq!(attributes.mesh_opacity, vec![1.0, 0.8]);
+        assert!(camera.state().orientation.angle_to(&start.orientation) < 1e-5);
+    }
+}
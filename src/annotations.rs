@@ -0,0 +1,177 @@
+//! Contains [Annotation]s anchored to the model surface, and the JSON sidecar file they are saved
+//! to next to the model along with the model's [SavedView]s.
+
+use crate::{camera::CameraState, views::SavedView};
+use anyhow::{anyhow, Result};
+use na::{Point3, Vector3};
+use nalgebra as na;
+use serde::{Deserialize, Serialize};
+
+/// Ending of the sidecar file name, which replaces the model file's `.obj`.
+pub const SIDECAR_SUFFIX: &str = ".annotations.json";
+/// Version of the sidecar format written by [to_json]. Version 2 added the saved views, so older
+/// builds refuse these files rather than dropping the views when saving over them.
+const FORMAT_VERSION: u32 = 2;
+
+/// Represents how an annotation is shown.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationKind {
+    /// A marker on the surface with its name beside it.
+    #[default]
+    Landmark,
+    /// A text box connected to the surface by a line.
+    Label,
+}
+
+impl AnnotationKind {
+    pub const ALL: [AnnotationKind; 2] = [AnnotationKind::Landmark, AnnotationKind::Label];
+}
+
+impl std::fmt::Display for AnnotationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AnnotationKind::Landmark => write!(f, "Landmark"),
+            AnnotationKind::Label => write!(f, "Label"),
+        }
+    }
+}
+
+/// A named point on the model surface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub kind: AnnotationKind,
+    pub text: String,
+    /// Position in the model's coordinates, centered on the origin.
+    pub position: Point3<f32>,
+}
+
+/// An annotation as stored in the sidecar file, with its position in the obj file's coordinates
+/// so the file stays valid however the model is centered.
+#[derive(Serialize, Deserialize)]
+struct StoredAnnotation {
+    kind: AnnotationKind,
+    text: String,
+    position: [f32; 3],
+}
+
+/// A saved view as stored in the sidecar file, with its target in the obj file's coordinates.
+#[derive(Serialize, Deserialize)]
+struct StoredView {
+    name: String,
+    camera: CameraState,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    version: u32,
+    annotations: Vec<StoredAnnotation>,
+    /// Missing from version 1 files, written before views were saved with the model.
+    #[serde(default)]
+    views: Vec<StoredView>,
+}
+
+/// Writes annotations and saved views as JSON. `offset` is added to their positions, to store
+/// them in the coordinates of the model file.
+pub fn to_json(
+    annotations: &[Annotation],
+    views: &[SavedView],
+    offset: &Vector3<f32>,
+) -> Result<String> {
+    let sidecar = Sidecar {
+        version: FORMAT_VERSION,
+        annotations: annotations
+            .iter()
+            .map(|a| StoredAnnotation {
+                kind: a.kind,
+                text: a.text.clone(),
+                position: (a.position + offset).coords.into(),
+            })
+            .collect(),
+        views: views
+            .iter()
+            .map(|view| StoredView {
+                name: view.name.clone(),
+                camera: CameraState {
+                    target: view.state.target + offset,
+                    ..view.state
+                },
+            })
+            .collect(),
+    };
+    Ok(serde_json::to_string_pretty(&sidecar)?)
+}
+
+/// Reads annotations and saved views written by [to_json], subtracting `offset` from their
+/// positions.
+pub fn from_json(text: &str, offset: &Vector3<f32>) -> Result<(Vec<Annotation>, Vec<SavedView>)> {
+    let sidecar: Sidecar = serde_json::from_str(text)?;
+    if sidecar.version > FORMAT_VERSION {
+        return Err(anyhow!(
+            "The annotations were saved by a newer version (format {}).",
+            sidecar.version
+        ));
+    }
+    let annotations = sidecar
+        .annotations
+        .into_iter()
+        .map(|a| Annotation {
+            kind: a.kind,
+            text: a.text,
+            position: Point3::from(a.position) - offset,
+        })
+        .collect();
+    let views = sidecar
+        .views
+        .into_iter()
+        .map(|view| SavedView {
+            name: view.name,
+            state: CameraState {
+                target: view.camera.target - offset,
+                ..view.camera
+            },
+        })
+        .collect();
+    Ok((annotations, views))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar_round_trip() {
+        let annotations = vec![
+            Annotation {
+                kind: AnnotationKind::Landmark,
+                text: "Bifurcation".to_owned(),
+                position: Point3::new(1.0, 2.0, 3.0),
+            },
+            Annotation {
+                kind: AnnotationKind::Label,
+                text: "Narrowing \"here\"".to_owned(),
+                position: Point3::new(-1.5, 0.0, 0.25),
+            },
+        ];
+        let views = vec![SavedView {
+            name: "Figure 2".to_owned(),
+            state: CameraState {
+                target: Point3::new(0.5, 0.0, 0.0),
+                orientation: na::UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3),
+                dist: 4.0,
+            },
+        }];
+        let offset = Vector3::new(10.0, 0.0, -5.0);
+        let json = to_json(&annotations, &views, &offset).unwrap();
+        let stored: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(stored["version"], 2);
+        assert_eq!(stored["annotations"][0]["position"][0], 11.0);
+        assert_eq!(stored["annotations"][1]["kind"], "label");
+        assert_eq!(stored["views"][0]["camera"]["target"][0], 10.5);
+        assert_eq!(from_json(&json, &offset).unwrap(), (annotations, views));
+        let (_, no_views) = from_json("{\"version\": 1, \"annotations\": []}", &offset).unwrap();
+        assert!(no_views.is_empty());
+        assert!(from_json("{\"version\": 99, \"annotations\": []}", &offset).is_err());
+        assert!(from_json("[]", &offset).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Contains [SceneBuffer], an offscreen target the model is rendered into before the UI, so the
+//! image can be anti-aliased either by multisampling or with FXAA as a post-process.
+
+use crate::{
+    render_gl::{
+        buffer::{FrameBuffer, Texture},
+        FullscreenPass, Viewport,
+    },
+    resources::Resources,
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+const SHADER_PATH: &str = "shaders/fxaa";
+const TEXTURE_UNIT_SCENE: gl::types::GLenum = gl::TEXTURE0;
+
+/// Represents how the rendered model is anti-aliased.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum AntiAliasing {
+    Off = 0,
+    /// Multisampled rendering, resolved before the UI is drawn.
+    #[default]
+    Msaa = 1,
+    /// Fast approximate anti-aliasing, as a post-process on the finished image.
+    Fxaa = 2,
+}
+
+impl AntiAliasing {
+    pub const ALL: [AntiAliasing; 3] = [AntiAliasing::Off, AntiAliasing::Msaa, AntiAliasing::Fxaa];
+}
+
+impl std::fmt::Display for AntiAliasing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AntiAliasing::Off => write!(f, "Off"),
+            AntiAliasing::Msaa => write!(f, "Multisampling (MSAA)"),
+            AntiAliasing::Fxaa => write!(f, "FXAA"),
+        }
+    }
+}
+
+/// Holds the offscreen render targets for anti-aliasing. The targets are sized to the viewport
+/// and reallocated when it or the sample count changes.
+pub struct SceneBuffer {
+    pass: FullscreenPass,
+    msaa_fbo: FrameBuffer,
+    msaa_color: Texture,
+    msaa_depth: Texture,
+    /// Size and sample count of the multisampled targets, and whether they could be used.
+    msaa_allocation: ((u32, u32), u32, bool),
+    fxaa_fbo: FrameBuffer,
+    fxaa_color: Texture,
+    fxaa_depth: Texture,
+    fxaa_size: (u32, u32),
+    /// Anti-aliasing in use for the current frame.
+    mode: AntiAliasing,
+    /// Framebuffer bound when the frame began, which the scene is resolved into.
+    target: gl::types::GLuint,
+}
+
+impl SceneBuffer {
+    /// Set up [SceneBuffer], compiling shaders and creating the framebuffers.
+    pub fn new(res: &Resources) -> Result<Self> {
+        let value = Self {
+            pass: FullscreenPass::new(res, SHADER_PATH)?,
+            msaa_fbo: FrameBuffer::new(),
+            msaa_color: Texture::with_target(TEXTURE_UNIT_SCENE, gl::TEXTURE_2D_MULTISAMPLE),
+            msaa_depth: Texture::with_target(TEXTURE_UNIT_SCENE, gl::TEXTURE_2D_MULTISAMPLE),
+            msaa_allocation: ((0, 0), 0, false),
+            fxaa_fbo: FrameBuffer::new(),
+            fxaa_color: Texture::new(TEXTURE_UNIT_SCENE),
+            fxaa_depth: Texture::new(TEXTURE_UNIT_SCENE),
+            fxaa_size: (0, 0),
+            mode: AntiAliasing::Off,
+            target: 0,
+        };
+        Ok(value)
+    }
+
+    /// Returns the largest multisampling sample count the driver supports.
+    pub fn max_samples() -> u32 {
+        let mut samples: gl::types::GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_SAMPLES, &mut samples);
+        }
+        samples.max(1) as u32
+    }
+
+    /// Binds the framebuffer the scene should be drawn into for the given anti-aliasing mode,
+    /// reallocating targets if needed. The framebuffer bound before is the one the scene ends up
+    /// in. Returns the mode in use, which falls back to
+    /// [AntiAliasing::Fxaa] if the multisampled target can't be created.
+    pub fn begin(&mut self, viewport: &Viewport, mode: AntiAliasing, samples: u32) -> AntiAliasing {
+        let size = viewport.size();
+        let dimensions = (size.0 as i32, size.1 as i32);
+        let mut mode = mode;
+        self.target = FrameBuffer::bound_id();
+
+        if mode == AntiAliasing::Msaa {
+            let samples = samples.clamp(1, Self::max_samples());
+            if (self.msaa_allocation.0, self.msaa_allocation.1) != (size, samples) {
+                self.msaa_color
+                    .load_texture_multisample(dimensions, samples as i32, gl::RGBA8);
+                self.msaa_depth.load_texture_multisample(
+                    dimensions,
+                    samples as i32,
+                    gl::DEPTH32F_STENCIL8,
+                );
+                self.msaa_depth.unbind();
+                self.msaa_fbo
+                    .bind_texture(gl::COLOR_ATTACHMENT0, &self.msaa_color);
+                self.msaa_fbo
+                    .bind_texture(gl::DEPTH_STENCIL_ATTACHMENT, &self.msaa_depth);
+                let complete = self.msaa_fbo.is_complete();
+                if !complete {
+                    eprintln!("Multisampled framebuffer is incomplete, falling back to FXAA.");
+                }
+                self.msaa_fbo.unbind();
+                self.msaa_allocation = (size, samples, complete);
+            }
+            if !self.msaa_allocation.2 {
+                mode = AntiAliasing::Fxaa;
+            }
+        }
+
+        if mode == AntiAliasing::Fxaa && self.fxaa_size != size {
+            self.fxaa_color.load_texture(
+                dimensions,
+                None,
+                gl::RGBA8 as gl::types::GLint,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                false,
+            );
+            self.fxaa_color.set_filter(gl::LINEAR);
+            self.fxaa_depth.load_texture(
+                dimensions,
+                None,
+                gl::DEPTH32F_STENCIL8 as gl::types::GLint,
+                gl::DEPTH_STENCIL,
+                gl::FLOAT_32_UNSIGNED_INT_24_8_REV,
+                false,
+            );
+            self.fxaa_depth.unbind();
+            self.fxaa_fbo
+                .bind_texture(gl::COLOR_ATTACHMENT0, &self.fxaa_color);
+            self.fxaa_fbo
+                .bind_texture(gl::DEPTH_STENCIL_ATTACHMENT, &self.fxaa_depth);
+            self.fxaa_fbo.unbind();
+            self.fxaa_size = size;
+        }
+
+        match mode {
+            AntiAliasing::Off => FrameBuffer::bind_id(self.target),
+            AntiAliasing::Msaa => self.msaa_fbo.bind(),
+            AntiAliasing::Fxaa => self.fxaa_fbo.bind(),
+        }
+        viewport.set_used();
+        self.mode = mode;
+        mode
+    }
+
+    /// Resolves the scene drawn since [SceneBuffer::begin] into the framebuffer that was bound
+    /// then, which is left bound.
+    ///
+    /// ### Safety
+    ///
+    /// Should only be called after [SceneBuffer::begin], with the same viewport.
+    pub unsafe fn finish(&self, viewport: &Viewport) {
+        let size = viewport.size();
+        match self.mode {
+            AntiAliasing::Off => {}
+            AntiAliasing::Msaa => self
+                .msaa_fbo
+                .blit_to(self.target, (size.0 as i32, size.1 as i32)),
+            AntiAliasing::Fxaa => {
+                FrameBuffer::bind_id(self.target);
+                viewport.set_used();
+                self.pass.program().set_used();
+                self.fxaa_color.bind();
+                gl::Disable(gl::DEPTH_TEST);
+                gl::Disable(gl::BLEND);
+                self.pass.draw();
+                gl::Enable(gl::DEPTH_TEST);
+                self.fxaa_color.unbind();
+                self.pass.program().unset_used();
+            }
+        }
+    }
+
+    /// Check if the shader has been updated.
+    pub fn check_shader_update(&mut self, path: &std::path::Path, res: &Resources) -> bool {
+        self.pass.check_shader_update(path, res)
+    }
+}
@@ -0,0 +1,276 @@
+//! Contains the batch rendering started with `--batch <job file>`, which renders a list of models
+//! with the same settings and cameras into PNG files, through a [HeadlessContext] instead of a
+//! window. None of the UI is set up in this mode.
+//!
+//! The job file is JSON, where relative paths are relative to the job file:
+//!
+//! ```json
+//! {
+//!     "models": ["cases/case001.obj", "/data/case002.obj"],
+//!     "settings": "settings.json",
+//!     "views": ["saved", "anterior", "left"],
+//!     "size": [3840, 2160],
+//!     "output": "renders"
+//! }
+//! ```
+
+use crate::{
+    camera::{Camera, CameraState},
+    capture::{self, CaptureSettings, OffscreenTarget},
+    headless::HeadlessContext,
+    model::Model,
+    resources::Resources,
+    settings::SettingsFile,
+    views::{AnatomicalView, PatientFrame},
+};
+use anyhow::{anyhow, Context, Result};
+use nalgebra as na;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Describes a batch of models to render.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchJob {
+    pub models: Vec<PathBuf>,
+    /// Settings file saved from the application, or an image captured with it. The defaults are
+    /// used without one.
+    #[serde(default)]
+    pub settings: Option<PathBuf>,
+    /// Cameras each model is rendered from. Without any, the camera saved in the settings is
+    /// used if there is one, and the starting view otherwise.
+    #[serde(default)]
+    pub views: Vec<ViewSpec>,
+    /// Model axes the anatomical views are defined by.
+    #[serde(default)]
+    pub patient_frame: PatientFrame,
+    #[serde(default = "default_size")]
+    pub size: (u32, u32),
+    #[serde(default)]
+    pub transparent: bool,
+    /// Scale of lines and hatching drawn in screen space, like the display scale in the
+    /// application.
+    #[serde(default = "default_pixels_per_point")]
+    pub pixels_per_point: f32,
+    /// Folder the images are written to, named after the model and the view.
+    #[serde(default = "default_output")]
+    pub output: PathBuf,
+}
+
+fn default_size() -> (u32, u32) {
+    (1920, 1080)
+}
+
+fn default_pixels_per_point() -> f32 {
+    1.0
+}
+
+fn default_output() -> PathBuf {
+    PathBuf::from("renders")
+}
+
+/// A camera to render from: `"home"` for the starting view, `"saved"` for the camera in the
+/// settings file, the name of an anatomical view like `"anterior"`, or a full camera state.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ViewSpec {
+    Named(String),
+    Camera(CameraState),
+}
+
+impl BatchJob {
+    /// Reads a job file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).context("Failed to read the job file")?;
+        Self::parse(&text, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Parses a job, making the paths in it relative to `folder` instead.
+    fn parse(text: &str, folder: &Path) -> Result<Self> {
+        let mut job: BatchJob = serde_json::from_str(text).context("Invalid job file")?;
+        for model in &mut job.models {
+            *model = folder.join(&*model);
+        }
+        job.settings = job.settings.map(|settings| folder.join(settings));
+        job.output = folder.join(&job.output);
+        Ok(job)
+    }
+
+    /// Returns the names and specifications of the views to render.
+    fn views(&self, settings: &SettingsFile) -> Vec<(String, ViewSpec)> {
+        if self.views.is_empty() {
+            let name = if settings.camera.is_some() {
+                "saved"
+            } else {
+                "home"
+            };
+            return vec![(name.to_owned(), ViewSpec::Named(name.to_owned()))];
+        }
+        self.views
+            .iter()
+            .enumerate()
+            .map(|(i, view)| match view {
+                ViewSpec::Named(name) => (name.to_lowercase(), view.clone()),
+                ViewSpec::Camera(_) => (format!("view{}", i + 1), view.clone()),
+            })
+            .collect()
+    }
+}
+
+/// Returns the camera state a view asks for, given a camera set up for the model.
+fn view_state(
+    view: &ViewSpec,
+    camera: &Camera,
+    settings: &SettingsFile,
+    frame: &PatientFrame,
+) -> Result<CameraState> {
+    let name = match view {
+        ViewSpec::Camera(state) => return Ok(*state),
+        ViewSpec::Named(name) => name.to_lowercase(),
+    };
+    match name.as_str() {
+        "home" => Ok(camera.home_state()),
+        "saved" => settings
+            .camera
+            .ok_or_else(|| anyhow!("The settings file has no saved camera.")),
+        _ => {
+            let view = AnatomicalView::ALL
+                .into_iter()
+                .find(|view| view.to_string().to_lowercase() == name)
+                .ok_or_else(|| anyhow!("Unknown view \"{}\".", name))?;
+            let orientation = frame
+                .orientation(view)
+                .ok_or_else(|| anyhow!("The patient frame's axes must be perpendicular."))?;
+            Ok(CameraState {
+                orientation,
+                ..camera.home_state()
+            })
+        }
+    }
+}
+
+/// Renders every model in the job file, carrying on past models that fail. Returns an error if
+/// the job can't be started or any model failed.
+pub fn run(res: &Resources, job_file: &Path) -> Result<()> {
+    let job = BatchJob::load(job_file)?;
+    let settings = match &job.settings {
+        Some(path) => SettingsFile::load(path).context("Invalid settings file")?,
+        None => SettingsFile::default(),
+    };
+    let views = job.views(&settings);
+
+    let context = HeadlessContext::new()?;
+    eprintln!("Rendering through {}.", context.backend());
+    let mut offscreen = OffscreenTarget::new(res)?;
+    fs::create_dir_all(&job.output).context("Failed to create the output folder")?;
+
+    let mut failed = 0;
+    for (i, path) in job.models.iter().enumerate() {
+        eprintln!("[{}/{}] {}", i + 1, job.models.len(), path.display());
+        if let Err(e) = render_model(res, &mut offscreen, &job, &settings, &views, path) {
+            eprintln!("Couldn't render {}:", path.display());
+            eprintln!("{:#}", e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!("{} of {} models failed.", failed, job.models.len()));
+    }
+    Ok(())
+}
+
+/// Loads a model, applies the settings and renders it from each view.
+fn render_model(
+    res: &Resources,
+    offscreen: &mut OffscreenTarget,
+    job: &BatchJob,
+    settings: &SettingsFile,
+    views: &[(String, ViewSpec)],
+    path: &Path,
+) -> Result<()> {
+    let absolute = fs::canonicalize(path).context("Failed to find the model")?;
+    let source = absolute.to_string_lossy();
+    let mut model = Model::new(res, &source)?;
+    model.set_attributes(settings.apply(model.get_attributes()));
+
+    let mut camera = Camera::new();
+    camera.set_screen_size(job.size);
+    camera.set_scene_radius(model.get_size().magnitude() / 2.0);
+    camera.set_projection(settings.projection);
+    let capture = CaptureSettings {
+        size: job.size,
+        transparent: job.transparent,
+        anti_aliasing: settings.anti_aliasing,
+        msaa_samples: settings.msaa_samples,
+        pixels_per_point: job.pixels_per_point,
+    };
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "model".into(), |stem| stem.to_string_lossy());
+
+    for (name, view) in views {
+        camera.set_state(&view_state(view, &camera, settings, &job.patient_frame)?);
+        let image = offscreen.render(
+            &mut model,
+            &camera,
+            na::Isometry3::identity(),
+            None,
+            &capture,
+            &settings.background.into(),
+            |_| {},
+        )?;
+        let used = SettingsFile {
+            attributes: model.get_attributes().clone(),
+            camera: Some(camera.state()),
+            ..settings.clone()
+        };
+        let text = capture::metadata(&source, &used)?;
+        capture::save_png(
+            &job.output.join(format!("{}_{}.png", stem, name)),
+            &image,
+            &text,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_file_paths_and_views() {
+        let dir = Path::new("jobs");
+        let job = BatchJob::parse(
+            r#"{
+                "models": ["a.obj", "/data/b.obj"],
+                "views": ["Anterior", {"target": [0, 0, 0], "orientation": [0, 0, 0, 1], "dist": 2}],
+                "size": [640, 480]
+            }"#,
+            dir,
+        )
+        .unwrap();
+        assert_eq!(
+            job.models,
+            vec![dir.join("a.obj"), PathBuf::from("/data/b.obj")]
+        );
+        assert_eq!(job.output, dir.join("renders"));
+        assert_eq!(job.size, (640, 480));
+
+        let views = job.views(&SettingsFile::default());
+        assert_eq!(views[0].0, "anterior");
+        assert_eq!(views[1].0, "view2");
+        let camera = Camera::new();
+        let frame = PatientFrame::default();
+        let state = view_state(&views[1].1, &camera, &SettingsFile::default(), &frame).unwrap();
+        assert_eq!(state.dist, 2.0);
+        let anterior = view_state(&views[0].1, &camera, &SettingsFile::default(), &frame);
+        assert!(anterior.is_ok());
+        let saved = ViewSpec::Named("saved".to_owned());
+        assert!(view_state(&saved, &camera, &SettingsFile::default(), &frame).is_err());
+        assert!(BatchJob::parse(r#"{"models": [], "size": 3}"#, dir).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+//! Contains a bounding volume hierarchy over the triangles of a mesh, which answers ray queries
+//! without testing every triangle.
+
+use crate::geometry::intersect_ray_triangle;
+use na::{Point3, Vector3};
+use nalgebra as na;
+
+/// Most triangles a leaf holds before it is split.
+const LEAF_SIZE: usize = 4;
+
+/// Axis aligned bounding box.
+#[derive(Copy, Clone, Debug)]
+struct Bounds {
+    min: Point3<f32>,
+    max: Point3<f32>,
+}
+
+impl Bounds {
+    fn empty() -> Self {
+        Self {
+            min: Point3::from(Vector3::from_element(f32::MAX)),
+            max: Point3::from(Vector3::from_element(f32::MIN)),
+        }
+    }
+
+    fn grow(&mut self, point: &Point3<f32>) {
+        self.min = self.min.inf(point);
+        self.max = self.max.sup(point);
+    }
+
+    /// Returns the distance along the ray where it enters the box, if it hits before `limit`.
+    /// `inverse_direction` is the componentwise reciprocal of the ray direction.
+    fn entry(
+        &self,
+        origin: &Point3<f32>,
+        inverse_direction: &Vector3<f32>,
+        limit: f32,
+    ) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, limit);
+        for axis in 0..3 {
+            let t1 = (self.min[axis] - origin[axis]) * inverse_direction[axis];
+            let t2 = (self.max[axis] - origin[axis]) * inverse_direction[axis];
+            // A NaN from a zero direction component along a box face leaves the range unchanged.
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+/// A node covers the triangles `first..first + count` in [Bvh::triangles] when it is a leaf, and
+/// otherwise has its children at `first` and `first + 1` in [Bvh::nodes].
+#[derive(Clone, Debug)]
+struct Node {
+    bounds: Bounds,
+    first: usize,
+    count: usize,
+}
+
+/// A ray's nearest accepted intersection with a mesh.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    /// Index of the triangle, counting triangles in the order of the mesh's index list.
+    pub triangle: usize,
+    /// Distance along the ray in units of its direction.
+    pub distance: f32,
+}
+
+/// Bounding volume hierarchy over a triangle mesh, splitting nodes at the median triangle along
+/// their longest axis. It keeps its own copy of the triangles, so it doesn't depend on the mesh
+/// after it is built.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// Corners of each triangle, in the order the leaves refer to them.
+    triangles: Vec<[Point3<f32>; 3]>,
+    /// Index of each triangle in the original mesh.
+    ids: Vec<usize>,
+}
+
+impl Bvh {
+    /// Builds the hierarchy over the triangles given by every three indices into `positions`.
+    pub fn new(positions: &[Point3<f32>], indices: &[u32]) -> Self {
+        let triangles: Vec<[Point3<f32>; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| [0, 1, 2].map(|k| positions[t[k] as usize]))
+            .collect();
+        let centroids: Vec<Point3<f32>> = triangles
+            .iter()
+            .map(|[a, b, c]| Point3::from((a.coords + b.coords + c.coords) / 3.0))
+            .collect();
+        let mut ids: Vec<usize> = (0..triangles.len()).collect();
+        let mut nodes = vec![Node {
+            bounds: Bounds::empty(),
+            first: 0,
+            count: ids.len(),
+        }];
+
+        // Nodes are split breadth first, so both children of a node end up next to each other.
+        let mut index = 0;
+        while index < nodes.len() {
+            let Node { first, count, .. } = nodes[index];
+            let members = &mut ids[first..first + count];
+            let mut bounds = Bounds::empty();
+            let mut centroid_bounds = Bounds::empty();
+            for &id in members.iter() {
+                triangles[id].iter().for_each(|p| bounds.grow(p));
+                centroid_bounds.grow(&centroids[id]);
+            }
+            nodes[index].bounds = bounds;
+            if count > LEAF_SIZE {
+                let extent = centroid_bounds.max - centroid_bounds.min;
+                let axis = extent.imax();
+                let half = count / 2;
+                members.select_nth_unstable_by(half, |&a, &b| {
+                    centroids[a][axis].total_cmp(&centroids[b][axis])
+                });
+                nodes[index].first = nodes.len();
+                nodes[index].count = 0;
+                nodes.push(Node {
+                    bounds,
+                    first,
+                    count: half,
+                });
+                nodes.push(Node {
+                    bounds,
+                    first: first + half,
+                    count: count - half,
+                });
+            }
+            index += 1;
+        }
+
+        let triangles = ids.iter().map(|&id| triangles[id]).collect();
+        Self {
+            nodes,
+            triangles,
+            ids,
+        }
+    }
+
+    /// Returns the bounds of the whole mesh as its minimum and maximum corners.
+    #[cfg(test)]
+    fn bounds(&self) -> (Point3<f32>, Point3<f32>) {
+        let bounds = self.nodes[0].bounds;
+        (bounds.min, bounds.max)
+    }
+
+    /// Finds the nearest triangle the ray hits, among the hits `accept` approves of. It is called
+    /// with the triangle index and distance of each hit, nearer than the best found so far.
+    pub fn cast(
+        &self,
+        origin: &Point3<f32>,
+        direction: &Vector3<f32>,
+        mut accept: impl FnMut(usize, f32) -> bool,
+    ) -> Option<RayHit> {
+        let mut nearest: Option<RayHit> = None;
+        self.traverse(origin, direction, |triangle, distance| {
+            if accept(triangle, distance) {
+                nearest = Some(RayHit { triangle, distance });
+                Some(distance)
+            } else {
+                None
+            }
+        });
+        nearest
+    }
+
+    /// Counts the triangles the ray crosses, among those `accept` approves of by index.
+    pub fn count_hits(
+        &self,
+        origin: &Point3<f32>,
+        direction: &Vector3<f32>,
+        accept: impl Fn(usize) -> bool,
+    ) -> usize {
+        let mut count = 0;
+        self.traverse(origin, direction, |triangle, _| {
+            if accept(triangle) {
+                count += 1;
+            }
+            None
+        });
+        count
+    }
+
+    /// Calls `visit` for each triangle hit, visiting nearer nodes first. Returning a distance from
+    /// `visit` skips everything beyond it from then on.
+    fn traverse(
+        &self,
+        origin: &Point3<f32>,
+        direction: &Vector3<f32>,
+        mut visit: impl FnMut(usize, f32) -> Option<f32>,
+    ) {
+        if self.triangles.is_empty() {
+            return;
+        }
+        let inverse_direction = direction.map(|d| 1.0 / d);
+        let mut limit = f32::INFINITY;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node
+                .bounds
+                .entry(origin, &inverse_direction, limit)
+                .is_none()
+            {
+                continue;
+            }
+            if node.count == 0 {
+                let entries = [node.first, node.first + 1].map(|i| {
+                    self.nodes[i]
+                        .bounds
+                        .entry(origin, &inverse_direction, limit)
+                });
+                // Push the farther child first, so the nearer one is searched first.
+                let (near, far) = match entries {
+                    [Some(a), Some(b)] if b < a => (node.first + 1, node.first),
+                    _ => (node.first, node.first + 1),
+                };
+                stack.push(far);
+                stack.push(near);
+                continue;
+            }
+            for i in node.first..node.first + node.count {
+                let corners = self.triangles[i].each_ref();
+                match intersect_ray_triangle(origin, direction, corners) {
+                    Some(distance) if distance < limit => {
+                        if let Some(new_limit) = visit(self.ids[i], distance) {
+                            limit = new_limit;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a closed box from two triangles per face.
+    fn cube(half: f32) -> (Vec<Point3<f32>>, Vec<u32>) {
+        let positions = (0..8)
+            .map(|i| {
+                Point3::new(
+                    if i & 1 == 0 { -half } else { half },
+                    if i & 2 == 0 { -half } else { half },
+                    if i & 4 == 0 { -half } else { half },
+                )
+            })
+            .collect();
+        let faces = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ];
+        let indices = faces
+            .iter()
+            .flat_map(|f| [f[0], f[1], f[2], f[0], f[2], f[3]])
+            .collect();
+        (positions, indices)
+    }
+
+    /// Builds a flat grid of triangles in the plane z = 0, covering [0, n] along x and y.
+    fn grid(n: u32) -> (Vec<Point3<f32>>, Vec<u32>) {
+        let positions = (0..=n)
+            .flat_map(|y| (0..=n).map(move |x| Point3::new(x as f32, y as f32, 0.0)))
+            .collect();
+        let mut indices = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                let i = y * (n + 1) + x;
+                indices.extend([i, i + 1, i + n + 2, i, i + n + 2, i + n + 1]);
+            }
+        }
+        (positions, indices)
+    }
+
+    #[test]
+    fn cast_matches_brute_force() {
+        let (positions, indices) = grid(20);
+        let bvh = Bvh::new(&positions, &indices);
+        assert_eq!(
+            bvh.bounds(),
+            (Point3::new(0.0, 0.0, 0.0), Point3::new(20.0, 20.0, 0.0))
+        );
+        for (x, y) in [(0.3, 0.6), (7.8, 3.1), (19.2, 12.2), (10.5, 10.4)] {
+            let origin = Point3::new(x, y, 5.0);
+            let direction = Vector3::new(0.1, -0.05, -1.0);
+            let hit = bvh.cast(&origin, &direction, |_, _| true).unwrap();
+            let expected = indices
+                .chunks_exact(3)
+                .enumerate()
+                .filter_map(|(i, t)| {
+                    let corners = [0, 1, 2].map(|k| &positions[t[k] as usize]);
+                    intersect_ray_triangle(&origin, &direction, corners).map(|d| (i, d))
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            assert_eq!(hit.triangle, expected.0);
+            assert!((hit.distance - 5.0).abs() < 1e-4);
+        }
+        let away = Vector3::new(0.0, 0.0, 1.0);
+        assert!(bvh
+            .cast(&Point3::new(5.0, 5.0, 5.0), &away, |_, _| true)
+            .is_none());
+    }
+
+    #[test]
+    fn rejected_hits_are_skipped() {
+        let (positions, indices) = cube(1.0);
+        let bvh = Bvh::new(&positions, &indices);
+        let origin = Point3::new(0.2, 0.3, 5.0);
+        let direction = -Vector3::z();
+        let front = bvh.cast(&origin, &direction, |_, _| true).unwrap();
+        assert!((front.distance - 4.0).abs() < 1e-5);
+        let back = bvh
+            .cast(&origin, &direction, |_, distance| distance > 4.5)
+            .unwrap();
+        assert!((back.distance - 6.0).abs() < 1e-5);
+        assert_ne!(front.triangle, back.triangle);
+    }
+
+    #[test]
+    fn hit_count_tells_inside_from_outside() {
+        let (positions, indices) = cube(1.0);
+        let bvh = Bvh::new(&positions, &indices);
+        let direction = Vector3::new(1.0, 0.123, 0.0457);
+        let inside = bvh.count_hits(&Point3::new(0.1, 0.2, 0.3), &direction, |_| true);
+        let outside = bvh.count_hits(&Point3::new(-3.0, 0.2, 0.3), &direction, |_| true);
+        assert_eq!(inside, 1);
+        assert_eq!(outside, 2);
+    }
+}
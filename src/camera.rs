@@ -1,20 +1,180 @@
 //! Contains the [Camera] struct and related constants.
 
+use crate::geometry::frustum;
 use core::f32;
 use na::Point3;
 use nalgebra as na;
+use serde::{Deserialize, Serialize};
 
-/// Avoids a camera distance of zero, messing up math elsewhere.
-const MIN_ZOOM: f32 = 1.0;
-/// Reasonable far plane distance.
-const MAX_ZOOM: f32 = 400.0;
+/// Closest camera distance relative to the scene radius. Avoids a camera distance of zero,
+/// messing up math elsewhere.
+const MIN_ZOOM: f32 = 0.05;
+/// Furthest camera distance relative to the scene radius.
+const MAX_ZOOM: f32 = 20.0;
+/// Factor the camera distance changes by per mousewheel step.
+const ZOOM_STEP: f32 = 1.15;
+/// Distance the camera starts at, relative to the scene radius.
+const HOME_DIST: f32 = 2.4;
+/// Angle the camera turns by per unit of mouse movement.
+const ROTATION_PER_PIXEL: f32 = f32::consts::TAU / 500.0;
+/// Rate at which arcball spin slows down after release, per second.
+const INERTIA_DAMPING: f32 = 4.0;
+/// Arcball spin speed, in radians per second, below which the camera stops.
+const INERTIA_MIN_SPEED: f32 = 0.01;
+/// Smallest near plane distance relative to the far plane, which bounds the loss of depth
+/// precision when the camera is inside the scene.
+const MIN_NEAR_RATIO: f32 = 1e-3;
+/// Space kept between the scene and the clip planes, relative to the scene radius.
+const DEPTH_MARGIN: f32 = 1.05;
+
+/// Represents how the camera projects the scene onto the screen.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum Projection {
+    #[default]
+    Perspective = 0,
+    /// Parallel projection, keeping sizes independent of depth. Zooming changes the visible
+    /// extent instead of moving the camera.
+    Orthographic = 1,
+}
+
+impl Projection {
+    pub const ALL: [Projection; 2] = [Projection::Perspective, Projection::Orthographic];
+}
+
+impl std::fmt::Display for Projection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Projection::Perspective => write!(f, "Perspective"),
+            Projection::Orthographic => write!(f, "Orthographic"),
+        }
+    }
+}
+
+/// One of the two eyes of a stereo view.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    /// Direction the eye is offset in along the camera's X axis.
+    fn sign(&self) -> f32 {
+        match self {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        }
+    }
+}
+
+/// View and projection for a single eye, as produced by [Camera::construct_eye_mvp].
+pub struct EyeView {
+    pub model_view_projection: na::Matrix4<f32>,
+    pub view: na::Matrix4<f32>,
+    pub position: Point3<f32>,
+}
+
+/// A camera viewpoint that can be stored and restored with [Camera::state] and
+/// [Camera::set_state].
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+    pub target: Point3<f32>,
+    /// Rotation from camera to world space.
+    pub orientation: na::UnitQuaternion<f32>,
+    /// Distance from the camera to the target.
+    pub dist: f32,
+}
+
+impl CameraState {
+    /// Blends between two viewpoints, where `t` goes from 0 at `self` to 1 at `other`. The
+    /// distance is blended geometrically so zooming appears to happen at an even speed.
+    pub fn interpolate(&self, other: &CameraState, t: f32) -> CameraState {
+        CameraState {
+            target: self.target + (other.target - self.target) * t,
+            orientation: self
+                .orientation
+                .try_slerp(&other.orientation, t, 1e-6)
+                .unwrap_or(other.orientation),
+            dist: self.dist * (other.dist / self.dist).powf(t),
+        }
+    }
+}
+
+/// An animated move between two viewpoints.
+struct Transition {
+    from: CameraState,
+    to: CameraState,
+    /// Seconds since the start.
+    elapsed: f32,
+    /// Length of the transition in seconds.
+    duration: f32,
+}
+
+/// Represents what dragging the mouse does to the camera.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DragMode {
+    /// Orbit around the target.
+    Rotate,
+    /// Move the target in the view plane.
+    Pan,
+}
+
+/// Represents how dragging rotates the camera.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub enum RotationMode {
+    /// Orbit with the vertical axis kept upright, stopping at the poles.
+    #[default]
+    Turntable = 0,
+    /// Free rotation around the axis perpendicular to the drag, which can go over the poles and
+    /// keeps spinning for a while after being released.
+    Arcball = 1,
+}
+
+impl RotationMode {
+    pub const ALL: [RotationMode; 2] = [RotationMode::Turntable, RotationMode::Arcball];
+}
+
+impl std::fmt::Display for RotationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            RotationMode::Turntable => write!(f, "Turntable"),
+            RotationMode::Arcball => write!(f, "Arcball"),
+        }
+    }
+}
 
 pub struct Camera {
     fov: f32,
-    mousedown: bool,
+    drag: Option<DragMode>,
+    rotation_mode: RotationMode,
     roll: f32,
     pitch: f32,
+    /// Rotation from camera to world space in [RotationMode::Arcball].
+    orientation: na::UnitQuaternion<f32>,
+    /// Arcball rotation dragged since the last [Camera::update], as a camera space axis scaled by
+    /// the angle.
+    drag_rotation: na::Vector3<f32>,
+    /// Arcball spin in radians per second, as a camera space axis scaled by the speed.
+    angular_velocity: na::Vector3<f32>,
     dist: f32,
+    transition: Option<Transition>,
+    /// The point the camera looks at and orbits around.
+    target: Point3<f32>,
+    projection: Projection,
+    /// Window size in the units of mouse coordinates.
+    screen_size: (f32, f32),
+    /// Radius of a sphere around the origin enclosing the scene, which zoom limits and clip
+    /// planes are fitted to.
+    scene_radius: f32,
+    /// Map depth from 1 at the near plane to 0 at the far plane.
+    reversed_z: bool,
+    /// Orientation set by [Camera::set_state] or [Camera::fly_to], from camera to world space.
+    /// Unlike the turntable orbit, it may be rolled, and it is kept until the camera is turned
+    /// some other way.
+    held_orientation: Option<na::UnitQuaternion<f32>>,
 }
 
 #[allow(clippy::new_without_default)]
@@ -23,62 +183,664 @@ impl Camera {
     pub fn new() -> Self {
         Self {
             fov: f32::consts::PI / 4.0,
-            mousedown: false,
+            drag: None,
+            rotation_mode: RotationMode::Turntable,
             roll: 0.0,
             pitch: f32::consts::PI / 4.0,
+            orientation: na::UnitQuaternion::identity(),
+            drag_rotation: na::Vector3::zeros(),
+            angular_velocity: na::Vector3::zeros(),
             dist: 5.0,
+            transition: None,
+            target: Point3::origin(),
+            projection: Projection::Perspective,
+            screen_size: (1.0, 1.0),
+            scene_radius: 20.0,
+            reversed_z: false,
+            held_orientation: None,
+        }
+    }
+
+    /// Returns the direction from the target towards the camera.
+    fn direction(&self) -> na::Vector3<f32> {
+        if let Some(orientation) = self.held_orientation {
+            return orientation * na::Vector3::z();
+        }
+        match self.rotation_mode {
+            RotationMode::Turntable => {
+                let rot = na::Rotation3::from_euler_angles(self.roll, self.pitch, 0.0);
+                rot * na::Vector3::z()
+            }
+            RotationMode::Arcball => self.orientation * na::Vector3::z(),
         }
     }
 
+    /// Turns the camera so that it lies in the given direction from the target.
+    fn set_direction(&mut self, direction: &na::Vector3<f32>) {
+        let direction = direction.normalize();
+        if self.rotation_mode == RotationMode::Arcball {
+            let up = self.orientation * na::Vector3::y();
+            self.orientation = na::UnitQuaternion::face_towards(&direction, &up);
+            return;
+        }
+        self.roll = (-direction.y).asin().clamp(
+            -f32::consts::PI / 2.0 + 0.001,
+            f32::consts::PI / 2.0 - 0.001,
+        );
+        self.pitch = direction.x.atan2(direction.z);
+    }
+
     /// Returns the position of the camera.
     pub fn position(&self) -> Point3<f32> {
-        let rot = na::Rotation3::from_euler_angles(self.roll, self.pitch, 0.0);
-        let rotated = rot * na::Vector3::z();
-        Point3::from(rotated) * self.dist
+        self.target + self.direction() * self.dist
+    }
+
+    /// Moves the point the camera looks at, keeping the viewing direction and distance.
+    pub fn set_target(&mut self, target: Point3<f32>) {
+        self.target = target;
+    }
+
+    /// Manually sets the camera distance from the target.
+    pub fn set_dist(&mut self, dist: f32) {
+        self.dist = self.clamp_dist(dist);
+    }
+
+    /// Returns the current viewpoint.
+    pub fn state(&self) -> CameraState {
+        CameraState {
+            target: self.target,
+            orientation: self.view().rotation.inverse(),
+            dist: self.dist,
+        }
+    }
+
+    /// Moves the camera to the given viewpoint, including its roll. Turntable mode levels the
+    /// camera again once it is turned.
+    pub fn set_state(&mut self, state: &CameraState) {
+        self.held_orientation = None;
+        self.set_target(state.target);
+        self.set_dist(state.dist);
+        self.orientation = state.orientation;
+        if self.rotation_mode == RotationMode::Turntable {
+            self.set_direction(&(state.orientation * na::Vector3::z()));
+        }
+        self.held_orientation = Some(state.orientation);
+        self.angular_velocity = na::Vector3::zeros();
+    }
+
+    /// Returns the viewpoint a new camera starts from, looking down at the scene from an angle.
+    pub fn home_state(&self) -> CameraState {
+        let direction =
+            na::Rotation3::from_euler_angles(0.0, f32::consts::PI / 4.0, 0.0) * na::Vector3::z();
+        let eye = Point3::from(direction);
+        CameraState {
+            target: Point3::origin(),
+            orientation: na::Isometry3::look_at_rh(&eye, &Point3::origin(), &na::Vector3::y())
+                .rotation
+                .inverse(),
+            dist: self.scene_radius * HOME_DIST,
+        }
+    }
+
+    /// Places the camera at `eye` with the given orientation, from camera to world space, for
+    /// flying along a path. The camera orbits a point close in front of it when moved afterwards.
+    pub fn fly_to(&mut self, eye: Point3<f32>, orientation: na::UnitQuaternion<f32>) {
+        self.transition = None;
+        self.angular_velocity = na::Vector3::zeros();
+        self.dist = self.clamp_dist(0.0);
+        self.orientation = orientation;
+        if self.rotation_mode == RotationMode::Turntable {
+            self.set_direction(&(orientation * na::Vector3::z()));
+        }
+        self.held_orientation = Some(orientation);
+        self.target = eye - self.direction() * self.dist;
+    }
+
+    /// Moves the camera smoothly to the given viewpoint over `duration`. Dragging the mouse
+    /// cancels the transition.
+    pub fn animate_to(&mut self, state: CameraState, duration: std::time::Duration) {
+        self.transition = Some(Transition {
+            from: self.state(),
+            to: state,
+            elapsed: 0.0,
+            duration: duration.as_secs_f32(),
+        });
+        self.angular_velocity = na::Vector3::zeros();
+    }
+
+    /// Makes the camera orbit around the given point. The camera stays where it is and turns
+    /// towards the new pivot, so the view doesn't jump.
+    pub fn set_pivot(&mut self, pivot: Point3<f32>) {
+        let offset = self.position() - pivot;
+        self.held_orientation = None;
+        if offset.norm() < self.scene_radius * MIN_ZOOM {
+            self.target = pivot;
+            return;
+        }
+        self.set_direction(&offset);
+        self.set_dist(offset.norm());
+        self.set_target(pivot);
+    }
+
+    /// Fits the zoom limits and clip planes to a scene of the given radius around the origin, for
+    /// a newly loaded model.
+    pub fn set_scene_radius(&mut self, radius: f32) {
+        self.scene_radius = radius.max(f32::EPSILON);
+        self.dist = self.clamp_dist(self.dist);
+    }
+
+    /// Limits a camera distance to the zoom range of the scene.
+    fn clamp_dist(&self, dist: f32) -> f32 {
+        dist.clamp(self.scene_radius * MIN_ZOOM, self.scene_radius * MAX_ZOOM)
+    }
+
+    /// Returns the near and far clip plane distances, enclosing the scene as tightly as possible
+    /// for the best depth precision. Orthographic near planes may lie behind the camera, so
+    /// zooming in doesn't cut into the model.
+    pub fn clip_range(&self) -> (f32, f32) {
+        // Distance of the scene's center along the viewing direction.
+        let depth = -(self.view() * Point3::origin()).z;
+        let radius = self.scene_radius * DEPTH_MARGIN;
+        match self.projection {
+            Projection::Perspective => {
+                let far = (depth + radius).max(radius);
+                ((depth - radius).max(far * MIN_NEAR_RATIO), far)
+            }
+            Projection::Orthographic => (depth - radius, depth + radius),
+        }
+    }
+
+    /// Returns whether the projection uses reversed-Z.
+    pub fn reversed_z(&self) -> bool {
+        self.reversed_z
+    }
+
+    /// Makes the projection map depth from 1 at the near plane to 0 at the far plane, for use with
+    /// [crate::render_gl::set_depth_convention].
+    pub fn set_reversed_z(&mut self, reversed_z: bool) {
+        self.reversed_z = reversed_z;
+    }
+
+    /// Informs the camera of the window size in mouse coordinates, used to convert mouse
+    /// movement to distances in the scene.
+    pub fn set_screen_size(&mut self, (w, h): (u32, u32)) {
+        self.screen_size = (w.max(1) as f32, h.max(1) as f32);
+    }
+
+    /// Returns how dragging rotates the camera.
+    pub fn rotation_mode(&self) -> RotationMode {
+        self.rotation_mode
+    }
+
+    /// Switches how dragging rotates the camera, keeping the current view direction. Leaving
+    /// [RotationMode::Arcball] levels the camera again.
+    pub fn set_rotation_mode(&mut self, mode: RotationMode) {
+        if mode == self.rotation_mode {
+            return;
+        }
+        let direction = self.direction();
+        if mode == RotationMode::Arcball {
+            self.orientation = self.view().rotation.inverse();
+        }
+        self.held_orientation = None;
+        self.rotation_mode = mode;
+        self.set_direction(&direction);
+        self.drag_rotation = na::Vector3::zeros();
+        self.angular_velocity = na::Vector3::zeros();
+    }
+
+    /// Returns the view transform, from world space to camera space.
+    pub fn view(&self) -> na::Isometry3<f32> {
+        let eye = self.position();
+        if let Some(orientation) = self.held_orientation {
+            return na::Isometry3::from_parts(eye.coords.into(), orientation).inverse();
+        }
+        match self.rotation_mode {
+            RotationMode::Turntable => {
+                na::Isometry3::look_at_rh(&eye, &self.target, &na::Vector3::y())
+            }
+            RotationMode::Arcball => {
+                na::Isometry3::from_parts(eye.coords.into(), self.orientation).inverse()
+            }
+        }
+    }
+
+    /// Returns the ray through the given mouse position, as an origin and a unit direction.
+    /// Orthographic rays start behind the camera, where the depth range begins.
+    pub fn ray(&self, (x, y): (f32, f32)) -> (Point3<f32>, na::Vector3<f32>) {
+        let (w, h) = self.screen_size;
+        let top = (self.fov / 2.0).tan();
+        let ndc_x = (2.0 * x / w - 1.0) * top * w / h;
+        let ndc_y = (1.0 - 2.0 * y / h) * top;
+        let rotation = self.view().rotation.inverse();
+        match self.projection {
+            Projection::Perspective => {
+                let direction = rotation * na::Vector3::new(ndc_x, ndc_y, -1.0);
+                (self.position(), direction.normalize())
+            }
+            Projection::Orthographic => {
+                let near = self.clip_range().0;
+                let offset = na::Vector3::new(ndc_x * self.dist, ndc_y * self.dist, -near);
+                let direction = rotation * -na::Vector3::z();
+                (self.position() + rotation * offset, direction)
+            }
+        }
+    }
+
+    /// Size of a mouse coordinate unit in the scene, at the target's depth.
+    fn units_per_pixel(&self) -> f32 {
+        2.0 * self.dist * (self.fov / 2.0).tan() / self.screen_size.1
+    }
+
+    /// Returns how the camera projects the scene.
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Switches between perspective and orthographic projection.
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+
+    /// Returns the projection matrix for the current [Projection]. The orthographic view shows
+    /// the same extent at the camera's target as the perspective one.
+    fn projection_matrix(&self, aspect: f32) -> na::Matrix4<f32> {
+        let (near, far) = self.clip_range();
+        let projection = match self.projection {
+            Projection::Perspective => {
+                na::Perspective3::new(aspect, self.fov, near, far).into_inner()
+            }
+            Projection::Orthographic => {
+                let top = self.dist * (self.fov / 2.0).tan();
+                let right = top * aspect;
+                na::Orthographic3::new(-right, right, -top, top, near, far).into_inner()
+            }
+        };
+        self.depth_remap() * projection
+    }
+
+    /// Returns the transform applied after the projection, which turns OpenGL's depth range of
+    /// -1 to 1 into 1 to 0 for reversed-Z.
+    fn depth_remap(&self) -> na::Matrix4<f32> {
+        if !self.reversed_z {
+            return na::Matrix4::identity();
+        }
+        let mut remap = na::Matrix4::identity();
+        remap[(2, 2)] = -0.5;
+        remap[(2, 3)] = 0.5;
+        remap
     }
 
     /// Constructs a model-view-projection matrix using the camera.
     pub fn construct_mvp(&self, aspect: f32, model: na::Isometry3<f32>) -> na::Matrix4<f32> {
-        let eye = self.position();
-        let target = na::Point3::new(0.0, 0.0, 0.0);
-        let view = na::Isometry3::look_at_rh(&eye, &target, &na::Vector3::y());
-        let projection = na::Perspective3::new(aspect, self.fov, 0.1, 1000.0);
-        projection.into_inner() * (view * model).to_homogeneous()
+        let view = self.view();
+        self.projection_matrix(aspect) * (view * model).to_homogeneous()
+    }
+
+    /// Constructs the view for one eye of a stereo pair. In perspective, the eyes are moved apart
+    /// along the camera's X axis and use off-axis frusta, so they converge on a plane without
+    /// toeing in. Parallel projection has no parallax from moving sideways, so orthographic eyes
+    /// are instead rotated around the convergence point by the angle the eye offset would give.
+    ///
+    /// `convergence` is the distance to the zero-parallax plane as a fraction of the camera
+    /// distance, and `interocular` the eye separation as a fraction of that distance.
+    pub fn construct_eye_mvp(
+        &self,
+        aspect: f32,
+        model: na::Isometry3<f32>,
+        eye: Eye,
+        interocular: f32,
+        convergence: f32,
+    ) -> EyeView {
+        let (near, far) = self.clip_range();
+        let convergence = (convergence * self.dist).max(near).max(f32::EPSILON);
+        let offset = eye.sign() * interocular * convergence / 2.0;
+        let (view, projection) = match self.projection {
+            Projection::Perspective => {
+                let view = na::Translation3::new(-offset, 0.0, 0.0) * self.view();
+
+                // Shift the frustum towards the other eye so both are centered on the same point
+                // of the convergence plane.
+                let top = near * (self.fov / 2.0).tan();
+                let right = top * aspect;
+                let shift = offset * near / convergence;
+                let projection = frustum(-right - shift, right - shift, -top, top, near, far);
+                (view, self.depth_remap() * projection)
+            }
+            Projection::Orthographic => {
+                let pivot = na::Translation3::new(0.0, 0.0, -convergence);
+                let angle = (offset / convergence).atan();
+                let rotation = na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), -angle);
+                let view = pivot * rotation * pivot.inverse() * self.view();
+                (view, self.projection_matrix(aspect))
+            }
+        };
+
+        EyeView {
+            model_view_projection: projection * (view * model).to_homogeneous(),
+            view: view.to_homogeneous(),
+            position: view.inverse() * Point3::origin(),
+        }
     }
 
-    /// Informs the camera that the mouse button is held down, to enable camera movement.
-    pub fn mousedown(&mut self) {
-        self.mousedown = true;
+    /// Informs the camera that a mouse button is held down, to enable camera movement.
+    pub fn mousedown(&mut self, mode: DragMode) {
+        self.drag = Some(mode);
+        self.transition = None;
+        // Grabbing the model stops it from spinning.
+        self.angular_velocity = na::Vector3::zeros();
     }
 
     /// Informs the camera that the mouse button is no longer being held down, to disable camera
     /// movement.
     pub fn mouseup(&mut self) {
-        self.mousedown = false;
+        self.drag = None;
     }
 
     /// Mouse movement handler. Returns true if camera view has changed.
     pub fn mousemove(&mut self, xrel: i32, yrel: i32) -> bool {
-        if !self.mousedown {
-            return false;
+        let (xrel, yrel) = (xrel as f32, yrel as f32);
+        match self.drag {
+            None => return false,
+            Some(DragMode::Rotate) => {
+                self.orbit(xrel, yrel);
+                if self.rotation_mode == RotationMode::Arcball {
+                    self.drag_rotation += na::Vector3::new(yrel, xrel, 0.0) * ROTATION_PER_PIXEL;
+                }
+            }
+            Some(DragMode::Pan) => self.pan(xrel, yrel),
         }
 
-        self.pitch = (self.pitch + f32::consts::TAU / 500.0 * -xrel as f32) % f32::consts::TAU;
-        self.roll = (self.roll + f32::consts::TAU / 500.0 * -yrel as f32).clamp(
+        true
+    }
+
+    /// Turns the model as if it was dragged by the given amount of mouse movement.
+    pub fn orbit(&mut self, xrel: f32, yrel: f32) {
+        self.held_orientation = None;
+        if self.rotation_mode == RotationMode::Arcball {
+            // Turn the model around the axis perpendicular to the drag, by moving the camera the
+            // opposite way.
+            let rotation = na::Vector3::new(yrel, xrel, 0.0) * ROTATION_PER_PIXEL;
+            self.orientation *= na::UnitQuaternion::from_scaled_axis(-rotation);
+            return;
+        }
+        self.pitch = (self.pitch - ROTATION_PER_PIXEL * xrel) % f32::consts::TAU;
+        self.roll = (self.roll - ROTATION_PER_PIXEL * yrel).clamp(
             -f32::consts::PI / 2.0 + 0.001,
             f32::consts::PI / 2.0 - 0.001,
         );
+    }
+
+    /// Moves the model in the view plane as if it was dragged by the given amount of mouse
+    /// movement.
+    pub fn pan(&mut self, xrel: f32, yrel: f32) {
+        // Move the target against the drag, so the model follows the cursor.
+        let rotation = self.view().rotation.inverse();
+        let right = rotation * na::Vector3::x();
+        let up = rotation * na::Vector3::y();
+        let scale = self.units_per_pixel();
+        self.target += (-right * xrel + up * yrel) * scale;
+    }
 
+    /// Moves the camera towards the target by the given number of mousewheel steps, or away
+    /// from it if negative.
+    pub fn zoom(&mut self, steps: f32) {
+        // Zoom by a constant factor per step, so it feels the same at any scale.
+        self.dist = self.clamp_dist(self.dist * ZOOM_STEP.powf(-steps));
+    }
+
+    /// Advances view transitions and arcball inertia by the time since the last frame. While
+    /// dragging, the spin speed follows the mouse; once released, the camera keeps turning and
+    /// slows down. Returns true if the view has changed.
+    pub fn update(&mut self, frame_time: std::time::Duration) -> bool {
+        let dt = frame_time.as_secs_f32();
+        if let Some(mut transition) = self.transition.take() {
+            transition.elapsed += dt;
+            let t = (transition.elapsed / transition.duration.max(f32::EPSILON)).min(1.0);
+            // Ease in and out.
+            let t = t * t * (3.0 - 2.0 * t);
+            self.set_state(&transition.from.interpolate(&transition.to, t));
+            if transition.elapsed < transition.duration {
+                self.transition = Some(transition);
+            }
+            return true;
+        }
+        if self.rotation_mode != RotationMode::Arcball || dt <= 0.0 {
+            return false;
+        }
+        if self.drag == Some(DragMode::Rotate) {
+            // Smooth the measured speed, as mouse events don't arrive evenly between frames.
+            let velocity = std::mem::replace(&mut self.drag_rotation, na::Vector3::zeros()) / dt;
+            self.angular_velocity = self.angular_velocity.lerp(&velocity, 0.5);
+            return false;
+        }
+        if self.angular_velocity.norm() < INERTIA_MIN_SPEED {
+            self.angular_velocity = na::Vector3::zeros();
+            return false;
+        }
+        self.orientation *= na::UnitQuaternion::from_scaled_axis(-self.angular_velocity * dt);
+        self.orientation.renormalize_fast();
+        self.angular_velocity *= (-INERTIA_DAMPING * dt).exp();
         true
     }
 
-    /// Informs the camera that the mousewheel has been scrolled, to enable camera zoom.
-    pub fn mousewheel(&mut self, y: i32) {
-        self.dist = (self.dist - 3.0 * y as f32).clamp(MIN_ZOOM, MAX_ZOOM);
+    /// Informs the camera that the mousewheel has been scrolled, to enable camera zoom. If the
+    /// cursor position is given, the zoom moves towards the point under it.
+    pub fn mousewheel(&mut self, y: i32, cursor: Option<(f32, f32)>) {
+        let old_dist = self.dist;
+        let ray = cursor.map(|cursor| self.ray(cursor));
+        self.zoom(y as f32);
+
+        // Both the camera and the target move towards the point under the cursor in the target's
+        // plane, which keeps that point fixed on screen for either projection.
+        if let Some((origin, direction)) = ray {
+            let normal = self.direction();
+            let denominator = direction.dot(&normal);
+            if denominator.abs() > f32::EPSILON {
+                let t = (self.target - origin).dot(&normal) / denominator;
+                let point = origin + direction * t;
+                self.target += (point - self.target) * (1.0 - self.dist / old_dist);
+            }
+        }
     }
+}
 
-    /// Manually sets the camera distance from origin.
-    pub fn set_dist(&mut self, dist: f32) {
-        self.dist = dist.clamp(MIN_ZOOM, MAX_ZOOM);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pivot_keeps_camera_in_place() {
+        let mut camera = Camera::new();
+        camera.set_dist(10.0);
+        let position = camera.position();
+        let pivot = Point3::new(1.0, -2.0, 0.5);
+        camera.set_pivot(pivot);
+        assert!((camera.position() - position).norm() < 1e-4);
+        assert_eq!(camera.target, pivot);
+    }
+
+    #[test]
+    fn arcball_rotates_over_the_pole() {
+        let mut camera = Camera::new();
+        camera.set_rotation_mode(RotationMode::Arcball);
+        let direction = camera.direction();
+        camera.mousedown(DragMode::Rotate);
+        // Half a turn straight up, which turntable mode would stop at the pole.
+        camera.mousemove(0, 250);
+        assert!((camera.direction() + direction).norm() < 1e-3);
+    }
+
+    #[test]
+    fn arcball_inertia_slows_down() {
+        let mut camera = Camera::new();
+        camera.set_rotation_mode(RotationMode::Arcball);
+        let frame = std::time::Duration::from_millis(16);
+        camera.mousedown(DragMode::Rotate);
+        camera.mousemove(10, 0);
+        camera.update(frame);
+        camera.mouseup();
+        let speed = camera.angular_velocity.norm();
+        assert!(camera.update(frame));
+        assert!(camera.angular_velocity.norm() < speed);
+        for _ in 0..1000 {
+            camera.update(frame);
+        }
+        assert!(!camera.update(frame));
+    }
+
+    #[test]
+    fn switching_rotation_mode_keeps_direction() {
+        let mut camera = Camera::new();
+        let direction = camera.direction();
+        camera.set_rotation_mode(RotationMode::Arcball);
+        assert!((camera.direction() - direction).norm() < 1e-5);
+        camera.mousedown(DragMode::Rotate);
+        camera.mousemove(40, -30);
+        let direction = camera.direction();
+        camera.set_rotation_mode(RotationMode::Turntable);
+        assert!((camera.direction() - direction).norm() < 1e-5);
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let mut camera = Camera::new();
+        camera.set_rotation_mode(RotationMode::Arcball);
+        camera.mousedown(DragMode::Rotate);
+        camera.mousemove(120, 80);
+        camera.mouseup();
+        camera.set_target(Point3::new(1.0, 2.0, 3.0));
+        let state = camera.state();
+
+        let mut other = Camera::new();
+        other.set_rotation_mode(RotationMode::Arcball);
+        other.set_state(&state);
+        assert!((other.position() - camera.position()).norm() < 1e-4);
+        assert!(other.state().orientation.angle_to(&state.orientation) < 1e-4);
+    }
+
+    #[test]
+    fn transition_ends_at_target_state() {
+        let mut camera = Camera::new();
+        let mut state = camera.state();
+        state.target = Point3::new(0.0, 5.0, 0.0);
+        state.dist = 20.0;
+        state.orientation = na::UnitQuaternion::face_towards(&na::Vector3::x(), &na::Vector3::y());
+        let duration = std::time::Duration::from_millis(500);
+        camera.animate_to(state, duration);
+        assert!(camera.update(std::time::Duration::from_millis(100)));
+        assert!((camera.position() - (state.target + na::Vector3::x() * 20.0)).norm() > 1.0);
+        assert!(camera.update(duration));
+        assert!((camera.position() - (state.target + na::Vector3::x() * 20.0)).norm() < 1e-3);
+        assert!(!camera.update(duration));
+    }
+
+    #[test]
+    fn center_ray_hits_target() {
+        let mut camera = Camera::new();
+        camera.set_screen_size((800, 600));
+        camera.set_target(Point3::new(3.0, 0.0, -1.0));
+        for projection in Projection::ALL {
+            camera.set_projection(projection);
+            let (origin, direction) = camera.ray((400.0, 300.0));
+            let to_target = camera.target - origin;
+            assert!(to_target.cross(&direction).norm() < 1e-3 * to_target.norm());
+        }
+    }
+
+    #[test]
+    fn zoom_keeps_point_under_cursor() {
+        let mut camera = Camera::new();
+        camera.set_screen_size((800, 600));
+        camera.set_dist(50.0);
+        let cursor = (600.0, 200.0);
+        let (origin, direction) = camera.ray(cursor);
+        let normal = camera.direction();
+        let t = (camera.target - origin).dot(&normal) / direction.dot(&normal);
+        let point = origin + direction * t;
+        camera.mousewheel(2, Some(cursor));
+        let (origin, direction) = camera.ray(cursor);
+        let to_point = point - origin;
+        assert!(
+            to_point.cross(&direction).norm() < 1e-2,
+            "{}",
+            to_point.cross(&direction).norm()
+        );
+    }
+
+    #[test]
+    fn turntable_keeps_roll_of_views() {
+        use crate::views::{AnatomicalView, AxisDirection, PatientFrame};
+
+        // The superior axis isn't world up here, so most views are rolled or look along it.
+        let frame = PatientFrame {
+            anterior: AxisDirection::NegY,
+            superior: AxisDirection::PosZ,
+        };
+        let mut camera = Camera::new();
+        assert_eq!(camera.rotation_mode(), RotationMode::Turntable);
+        for view in AnatomicalView::ALL {
+            let orientation = frame.orientation(view).unwrap();
+            camera.set_state(&CameraState {
+                target: Point3::new(1.0, 2.0, 3.0),
+                orientation,
+                dist: 10.0,
+            });
+            let state = camera.state();
+            assert!(state.orientation.angle_to(&orientation) < 1e-4, "{}", view);
+            // Zooming and panning don't level the view.
+            camera.zoom(1.0);
+            camera.pan(5.0, -3.0);
+            assert!(
+                camera.state().orientation.angle_to(&orientation) < 1e-4,
+                "{}",
+                view
+            );
+        }
+    }
+
+    #[test]
+    fn flight_pose_is_kept() {
+        let mut camera = Camera::new();
+        let eye = Point3::new(1.0, -2.0, 3.0);
+        let orientation = na::UnitQuaternion::from_euler_angles(0.3, -1.2, 0.4);
+        camera.fly_to(eye, orientation);
+        let view = camera.view().inverse();
+        assert!((view.translation.vector - eye.coords).norm() < 1e-4);
+        assert!(view.rotation.angle_to(&orientation) < 1e-4);
+        // Turning afterwards orbits a point in front of the camera.
+        camera.orbit(10.0, 0.0);
+        assert!((camera.position() - eye).norm() < camera.dist);
+    }
+
+    #[test]
+    fn clip_range_encloses_scene() {
+        let mut camera = Camera::new();
+        camera.set_scene_radius(0.01);
+        camera.set_dist(0.02);
+        let (near, far) = camera.clip_range();
+        assert!(near > 0.0 && near < 0.01 && far > 0.03 && far < 0.05);
+        // Zoom limits scale with the scene too.
+        camera.set_dist(1000.0);
+        assert!(camera.dist < 1.0);
+        camera.set_dist(0.0);
+        assert!(camera.dist > 0.0);
+    }
+
+    #[test]
+    fn reversed_z_maps_near_to_one() {
+        let mut camera = Camera::new();
+        camera.set_reversed_z(true);
+        for projection in Projection::ALL {
+            camera.set_projection(projection);
+            let (near, far) = camera.clip_range();
+            let view = camera.view().inverse();
+            let mvp = camera.construct_mvp(1.0, na::Isometry3::identity());
+            for (distance, depth) in [(near, 1.0), (far, 0.0)] {
+                let point = view * Point3::new(0.0, 0.0, -distance);
+                let clip = mvp * point.to_homogeneous();
+                assert!((clip.z / clip.w - depth).abs() < 1e-3, "{:?}", projection);
+            }
+        }
     }
 }
@@ -0,0 +1,318 @@
+//! Contains [OffscreenTarget], which renders the model into an image of any size independent of
+//! the window, and functions writing such images to PNG files with the settings used to make
+//! them.
+
+use crate::{
+    antialiasing::{AntiAliasing, SceneBuffer},
+    camera::Camera,
+    model::Model,
+    render_gl::{
+        self,
+        buffer::{FrameBuffer, Texture},
+        Viewport,
+    },
+    resources::Resources,
+    settings::{self, SettingsFile},
+    stereo::{StereoBuffer, StereoSettings},
+};
+use anyhow::{anyhow, Context, Result};
+use nalgebra as na;
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Texture unit the offscreen targets are bound to while being set up.
+const TEXTURE_UNIT_CAPTURE: gl::types::GLenum = gl::TEXTURE0;
+
+/// Describes the image an [OffscreenTarget] renders.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CaptureSettings {
+    /// Width and height in pixels.
+    pub size: (u32, u32),
+    /// Leave the background transparent instead of filling it with the clear colour.
+    pub transparent: bool,
+    pub anti_aliasing: AntiAliasing,
+    pub msaa_samples: u32,
+    /// Pixels per UI point, which sizes lines and patterns drawn in screen space. Scaling it with
+    /// the image keeps them looking like they do in the window.
+    pub pixels_per_point: f32,
+}
+
+/// Screenshot choices made in the UI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenshotOptions {
+    /// Width and height in pixels.
+    pub size: (u32, u32),
+    pub transparent: bool,
+    /// Draw the UI over the model, stretched to the image like it is to the window.
+    pub include_ui: bool,
+    /// Folder the image is saved in.
+    pub folder: String,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        Self {
+            size: (3840, 2160),
+            transparent: false,
+            include_ui: false,
+            folder: "screenshots".to_owned(),
+        }
+    }
+}
+
+/// An 8-bit RGBA image, stored row by row from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub size: (u32, u32),
+    pub pixels: Vec<u8>,
+}
+
+/// Framebuffer with colour and depth textures of a chosen size, which the scene is rendered into
+/// and read back from.
+pub struct OffscreenTarget {
+    scene: SceneBuffer,
+    fbo: FrameBuffer,
+    color: Texture,
+    depth: Texture,
+    size: (u32, u32),
+}
+
+impl OffscreenTarget {
+    /// Set up [OffscreenTarget]. Its textures are allocated on the first render.
+    pub fn new(res: &Resources) -> Result<Self> {
+        Ok(Self {
+            scene: SceneBuffer::new(res)?,
+            fbo: FrameBuffer::new(),
+            color: Texture::new(TEXTURE_UNIT_CAPTURE),
+            depth: Texture::new(TEXTURE_UNIT_CAPTURE),
+            size: (0, 0),
+        })
+    }
+
+    /// Returns the largest width or height the driver can render.
+    pub fn max_size() -> u32 {
+        let mut texture_size = 0;
+        let mut viewport_size = [0; 2];
+        unsafe {
+            gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut texture_size);
+            gl::GetIntegerv(gl::MAX_VIEWPORT_DIMS, viewport_size.as_mut_ptr());
+        }
+        texture_size
+            .min(viewport_size[0])
+            .min(viewport_size[1])
+            .max(1) as u32
+    }
+
+    /// Allocates the textures for images of the given size, unless they already fit.
+    fn allocate(&mut self, size: (u32, u32)) -> Result<()> {
+        if size == self.size {
+            return Ok(());
+        }
+        let max = Self::max_size();
+        if size.0 == 0 || size.1 == 0 || size.0 > max || size.1 > max {
+            return Err(anyhow!(
+                "Can't render {}×{} images, the driver allows at most {}×{}.",
+                size.0,
+                size.1,
+                max,
+                max
+            ));
+        }
+        let dimensions = (size.0 as i32, size.1 as i32);
+        self.color.load_texture(
+            dimensions,
+            None,
+            gl::RGBA8 as gl::types::GLint,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            false,
+        );
+        self.color.unbind();
+        self.depth.load_texture(
+            dimensions,
+            None,
+            gl::DEPTH32F_STENCIL8 as gl::types::GLint,
+            gl::DEPTH_STENCIL,
+            gl::FLOAT_32_UNSIGNED_INT_24_8_REV,
+            false,
+        );
+        self.depth.unbind();
+        self.fbo.bind_texture(gl::COLOR_ATTACHMENT0, &self.color);
+        self.fbo
+            .bind_texture(gl::DEPTH_STENCIL_ATTACHMENT, &self.depth);
+        let complete = self.fbo.is_complete();
+        self.fbo.unbind();
+        if !complete {
+            self.size = (0, 0);
+            return Err(anyhow!("The offscreen framebuffer is incomplete."));
+        }
+        self.size = size;
+        Ok(())
+    }
+
+    /// Renders the model as seen by the camera and reads back the image. `overlay` is called
+    /// with the finished scene still bound, to draw things like the UI on top. The model's
+    /// projection is restored afterwards, and the framebuffer bound when called is left bound.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        model: &mut Model,
+        camera: &Camera,
+        model_isometry: na::Isometry3<f32>,
+        stereo: Option<(&mut StereoBuffer, &StereoSettings)>,
+        settings: &CaptureSettings,
+        clear_color: &na::Vector3<f32>,
+        overlay: impl FnOnce(&Viewport),
+    ) -> Result<Image> {
+        let target = FrameBuffer::bound_id();
+        self.allocate(settings.size)?;
+        let (w, h) = settings.size;
+        let viewport = Viewport::for_window(w as i32, h as i32);
+
+        self.fbo.bind();
+        // In stereo, each eye is anti-aliased before the images are combined instead.
+        let scene_anti_aliasing = match stereo {
+            Some(_) => AntiAliasing::Off,
+            None => settings.anti_aliasing,
+        };
+        self.scene
+            .begin(&viewport, scene_anti_aliasing, settings.msaa_samples);
+        let alpha = if settings.transparent { 0.0 } else { 1.0 };
+        // Safety: The camera only uses reversed-Z when it is supported.
+        unsafe {
+            render_gl::set_depth_convention(camera.reversed_z());
+            gl::ClearColor(clear_color.x, clear_color.y, clear_color.z, alpha);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            gl::ClearColor(clear_color.x, clear_color.y, clear_color.z, 1.0);
+        }
+
+        let previous = model.get_attributes().clone();
+        model.set_camera(camera, model_isometry, w as f32 / h as f32);
+        let mut attr = model.get_attributes().clone();
+        attr.pixels_per_point = settings.pixels_per_point;
+        model.set_attributes(attr);
+        match stereo {
+            Some((buffer, stereo)) => {
+                buffer.render(
+                    model,
+                    camera,
+                    model_isometry,
+                    &viewport,
+                    stereo,
+                    settings.anti_aliasing,
+                    settings.msaa_samples,
+                );
+            }
+            None => model.render(&viewport),
+        }
+        let mut attr = model.get_attributes().clone();
+        attr.projection_matrix = previous.projection_matrix;
+        attr.pixels_per_point = previous.pixels_per_point;
+        model.set_attributes(attr);
+
+        // Safety: The scene was begun with the same viewport.
+        unsafe {
+            self.scene.finish(&viewport);
+        }
+        overlay(&viewport);
+        let pixels = flip_rows(&self.fbo.read_pixels(settings.size), w as usize * 4);
+        FrameBuffer::bind_id(target);
+        Ok(Image {
+            size: settings.size,
+            pixels,
+        })
+    }
+}
+
+/// Describes how a captured image of a model was made, to be stored in it as text. The settings
+/// are stored as a settings file, which [SettingsFile::load] reads back from the image.
+pub fn metadata(source: &str, settings: &SettingsFile) -> Result<Vec<(&'static str, String)>> {
+    Ok(vec![
+        ("Software", format!("IVRIGST {}", env!("CARGO_PKG_VERSION"))),
+        ("Source", source.to_owned()),
+        (settings::PNG_KEYWORD, settings.to_json()?),
+    ])
+}
+
+/// Reverses the order of the rows in an image, turning OpenGL's bottom-up rows top-down.
+fn flip_rows(pixels: &[u8], row_length: usize) -> Vec<u8> {
+    pixels
+        .chunks_exact(row_length)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// Encodes an image as PNG, with each `(keyword, text)` pair stored as a text chunk.
+pub fn write_png(writer: impl Write, image: &Image, text: &[(&str, String)]) -> Result<()> {
+    let mut encoder = png::Encoder::new(writer, image.size.0, image.size.1);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in text {
+        encoder.add_itxt_chunk(keyword.to_string(), text.clone())?;
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.pixels)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes an image to a PNG file, creating its folder if needed.
+pub fn save_png(path: &Path, image: &Image, text: &[(&str, String)]) -> Result<()> {
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder).context("Failed to create the image folder")?;
+    }
+    let file = fs::File::create(path).context("Failed to create the image file")?;
+    write_png(BufWriter::new(file), image, text)
+}
+
+/// Returns a path in `folder` for a PNG file named after `stem`, numbered if needed so no
+/// existing file is overwritten.
+pub fn unique_path(folder: &Path, stem: &str) -> PathBuf {
+    let path = folder.join(format!("{}.png", stem));
+    if !path.exists() {
+        return path;
+    }
+    (2..)
+        .map(|i| folder.join(format!("{}_{}.png", stem, i)))
+        .find(|path| !path.exists())
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_keeps_pixels_and_text() {
+        let image = Image {
+            size: (2, 1),
+            pixels: vec![255, 0, 0, 255, 0, 0, 255, 0],
+        };
+        let mut data = Vec::new();
+        let text = [
+            ("Software", "IVRIGST".to_owned()),
+            ("Source", "æøå.obj".to_owned()),
+        ];
+        write_png(&mut data, &image, &text).unwrap();
+
+        let decoder = png::Decoder::new(data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, image.pixels);
+        let chunks = &reader.info().utf8_text;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].keyword, "Source");
+        assert_eq!(chunks[1].get_text().unwrap(), "æøå.obj");
+    }
+
+    #[test]
+    fn rows_are_flipped() {
+        assert_eq!(flip_rows(&[1, 2, 3, 4, 5, 6], 2), vec![5, 6, 3, 4, 1, 2]);
+    }
+}
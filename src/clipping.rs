@@ -0,0 +1,129 @@
+//! Contains [ClipPlane], a user-placed plane cutting away part of the model so its inside can be
+//! seen.
+
+use nalgebra as na;
+use serde::{Deserialize, Serialize};
+
+/// Largest number of clipping planes. Must match `MAX_CLIP_PLANES` in the vertex shaders.
+pub const MAX_CLIP_PLANES: usize = 6;
+
+/// Represents which model axis a [ClipPlane] is perpendicular to.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum ClipAxis {
+    #[default]
+    X = 0,
+    Y = 1,
+    Z = 2,
+}
+
+impl ClipAxis {
+    pub const ALL: [ClipAxis; 3] = [ClipAxis::X, ClipAxis::Y, ClipAxis::Z];
+}
+
+impl std::fmt::Display for ClipAxis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ClipAxis::X => write!(f, "X"),
+            ClipAxis::Y => write!(f, "Y"),
+            ClipAxis::Z => write!(f, "Z"),
+        }
+    }
+}
+
+/// A plane perpendicular to one of the model axes, removing everything on one side of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipPlane {
+    pub enabled: bool,
+    pub axis: ClipAxis,
+    /// Position along the axis as a fraction of the model's bounding box, from -0.5 to 0.5.
+    pub offset: f32,
+    /// Removes the part on the negative side of the axis instead of the positive side.
+    pub flipped: bool,
+}
+
+impl ClipPlane {
+    /// An enabled plane through the center of the model.
+    pub fn new(axis: ClipAxis) -> Self {
+        Self {
+            enabled: true,
+            axis,
+            offset: 0.0,
+            flipped: false,
+        }
+    }
+
+    /// Unit normal pointing towards the removed side.
+    pub fn normal(&self) -> na::Vector3<f32> {
+        let mut normal = na::Vector3::zeros();
+        normal[self.axis as usize] = if self.flipped { -1.0 } else { 1.0 };
+        normal
+    }
+
+    /// The point where the plane crosses its axis, for a model with the given bounding box size.
+    pub fn origin(&self, model_size: &na::Vector3<f32>) -> na::Vector3<f32> {
+        let mut origin = na::Vector3::zeros();
+        let axis = self.axis as usize;
+        origin[axis] = self.offset * model_size[axis];
+        origin
+    }
+
+    /// The plane equation used for `gl_ClipDistance`, which is positive on the kept side.
+    pub fn equation(&self, model_size: &na::Vector3<f32>) -> [f32; 4] {
+        let normal = self.normal();
+        let distance = normal.dot(&self.origin(model_size));
+        [-normal.x, -normal.y, -normal.z, distance]
+    }
+
+    /// Half extents of the bounding box cross-section in the plane, along the two other axes.
+    /// The cap drawn over the cut uses these, slightly enlarged so it covers the whole model.
+    pub fn cap_extents(&self, model_size: &na::Vector3<f32>) -> [na::Vector3<f32>; 2] {
+        let axis = self.axis as usize;
+        [(axis + 1) % 3, (axis + 2) % 3].map(|other| {
+            let mut extent = na::Vector3::zeros();
+            extent[other] = model_size[other] * 0.51;
+            extent
+        })
+    }
+}
+
+/// Returns the enabled planes, up to [MAX_CLIP_PLANES].
+pub fn active_planes(planes: &[ClipPlane]) -> Vec<&ClipPlane> {
+    planes
+        .iter()
+        .filter(|plane| plane.enabled)
+        .take(MAX_CLIP_PLANES)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(equation: [f32; 4], point: na::Vector3<f32>) -> f32 {
+        na::Vector4::from(equation).dot(&point.push(1.0))
+    }
+
+    #[test]
+    fn keeps_negative_side() {
+        let size = na::Vector3::new(2.0, 4.0, 6.0);
+        let mut plane = ClipPlane::new(ClipAxis::Y);
+        plane.offset = 0.25;
+        let equation = plane.equation(&size);
+        assert!(distance(equation, na::Vector3::new(5.0, 0.9, -3.0)) > 0.0);
+        assert!(distance(equation, na::Vector3::new(0.0, 1.1, 0.0)) < 0.0);
+        assert!(distance(equation, na::Vector3::new(0.0, 1.0, 0.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn flipped_keeps_positive_side() {
+        let size = na::Vector3::new(2.0, 4.0, 6.0);
+        let mut plane = ClipPlane::new(ClipAxis::Z);
+        plane.offset = -0.5;
+        plane.flipped = true;
+        let equation = plane.equation(&size);
+        assert!(distance(equation, na::Vector3::new(0.0, 0.0, -2.9)) > 0.0);
+        assert!(distance(equation, na::Vector3::new(0.0, 0.0, -3.1)) < 0.0);
+    }
+}
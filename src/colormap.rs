@@ -0,0 +1,216 @@
+//! Contains the [Colormap] and [HueDirection] enums used by colored depth shading, along with CPU
+//! implementations of the colour mappings used to build the colormap texture and the legend.
+
+use serde::{Deserialize, Serialize};
+
+/// Number of texels in the generated colormap lookup texture.
+pub const COLORMAP_TEXTURE_SIZE: usize = 256;
+
+/// Represents how colored depth shading maps distance to a colour.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum Colormap {
+    #[default]
+    Hsv = 0,
+    Viridis = 1,
+    Plasma = 2,
+    Magma = 3,
+    Inferno = 4,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 5] = [
+        Colormap::Hsv,
+        Colormap::Viridis,
+        Colormap::Plasma,
+        Colormap::Magma,
+        Colormap::Inferno,
+    ];
+
+    /// Samples the colormap at `t` in the range [0…1]. Perceptual colormaps use the polynomial
+    /// fits from [mattz](https://www.shadertoy.com/view/WlfXRN). [Colormap::Hsv] has no fixed
+    /// colours, and returns a plain gray ramp.
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let t = t.clamp(0.0, 1.0);
+        let coefficients = match self {
+            Colormap::Hsv => return [t, t, t],
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Plasma => &PLASMA,
+            Colormap::Magma => &MAGMA,
+            Colormap::Inferno => &INFERNO,
+        };
+
+        let mut rgb = [0.0; 3];
+        for (channel, value) in rgb.iter_mut().enumerate() {
+            // Horner's method, starting from the highest order coefficient.
+            *value = coefficients
+                .iter()
+                .rev()
+                .fold(0.0, |acc, c| acc * t + c[channel])
+                .clamp(0.0, 1.0);
+        }
+        rgb
+    }
+
+    /// Builds RGBA texel data for a [COLORMAP_TEXTURE_SIZE] wide lookup texture.
+    pub fn texture_data(&self) -> Vec<u8> {
+        (0..COLORMAP_TEXTURE_SIZE)
+            .flat_map(|i| {
+                let t = i as f32 / (COLORMAP_TEXTURE_SIZE - 1) as f32;
+                let [r, g, b] = self.sample(t);
+                [to_u8(r), to_u8(g), to_u8(b), 255]
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Colormap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Colormap::Hsv => write!(f, "HSV hue"),
+            Colormap::Viridis => write!(f, "Viridis"),
+            Colormap::Plasma => write!(f, "Plasma"),
+            Colormap::Magma => write!(f, "Magma"),
+            Colormap::Inferno => write!(f, "Inferno"),
+        }
+    }
+}
+
+/// Represents which way around the colour wheel the hue travels from near to far.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum HueDirection {
+    Increasing = 0,
+    #[default]
+    Decreasing = 1,
+}
+
+impl std::fmt::Display for HueDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            HueDirection::Increasing => write!(f, "Increasing"),
+            HueDirection::Decreasing => write!(f, "Decreasing"),
+        }
+    }
+}
+
+/// Finds the hue in degrees at depth `t`, where 0 is nearest and 1 is furthest. Mirrors the hue
+/// calculation in `model.frag`.
+pub fn hue_at(hue_near: f32, hue_far: f32, direction: HueDirection, t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    let hue = match direction {
+        HueDirection::Increasing => hue_near + (hue_far - hue_near).rem_euclid(360.0) * t,
+        HueDirection::Decreasing => hue_near - (hue_near - hue_far).rem_euclid(360.0) * t,
+    };
+    hue.rem_euclid(360.0)
+}
+
+/// Converts a hue in degrees and saturation and value in the range [0…1] to RGB. Mirrors
+/// `hsv2rgb` in `model.frag`.
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let h = hue.rem_euclid(360.0) / 360.0;
+    let channel = |k: f32| {
+        let p = ((h + k).fract() * 6.0 - 3.0).abs();
+        value * (1.0 + ((p - 1.0).clamp(0.0, 1.0) - 1.0) * saturation)
+    };
+    [channel(1.0), channel(2.0 / 3.0), channel(1.0 / 3.0)]
+}
+
+/// Converts a colour in the range [0…1] to a byte.
+pub fn to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+const VIRIDIS: [[f32; 3]; 7] = [
+    [0.277_727_3, 0.005_407_344_5, 0.334_099_8],
+    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+    [-4.634_230_6, -5.799_101, -19.332_441],
+    [6.228_27, 14.179_933, 56.690_55],
+    [4.776_385, -13.745_145, -65.353_035],
+    [-5.435_456, 4.645_852_6, 26.312_435],
+];
+
+const PLASMA: [[f32; 3]; 7] = [
+    [0.058_732_344, 0.023_336_709, 0.543_340_2],
+    [2.176_514_6, 0.238_383_42, 0.753_960_45],
+    [-2.689_460_5, -7.455_851, 3.110_8],
+    [6.130_348, 42.346_188, -28.518_855],
+    [-11.107_436, -82.666_31, 60.139_847],
+    [10.023_066, 71.413_62, -54.072_186],
+    [-3.658_713_8, -22.931_534, 18.191_908],
+];
+
+const MAGMA: [[f32; 3]; 7] = [
+    [-0.002_136_485, -0.000_749_655_05, -0.005_386_128],
+    [0.251_660_54, 0.677_523_2, 2.494_026_6],
+    [8.353_717, -3.577_719_5, 0.314_467_9],
+    [-27.668_733, 14.264_731, -13.649_213],
+    [52.176_14, -27.943_607, 12.944_169],
+    [-50.768_524, 29.046_583, 4.234_153],
+    [18.655_705, -11.489_774, -5.601_961_5],
+];
+
+const INFERNO: [[f32; 3]; 7] = [
+    [0.000_218_940_37, 0.001_651_004_6, -0.019_480_899],
+    [0.106_513_42, 0.563_956_45, 3.932_712_3],
+    [11.602_493, -3.972_854, -15.942_394],
+    [-41.703_995, 17.436_4, 44.354_145],
+    [77.162_94, -33.402_36, -81.807_31],
+    [-71.319_43, 32.626_064, 73.209_52],
+    [25.131_126, -12.242_669, -23.070_325],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert!((a - b).abs() < 0.02, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn viridis_endpoints() {
+        assert_close(Colormap::Viridis.sample(0.0), [0.267, 0.005, 0.329]);
+        assert_close(Colormap::Viridis.sample(1.0), [0.993, 0.906, 0.144]);
+    }
+
+    #[test]
+    fn magma_endpoints() {
+        assert_close(Colormap::Magma.sample(0.0), [0.0, 0.0, 0.0]);
+        assert_close(Colormap::Magma.sample(1.0), [0.987, 0.991, 0.749]);
+    }
+
+    #[test]
+    fn texture_data_size() {
+        let data = Colormap::Plasma.texture_data();
+        assert_eq!(data.len(), COLORMAP_TEXTURE_SIZE * 4);
+    }
+
+    #[test]
+    fn hue_default_range() {
+        let near = hue_at(360.0, 220.0, HueDirection::Decreasing, 0.0);
+        let far = hue_at(360.0, 220.0, HueDirection::Decreasing, 1.0);
+        assert!(near.abs() < 0.001);
+        assert!((far - 220.0).abs() < 0.001);
+        assert!((hue_at(360.0, 220.0, HueDirection::Decreasing, 0.5) - 290.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn hsv_primaries() {
+        assert_close(hsv_to_rgb(0.0, 1.0, 1.0), [1.0, 0.0, 0.0]);
+        assert_close(hsv_to_rgb(120.0, 1.0, 1.0), [0.0, 1.0, 0.0]);
+        assert_close(hsv_to_rgb(240.0, 1.0, 0.5), [0.0, 0.0, 0.5]);
+        assert_close(hsv_to_rgb(60.0, 0.0, 1.0), [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn hue_increasing_wraps() {
+        let mid = hue_at(360.0, 220.0, HueDirection::Increasing, 0.5);
+        assert!((mid - 110.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,419 @@
+//! Estimates per-vertex principal curvatures and directions of triangle meshes, following
+//! [Rusinkiewicz](https://gfx.cs.princeton.edu/pubs/Rusinkiewicz_2004_ECA/). A curvature tensor
+//! is fitted to the change in vertex normals across each face, then averaged at the vertices.
+
+use na::{Matrix3, Vector3};
+use nalgebra as na;
+use std::collections::HashMap;
+
+/// Principal curvatures and directions at a vertex. Curvature is positive where the surface bends
+/// away from its normal, as on the outside of a sphere.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Curvature {
+    /// The largest principal curvature.
+    pub k1: f32,
+    /// The smallest principal curvature.
+    pub k2: f32,
+    /// Unit tangent direction of [Curvature::k1].
+    pub direction1: Vector3<f32>,
+    /// Unit tangent direction of [Curvature::k2].
+    pub direction2: Vector3<f32>,
+}
+
+impl Curvature {
+    /// Returns the direction of the principal curvature with the largest magnitude, which runs
+    /// across ridges and around cylinders.
+    pub fn dominant_direction(&self) -> Vector3<f32> {
+        if self.k1.abs() >= self.k2.abs() {
+            self.direction1
+        } else {
+            self.direction2
+        }
+    }
+
+    /// Returns the principal curvature with the largest magnitude. Positive on ridges, negative
+    /// in valleys.
+    pub fn dominant(&self) -> f32 {
+        if self.k1.abs() >= self.k2.abs() {
+            self.k1
+        } else {
+            self.k2
+        }
+    }
+
+    /// Mean curvature, the average of the principal curvatures.
+    pub fn mean(&self) -> f32 {
+        (self.k1 + self.k2) / 2.0
+    }
+
+    /// Gaussian curvature, the product of the principal curvatures.
+    pub fn gaussian(&self) -> f32 {
+        self.k1 * self.k2
+    }
+
+    /// Koenderink's shape index in the range [-1…1], independent of scale. -1 is a cup, 0 a
+    /// saddle, 0.5 a ridge and 1 a cap. Flat regions return 0.
+    pub fn shape_index(&self) -> f32 {
+        2.0 / std::f32::consts::PI * (self.k1 + self.k2).atan2(self.k1 - self.k2)
+    }
+}
+
+/// Estimates the curvature at each vertex of an indexed triangle mesh. Vertices sharing a
+/// position are treated as one, so seams from split normals or texture coordinates do not break
+/// up the surface. Normals are recomputed from the faces, which should be wound counter-clockwise
+/// when seen from outside.
+pub fn estimate(positions: &[Vector3<f32>], indices: &[u32]) -> Vec<Curvature> {
+    let (points, remap) = weld(positions);
+    let faces: Vec<[usize; 3]> = indices
+        .chunks_exact(3)
+        .map(|f| {
+            [
+                remap[f[0] as usize],
+                remap[f[1] as usize],
+                remap[f[2] as usize],
+            ]
+        })
+        .filter(|f| f[0] != f[1] && f[1] != f[2] && f[2] != f[0])
+        .collect();
+
+    // Area weighted vertex normals, and the total area of the faces around each vertex.
+    let mut normals = vec![Vector3::zeros(); points.len()];
+    let mut point_areas = vec![0.0f32; points.len()];
+    for f in &faces {
+        let cross = (points[f[1]] - points[f[0]]).cross(&(points[f[2]] - points[f[0]]));
+        for &v in f {
+            normals[v] += cross;
+            point_areas[v] += cross.norm() / 2.0;
+        }
+    }
+    for normal in normals.iter_mut() {
+        *normal = normal
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::z);
+    }
+
+    // Initial tangent frame at each vertex, taken from one of its edges.
+    let mut frames = vec![(Vector3::zeros(), Vector3::zeros()); points.len()];
+    for f in &faces {
+        for j in 0..3 {
+            frames[f[j]].0 = points[f[(j + 1) % 3]] - points[f[j]];
+        }
+    }
+    for (frame, normal) in frames.iter_mut().zip(normals.iter()) {
+        let u = frame
+            .0
+            .cross(normal)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| any_perpendicular(normal));
+        *frame = (u, normal.cross(&u));
+    }
+
+    // Fit the second fundamental form on each face, then add it to the vertices' tensors.
+    let mut tensors = vec![[0.0f32; 3]; points.len()];
+    for f in &faces {
+        let edges = [
+            points[f[2]] - points[f[1]],
+            points[f[0]] - points[f[2]],
+            points[f[1]] - points[f[0]],
+        ];
+        let face_normal = edges[0].cross(&edges[1]);
+        let area = face_normal.norm() / 2.0;
+        let (Some(t), Some(n)) = (
+            edges[0].try_normalize(f32::EPSILON),
+            face_normal.try_normalize(f32::EPSILON),
+        ) else {
+            continue;
+        };
+        let b = n.cross(&t);
+
+        let mut w = Matrix3::zeros();
+        let mut m = Vector3::zeros();
+        for (j, edge) in edges.iter().enumerate() {
+            let u = edge.dot(&t);
+            let v = edge.dot(&b);
+            w[(0, 0)] += u * u;
+            w[(0, 1)] += u * v;
+            w[(2, 2)] += v * v;
+            let dn = normals[f[(j + 2) % 3]] - normals[f[(j + 1) % 3]];
+            let dnu = dn.dot(&t);
+            let dnv = dn.dot(&b);
+            m[0] += dnu * u;
+            m[1] += dnu * v + dnv * u;
+            m[2] += dnv * v;
+        }
+        w[(1, 1)] = w[(0, 0)] + w[(2, 2)];
+        w[(1, 2)] = w[(0, 1)];
+        w[(1, 0)] = w[(0, 1)];
+        w[(2, 1)] = w[(1, 2)];
+        let Some(form) = w.cholesky().map(|c| c.solve(&m)) else {
+            continue;
+        };
+
+        for &v in f {
+            let (u_dir, v_dir) = frames[v];
+            let projected = project_tensor((t, b), [form[0], form[1], form[2]], (u_dir, v_dir));
+            let weight = area / point_areas[v];
+            for (total, value) in tensors[v].iter_mut().zip(projected) {
+                *total += weight * value;
+            }
+        }
+    }
+
+    let curvatures: Vec<Curvature> = tensors
+        .iter()
+        .zip(frames.iter())
+        .map(|(&tensor, &frame)| diagonalize(frame, tensor))
+        .collect();
+    remap.iter().map(|&i| curvatures[i]).collect()
+}
+
+/// Estimates the derivative of a per-vertex scalar field along a per-vertex direction, by fitting
+/// a gradient to the differences between each vertex and its neighbours. Zero crossings of the
+/// derivative of the dominant curvature along its direction mark ridge and valley lines.
+pub fn directional_derivatives(
+    positions: &[Vector3<f32>],
+    indices: &[u32],
+    values: &[f32],
+    directions: &[Vector3<f32>],
+) -> Vec<f32> {
+    let (points, remap) = weld(positions);
+    let mut welded_values = vec![0.0; points.len()];
+    for (&i, &value) in remap.iter().zip(values) {
+        welded_values[i] = value;
+    }
+
+    // Least squares fit of the gradient g minimizing Σ(g·e - Δv)² over the edges e of each vertex.
+    let mut normal_matrices = vec![Matrix3::zeros(); points.len()];
+    let mut right_sides = vec![Vector3::zeros(); points.len()];
+    for f in indices.chunks_exact(3) {
+        let f = [
+            remap[f[0] as usize],
+            remap[f[1] as usize],
+            remap[f[2] as usize],
+        ];
+        for j in 0..3 {
+            for (a, b) in [(f[j], f[(j + 1) % 3]), (f[(j + 1) % 3], f[j])] {
+                let edge = points[b] - points[a];
+                normal_matrices[a] += edge * edge.transpose();
+                right_sides[a] += edge * (welded_values[b] - welded_values[a]);
+            }
+        }
+    }
+
+    let gradients: Vec<Vector3<f32>> = normal_matrices
+        .into_iter()
+        .zip(right_sides)
+        .map(|(matrix, right_side)| {
+            // The edges only span the tangent plane, so regularize the normal direction away.
+            let regularization = matrix.trace() * 1e-4 + f32::MIN_POSITIVE;
+            (matrix + Matrix3::identity() * regularization)
+                .try_inverse()
+                .map(|inverse| inverse * right_side)
+                .unwrap_or_else(Vector3::zeros)
+        })
+        .collect();
+    remap
+        .iter()
+        .zip(directions)
+        .map(|(&i, direction)| gradients[i].dot(direction))
+        .collect()
+}
+
+/// Merges vertices with identical positions, returning the unique positions and the index of the
+/// merged vertex for each input vertex.
+pub fn weld(positions: &[Vector3<f32>]) -> (Vec<Vector3<f32>>, Vec<usize>) {
+    let mut unique = Vec::new();
+    let mut lookup = HashMap::new();
+    let remap = positions
+        .iter()
+        .map(|p| {
+            // Adding zero turns negative zero positive, so both compare as the same position.
+            let key = [
+                (p.x + 0.0).to_bits(),
+                (p.y + 0.0).to_bits(),
+                (p.z + 0.0).to_bits(),
+            ];
+            *lookup.entry(key).or_insert_with(|| {
+                unique.push(*p);
+                unique.len() - 1
+            })
+        })
+        .collect();
+    (unique, remap)
+}
+
+/// Finds some unit vector perpendicular to `v`.
+fn any_perpendicular(v: &Vector3<f32>) -> Vector3<f32> {
+    let axis = if v.x.abs() < 0.9 {
+        Vector3::x()
+    } else {
+        Vector3::y()
+    };
+    v.cross(&axis).normalize()
+}
+
+/// Rotates the frame `(u, v)` so its normal matches `normal`.
+fn rotate_frame(
+    (u, v): (Vector3<f32>, Vector3<f32>),
+    normal: &Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>) {
+    let old_normal = u.cross(&v);
+    let ndot = old_normal.dot(normal);
+    if ndot <= -1.0 {
+        return (-u, -v);
+    }
+    let perp_old = normal - ndot * old_normal;
+    let dperp = (old_normal + normal) / (1.0 + ndot);
+    (u - dperp * u.dot(&perp_old), v - dperp * v.dot(&perp_old))
+}
+
+/// Re-expresses the curvature tensor `[ku, kuv, kv]` given in the frame `from` in the frame `to`.
+fn project_tensor(
+    from: (Vector3<f32>, Vector3<f32>),
+    [ku, kuv, kv]: [f32; 3],
+    to: (Vector3<f32>, Vector3<f32>),
+) -> [f32; 3] {
+    let (u, v) = rotate_frame(to, &from.0.cross(&from.1));
+    let (u1, v1) = (u.dot(&from.0), u.dot(&from.1));
+    let (u2, v2) = (v.dot(&from.0), v.dot(&from.1));
+    [
+        ku * u1 * u1 + kuv * 2.0 * u1 * v1 + kv * v1 * v1,
+        ku * u1 * u2 + kuv * (u1 * v2 + u2 * v1) + kv * v1 * v2,
+        ku * u2 * u2 + kuv * 2.0 * u2 * v2 + kv * v2 * v2,
+    ]
+}
+
+/// Finds the principal curvatures and directions of a tensor given in the frame `(u, v)`.
+fn diagonalize((u, v): (Vector3<f32>, Vector3<f32>), [ku, kuv, kv]: [f32; 3]) -> Curvature {
+    // A single Jacobi rotation diagonalizes the symmetric 2×2 tensor.
+    let (mut c, mut s, mut tt) = (1.0, 0.0, 0.0);
+    if kuv != 0.0 {
+        let h = 0.5 * (kv - ku) / kuv;
+        tt = if h < 0.0 {
+            1.0 / (h - (1.0 + h * h).sqrt())
+        } else {
+            1.0 / (h + (1.0 + h * h).sqrt())
+        };
+        c = 1.0 / (1.0 + tt * tt).sqrt();
+        s = tt * c;
+    }
+    let first = (ku - tt * kuv, c * u - s * v);
+    let second = (kv + tt * kuv, s * u + c * v);
+    let (max, min) = if first.0 >= second.0 {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    Curvature {
+        k1: max.0,
+        k2: min.0,
+        direction1: max.1,
+        direction2: min.1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a closed grid of `rings + 1` rows of `segments` vertices, wound to face outwards.
+    fn grid(
+        rings: usize,
+        segments: usize,
+        point: impl Fn(f32, f32) -> Vector3<f32>,
+    ) -> (Vec<Vector3<f32>>, Vec<u32>) {
+        let mut positions = Vec::new();
+        for i in 0..=rings {
+            for j in 0..segments {
+                positions.push(point(
+                    i as f32 / rings as f32,
+                    j as f32 / segments as f32 * std::f32::consts::TAU,
+                ));
+            }
+        }
+        let index = |i: usize, j: usize| (i * segments + j % segments) as u32;
+        let mut indices = Vec::new();
+        for i in 0..rings {
+            for j in 0..segments {
+                let (a, b) = (index(i, j), index(i + 1, j));
+                let (c, d) = (index(i, j + 1), index(i + 1, j + 1));
+                indices.extend([a, c, b, c, d, b]);
+            }
+        }
+        (positions, indices)
+    }
+
+    #[test]
+    fn sphere_curvature() {
+        let radius = 2.0;
+        let (positions, indices) = grid(32, 64, |t, phi| {
+            let theta = (1.0 - t) * std::f32::consts::PI;
+            radius
+                * Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+        });
+        let curvatures = estimate(&positions, &indices);
+        let equator = &curvatures[16 * 64 + 5];
+        assert!((equator.k1 - 0.5).abs() < 0.02, "{:?}", equator);
+        assert!((equator.k2 - 0.5).abs() < 0.02, "{:?}", equator);
+        assert!((equator.mean() - 0.5).abs() < 0.02);
+        assert!((equator.gaussian() - 0.25).abs() < 0.02);
+        assert!(equator.shape_index() > 0.95);
+    }
+
+    #[test]
+    fn cylinder_curvature() {
+        let (positions, indices) = grid(20, 64, |t, phi| {
+            Vector3::new(phi.cos(), phi.sin(), t * 4.0 - 2.0)
+        });
+        let curvatures = estimate(&positions, &indices);
+        let middle = &curvatures[10 * 64 + 7];
+        assert!((middle.k1 - 1.0).abs() < 0.02, "{:?}", middle);
+        assert!(middle.k2.abs() < 0.02, "{:?}", middle);
+        assert!(middle.direction1.z.abs() < 0.05, "{:?}", middle);
+        assert!(middle.direction2.z.abs() > 0.95, "{:?}", middle);
+        assert_eq!(middle.dominant_direction(), middle.direction1);
+        assert!((middle.shape_index() - 0.5).abs() < 0.02);
+        assert!(middle.gaussian().abs() < 0.02);
+    }
+
+    #[test]
+    fn directional_derivative_of_linear_field() {
+        let (positions, indices) = grid(10, 32, |t, phi| {
+            Vector3::new(phi.cos(), phi.sin(), t * 4.0 - 2.0)
+        });
+        let values: Vec<f32> = positions.iter().map(|p| 3.0 * p.z).collect();
+        let along_axis = vec![Vector3::z(); positions.len()];
+        let derivatives = directional_derivatives(&positions, &indices, &values, &along_axis);
+        assert!((derivatives[5 * 32 + 3] - 3.0).abs() < 0.01);
+        let around = vec![Vector3::y(); positions.len()];
+        let derivatives = directional_derivatives(&positions, &indices, &values, &around);
+        assert!(derivatives[5 * 32].abs() < 0.01);
+    }
+
+    #[test]
+    fn flat_shape_index() {
+        let flat = Curvature {
+            k1: 0.0,
+            k2: 0.0,
+            direction1: Vector3::x(),
+            direction2: Vector3::y(),
+        };
+        assert_eq!(flat.shape_index(), 0.0);
+    }
+
+    #[test]
+    fn weld_merges_duplicates() {
+        let positions = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(-0.0, 0.0, 0.0),
+        ];
+        let (unique, remap) = weld(&positions);
+        assert_eq!(unique.len(), 2);
+        assert_eq!(remap, vec![0, 1, 0]);
+    }
+}
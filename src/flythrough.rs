@@ -0,0 +1,391 @@
+//! Contains [CameraPath], a smooth spline through recorded keyframes or a vessel centerline, and
+//! [Flythrough], which moves the camera along one.
+
+use crate::camera::CameraState;
+use anyhow::{anyhow, Result};
+use na::{Point3, UnitQuaternion, Vector3};
+use nalgebra as na;
+
+/// Spline samples per segment between two path points, used to measure distance along the path.
+const SAMPLES_PER_SEGMENT: usize = 16;
+
+/// Represents how the camera is turned while flying along a path.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub enum PathOrientation {
+    /// Look at the point a little further along the path, which follows bends smoothly.
+    #[default]
+    LookAhead = 0,
+    /// Blend between the orientations the keyframes were recorded with.
+    Keyframes = 1,
+}
+
+impl PathOrientation {
+    pub const ALL: [PathOrientation; 2] = [PathOrientation::LookAhead, PathOrientation::Keyframes];
+}
+
+impl std::fmt::Display for PathOrientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            PathOrientation::LookAhead => write!(f, "Look ahead"),
+            PathOrientation::Keyframes => write!(f, "Keyframes"),
+        }
+    }
+}
+
+/// A point on the sampled spline.
+#[derive(Copy, Clone, Debug)]
+struct PathSample {
+    position: Point3<f32>,
+    /// Distance along the path from its start.
+    distance: f32,
+    /// Up direction of a rotation-minimizing frame, so the view doesn't twist around the path.
+    up: Vector3<f32>,
+    /// Index of the segment the sample lies on, plus the fraction along it.
+    parameter: f32,
+}
+
+/// A centripetal Catmull-Rom spline through a list of points, measured by distance along it.
+#[derive(Clone, Debug)]
+pub struct CameraPath {
+    /// Camera orientation at each point, when the path was recorded from keyframes.
+    orientations: Option<Vec<UnitQuaternion<f32>>>,
+    samples: Vec<PathSample>,
+}
+
+impl CameraPath {
+    /// Builds a path through the given points. Repeated points are skipped, and at least two
+    /// distinct points are needed.
+    pub fn new(points: Vec<Point3<f32>>) -> Result<Self> {
+        Self::with_orientations(points.into_iter().map(|p| (p, None)).collect())
+    }
+
+    /// Builds a path through the eye positions of recorded viewpoints, keeping their orientations.
+    pub fn from_keyframes(keyframes: &[CameraState]) -> Result<Self> {
+        Self::with_orientations(
+            keyframes
+                .iter()
+                .map(|state| {
+                    let eye = state.target + state.orientation * Vector3::z() * state.dist;
+                    (eye, Some(state.orientation))
+                })
+                .collect(),
+        )
+    }
+
+    fn with_orientations(points: Vec<(Point3<f32>, Option<UnitQuaternion<f32>>)>) -> Result<Self> {
+        let mut distinct: Vec<(Point3<f32>, Option<UnitQuaternion<f32>>)> = Vec::new();
+        for point in points {
+            if distinct
+                .last()
+                .is_none_or(|last| (last.0 - point.0).norm() > f32::EPSILON)
+            {
+                distinct.push(point);
+            }
+        }
+        if distinct.len() < 2 {
+            return Err(anyhow!("A path needs at least two distinct points."));
+        }
+        let orientations = distinct.iter().map(|p| p.1).collect::<Option<Vec<_>>>();
+        let points: Vec<Point3<f32>> = distinct.into_iter().map(|p| p.0).collect();
+
+        let mut samples: Vec<PathSample> = Vec::new();
+        let segments = points.len() - 1;
+        for segment in 0..segments {
+            // Each segment starts where the previous one ended, so only the last includes its end.
+            let end = usize::from(segment == segments - 1);
+            for k in 0..SAMPLES_PER_SEGMENT + end {
+                let t = k as f32 / SAMPLES_PER_SEGMENT as f32;
+                let position = catmull_rom(&spline_controls(&points, segment), t);
+                let distance = samples.last().map_or(0.0, |last| {
+                    last.distance + (position - last.position).norm()
+                });
+                samples.push(PathSample {
+                    position,
+                    distance,
+                    up: Vector3::zeros(),
+                    parameter: segment as f32 + t,
+                });
+            }
+        }
+        add_rotation_minimizing_frames(&mut samples);
+
+        Ok(Self {
+            orientations,
+            samples,
+        })
+    }
+
+    /// Returns the length of the path.
+    pub fn length(&self) -> f32 {
+        self.samples.last().map_or(0.0, |s| s.distance)
+    }
+
+    /// Returns whether the path has orientations to use with [PathOrientation::Keyframes].
+    pub fn has_orientations(&self) -> bool {
+        self.orientations.is_some()
+    }
+
+    /// Finds the sample at or before the given distance, and how far it is towards the next.
+    fn locate(&self, distance: f32) -> (usize, f32) {
+        let distance = distance.clamp(0.0, self.length());
+        let next = self
+            .samples
+            .partition_point(|s| s.distance <= distance)
+            .clamp(1, self.samples.len() - 1);
+        let (a, b) = (&self.samples[next - 1], &self.samples[next]);
+        let t = (distance - a.distance) / (b.distance - a.distance).max(f32::EPSILON);
+        (next - 1, t.clamp(0.0, 1.0))
+    }
+
+    /// Returns the point at the given distance along the path.
+    pub fn position(&self, distance: f32) -> Point3<f32> {
+        let (i, t) = self.locate(distance);
+        let (a, b) = (&self.samples[i], &self.samples[i + 1]);
+        a.position + (b.position - a.position) * t
+    }
+
+    /// Returns the camera position and orientation, from camera to world space, at the given
+    /// distance. With [PathOrientation::LookAhead], the camera looks at the point `look_ahead`
+    /// further along the path, or along the path's direction at its end.
+    pub fn pose(
+        &self,
+        distance: f32,
+        orientation: PathOrientation,
+        look_ahead: f32,
+    ) -> (Point3<f32>, UnitQuaternion<f32>) {
+        let (i, t) = self.locate(distance);
+        let (a, b) = (&self.samples[i], &self.samples[i + 1]);
+        let position = a.position + (b.position - a.position) * t;
+
+        if let (PathOrientation::Keyframes, Some(orientations)) = (orientation, &self.orientations)
+        {
+            let parameter = a.parameter + (b.parameter - a.parameter) * t;
+            let segment = (parameter.floor() as usize).min(orientations.len() - 2);
+            let blend = parameter - segment as f32;
+            let from = orientations[segment];
+            let to = orientations[segment + 1];
+            let rotation = from.try_slerp(&to, blend, 1e-6).unwrap_or(to);
+            return (position, rotation);
+        }
+
+        let ahead = self.position(distance + look_ahead.max(f32::EPSILON));
+        let forward = if (ahead - position).norm() > f32::EPSILON {
+            ahead - position
+        } else {
+            b.position - a.position
+        };
+        let up = a.up.lerp(&b.up, t);
+        // The camera looks down its negative Z axis.
+        (position, UnitQuaternion::face_towards(&-forward, &up))
+    }
+}
+
+/// Returns the four control points for a segment, extending the path by mirroring its ends.
+fn spline_controls(points: &[Point3<f32>], segment: usize) -> [Point3<f32>; 4] {
+    let p1 = points[segment];
+    let p2 = points[segment + 1];
+    let p0 = if segment > 0 {
+        points[segment - 1]
+    } else {
+        p1 + (p1 - p2)
+    };
+    let p3 = points.get(segment + 2).copied().unwrap_or(p2 + (p2 - p1));
+    [p0, p1, p2, p3]
+}
+
+/// Evaluates a centripetal Catmull-Rom segment between the middle two control points, which
+/// unlike the uniform variant doesn't overshoot or loop where the points are unevenly spaced.
+fn catmull_rom(p: &[Point3<f32>; 4], t: f32) -> Point3<f32> {
+    let knot = |a: &Point3<f32>, b: &Point3<f32>| (b - a).norm().sqrt().max(1e-6);
+    let t0 = 0.0;
+    let t1 = t0 + knot(&p[0], &p[1]);
+    let t2 = t1 + knot(&p[1], &p[2]);
+    let t3 = t2 + knot(&p[2], &p[3]);
+    let u = t1 + (t2 - t1) * t;
+    let mix =
+        |a: &Point3<f32>, b: &Point3<f32>, ta: f32, tb: f32| a + (b - a) * ((u - ta) / (tb - ta));
+
+    // Barry and Goldman's pyramidal formulation.
+    let a1 = mix(&p[0], &p[1], t0, t1);
+    let a2 = mix(&p[1], &p[2], t1, t2);
+    let a3 = mix(&p[2], &p[3], t2, t3);
+    let b1 = mix(&a1, &a2, t0, t2);
+    let b2 = mix(&a2, &a3, t1, t3);
+    mix(&b1, &b2, t1, t2)
+}
+
+/// Fills in up vectors along the samples with the double reflection method of Wang et al.,
+/// starting from the world up direction where possible.
+fn add_rotation_minimizing_frames(samples: &mut [PathSample]) {
+    let tangent = |i: usize| {
+        let next = (i + 1).min(samples.len() - 1);
+        let previous = i.saturating_sub(1);
+        (samples[next].position - samples[previous].position)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::z)
+    };
+    let tangents: Vec<Vector3<f32>> = (0..samples.len()).map(tangent).collect();
+
+    let first = tangents[0];
+    let up = [Vector3::y(), Vector3::x()]
+        .into_iter()
+        .map(|axis| axis - first * axis.dot(&first))
+        .find(|up| up.norm() > 0.1)
+        .unwrap_or_else(Vector3::y);
+    samples[0].up = up.normalize();
+
+    for i in 0..samples.len() - 1 {
+        let reflect = |v: &Vector3<f32>, normal: &Vector3<f32>| {
+            let length = normal.norm_squared();
+            if length < f32::EPSILON {
+                *v
+            } else {
+                v - normal * (2.0 / length * normal.dot(v))
+            }
+        };
+        let v1 = samples[i + 1].position - samples[i].position;
+        let up = reflect(&samples[i].up, &v1);
+        let tangent = reflect(&tangents[i], &v1);
+        let v2 = tangents[i + 1] - tangent;
+        samples[i + 1].up = reflect(&up, &v2).normalize();
+    }
+}
+
+/// Reads the points of a centerline from text, with one point per line as three numbers
+/// separated by spaces or commas. Lines that don't start with a number, like comments or a CSV
+/// header, are skipped, and obj vertex lines (`v x y z`) are accepted too.
+pub fn parse_centerline(text: &str) -> Result<Vec<Point3<f32>>> {
+    let points: Vec<Point3<f32>> = text
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix("v ").unwrap_or(line);
+            let values: Vec<f32> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|v| !v.is_empty())
+                .take(3)
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .ok()?;
+            (values.len() == 3).then(|| Point3::new(values[0], values[1], values[2]))
+        })
+        .collect();
+    if points.len() < 2 {
+        return Err(anyhow!("The centerline has fewer than two points."));
+    }
+    Ok(points)
+}
+
+/// Playback state for a flight along a [CameraPath].
+#[derive(Clone, Debug)]
+pub struct Flythrough {
+    pub path: CameraPath,
+    /// Distance travelled along the path.
+    pub distance: f32,
+    /// Distance travelled per second while playing.
+    pub speed: f32,
+    /// How far ahead along the path the camera looks, for [PathOrientation::LookAhead].
+    pub look_ahead: f32,
+    pub orientation: PathOrientation,
+    pub playing: bool,
+    /// Start over at the end instead of stopping.
+    pub looping: bool,
+    /// Distance the camera was last moved to, to tell when it needs moving again.
+    shown_distance: Option<f32>,
+}
+
+impl Flythrough {
+    /// Sets up a flight from the start of the path, taking twenty seconds by default.
+    pub fn new(path: CameraPath) -> Self {
+        let length = path.length();
+        let orientation = if path.has_orientations() {
+            PathOrientation::Keyframes
+        } else {
+            PathOrientation::LookAhead
+        };
+        Self {
+            path,
+            distance: 0.0,
+            speed: length / 20.0,
+            look_ahead: length / 20.0,
+            orientation,
+            playing: true,
+            looping: false,
+            shown_distance: None,
+        }
+    }
+
+    /// Advances the flight by the time since the last frame. Returns the camera position and
+    /// orientation if the camera should move, either from playing or from settings changed.
+    pub fn update(
+        &mut self,
+        frame_time: std::time::Duration,
+    ) -> Option<(Point3<f32>, UnitQuaternion<f32>)> {
+        let length = self.path.length();
+        if self.playing {
+            self.distance += self.speed * frame_time.as_secs_f32();
+            if self.distance >= length {
+                if self.looping {
+                    self.distance %= length.max(f32::EPSILON);
+                } else {
+                    self.distance = length;
+                    self.playing = false;
+                }
+            }
+        }
+        if self.shown_distance == Some(self.distance) && !self.playing {
+            return None;
+        }
+        self.shown_distance = Some(self.distance);
+        Some(
+            self.path
+                .pose(self.distance, self.orientation, self.look_ahead),
+        )
+    }
+
+    /// Makes the next [Flythrough::update] move the camera, after settings have changed.
+    pub fn refresh(&mut self) {
+        self.shown_distance = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_passes_through_points() {
+        let points = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 3.0, 0.0),
+            Point3::new(2.0, 3.0, 1.0),
+        ];
+        let path = CameraPath::new(points.clone()).unwrap();
+        assert!(path.length() > 1.0 + 3.0 + 2.0_f32.sqrt() - 1e-3);
+        assert!((path.position(0.0) - points[0]).norm() < 1e-5);
+        assert!((path.position(path.length()) - points[3]).norm() < 1e-4);
+        let (i, _) = path.locate(path.length() / 2.0);
+        assert!(path.samples[i].parameter > 0.0);
+    }
+
+    #[test]
+    fn look_ahead_faces_along_path() {
+        let points = (0..5).map(|i| Point3::new(i as f32, 0.0, 0.0)).collect();
+        let path = CameraPath::new(points).unwrap();
+        let (position, rotation) = path.pose(1.0, PathOrientation::LookAhead, 0.5);
+        assert!((position - Point3::new(1.0, 0.0, 0.0)).norm() < 1e-3);
+        assert!((rotation * -Vector3::z() - Vector3::x()).norm() < 1e-3);
+        assert!((rotation * Vector3::y()).dot(&Vector3::y()) > 0.99);
+    }
+
+    #[test]
+    fn centerline_file_formats() {
+        let text = "x,y,z\n0,0,0\n1, 2, 3\n# comment\nv 4 5 6\nl 1 2\n";
+        let points = parse_centerline(text).unwrap();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[2], Point3::new(4.0, 5.0, 6.0));
+        assert!(parse_centerline("1 2 3").is_err());
+    }
+}
@@ -1,6 +1,6 @@
 //! Geometry module containing helper functions for calculating some intersections.
 
-use na::Vector3;
+use na::{Matrix4, Orthographic3, Point3, Vector3};
 use nalgebra as na;
 
 /// Finds the intersection of the given line and box, both centered at origin.
@@ -50,6 +50,89 @@ pub fn intersect_plane_and_line(
     ray_point - ray_vector.scale(prod3)
 }
 
+/// Finds the tightest orthographic projection containing a box centered at origin, as seen through
+/// the given view matrix. A small margin is added so geometry on the box faces is not clipped.
+pub fn fit_orthographic_to_box(view: &Matrix4<f32>, box_size: Vector3<f32>) -> Orthographic3<f32> {
+    let half = box_size / 2.0;
+    let mut min = Vector3::from_element(f32::MAX);
+    let mut max = Vector3::from_element(f32::MIN);
+    for i in 0..8 {
+        let corner = Point3::new(
+            if i & 1 == 0 { -half.x } else { half.x },
+            if i & 2 == 0 { -half.y } else { half.y },
+            if i & 4 == 0 { -half.z } else { half.z },
+        );
+        let corner = view.transform_point(&corner);
+        min = min.inf(&corner.coords);
+        max = max.sup(&corner.coords);
+    }
+
+    let margin = (box_size.norm() * 0.01).max(1e-4);
+    min.add_scalar_mut(-margin);
+    max.add_scalar_mut(margin);
+
+    // The view looks down the negative z axis, so the nearest corner has the largest z.
+    Orthographic3::new(min.x, max.x, min.y, max.y, -max.z, -min.z)
+}
+
+/// Finds where a ray hits a triangle, from either side, using the Möller–Trumbore algorithm.
+/// Returns the distance along the ray in units of `direction`, or `None` if it misses.
+pub fn intersect_ray_triangle(
+    origin: &Point3<f32>,
+    direction: &Vector3<f32>,
+    [a, b, c]: [&Point3<f32>; 3],
+) -> Option<f32> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(&edge2);
+    let determinant = edge1.dot(&p);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = origin - a;
+    let u = s.dot(&p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&edge1);
+    let v = direction.dot(&q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(&q) * inverse;
+    (t >= 0.0).then_some(t)
+}
+
+/// Returns the barycentric coordinates of a point with respect to a triangle, as the weights of
+/// its corners. Points off the triangle's plane are projected onto it.
+pub fn barycentric(point: &Point3<f32>, [a, b, c]: [&Point3<f32>; 3]) -> Vector3<f32> {
+    let (edge1, edge2, offset) = (b - a, c - a, point - a);
+    let (d11, d12, d22) = (edge1.dot(&edge1), edge1.dot(&edge2), edge2.dot(&edge2));
+    let (d1p, d2p) = (edge1.dot(&offset), edge2.dot(&offset));
+    let denominator = d11 * d22 - d12 * d12;
+    if denominator.abs() < f32::EPSILON {
+        return Vector3::new(1.0, 0.0, 0.0);
+    }
+    let v = (d22 * d1p - d12 * d2p) / denominator;
+    let w = (d11 * d2p - d12 * d1p) / denominator;
+    Vector3::new(1.0 - v - w, v, w)
+}
+
+/// Builds a perspective projection from the extents of the near plane, like `glFrustum`. Unlike
+/// [na::Perspective3], the frustum doesn't need to be centered on the view direction.
+pub fn frustum(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix4<f32> {
+    let mut m = Matrix4::zeros();
+    m[(0, 0)] = 2.0 * near / (right - left);
+    m[(0, 2)] = (right + left) / (right - left);
+    m[(1, 1)] = 2.0 * near / (top - bottom);
+    m[(1, 2)] = (top + bottom) / (top - bottom);
+    m[(2, 2)] = -(far + near) / (far - near);
+    m[(2, 3)] = -2.0 * far * near / (far - near);
+    m[(3, 2)] = -1.0;
+    m
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,6 +161,90 @@ mod tests {
         assert_eq!(result, expect);
     }
 
+    #[test]
+    fn fit_orthographic_to_box_identity() {
+        let box_size = Vector3::new(2.0, 4.0, 6.0);
+        let projection = fit_orthographic_to_box(&na::Matrix4::identity(), box_size);
+        let margin = box_size.norm() * 0.01;
+        assert!((projection.left() + 1.0 + margin).abs() < 1e-5);
+        assert!((projection.right() - 1.0 - margin).abs() < 1e-5);
+        assert!((projection.bottom() + 2.0 + margin).abs() < 1e-5);
+        assert!((projection.top() - 2.0 - margin).abs() < 1e-5);
+        assert!((projection.znear() + 3.0 + margin).abs() < 1e-5);
+        assert!((projection.zfar() - 3.0 - margin).abs() < 1e-5);
+    }
+
+    #[test]
+    fn fit_orthographic_to_box_contains_corners() {
+        let box_size = Vector3::new(10.0, 1.0, 3.0);
+        let eye = na::Point3::new(20.0, 15.0, -7.0);
+        let view = na::Matrix4::look_at_rh(&eye, &na::Point3::origin(), &Vector3::y());
+        let projection = fit_orthographic_to_box(&view, box_size).to_homogeneous() * view;
+        for i in 0..8 {
+            let corner = na::Point3::new(
+                if i & 1 == 0 { -5.0 } else { 5.0 },
+                if i & 2 == 0 { -0.5 } else { 0.5 },
+                if i & 4 == 0 { -1.5 } else { 1.5 },
+            );
+            let ndc = projection.transform_point(&corner);
+            assert!(ndc.coords.iter().all(|c| c.abs() < 1.0), "{:?}", ndc);
+        }
+    }
+
+    #[test]
+    fn symmetric_frustum_matches_perspective() {
+        let perspective = na::Perspective3::new(1.5, 0.8, 0.1, 100.0).into_inner();
+        let top = 0.1 * (0.4f32).tan();
+        let result = frustum(-top * 1.5, top * 1.5, -top, top, 0.1, 100.0);
+        assert!((result - perspective).abs().max() < 1e-4);
+    }
+
+    #[test]
+    fn shifted_frustum_centers_on_offset_point() {
+        // An eye offset to the right, converging on a point straight ahead of the center.
+        let (offset, distance, near) = (0.5, 10.0, 0.1);
+        let shift = offset * near / distance;
+        let projection = frustum(-0.1 - shift, 0.1 - shift, -0.1, 0.1, near, 100.0);
+        let ndc = projection.transform_point(&na::Point3::new(-offset, 0.0, -distance));
+        assert!(ndc.x.abs() < 1e-5 && ndc.y.abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_hits_triangle() {
+        let triangle = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+        ];
+        let [a, b, c] = &triangle;
+        let origin = Point3::new(0.5, 0.5, 3.0);
+        let hit = intersect_ray_triangle(&origin, &-Vector3::z(), [a, b, c]);
+        assert!((hit.unwrap() - 3.0).abs() < 1e-6);
+        // Behind the ray and outside the triangle.
+        assert_eq!(
+            intersect_ray_triangle(&origin, &Vector3::z(), [a, b, c]),
+            None
+        );
+        let outside = Point3::new(1.5, 1.5, 3.0);
+        assert_eq!(
+            intersect_ray_triangle(&outside, &-Vector3::z(), [a, b, c]),
+            None
+        );
+    }
+
+    #[test]
+    fn barycentric_weights_corners() {
+        let triangle = [
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(3.0, 0.0, 0.0),
+            Point3::new(1.0, 4.0, 0.0),
+        ];
+        let [a, b, c] = &triangle;
+        let weights = barycentric(&Point3::new(1.5, 1.0, 2.0), [a, b, c]);
+        assert!((weights - Vector3::new(0.5, 0.25, 0.25)).norm() < 1e-6);
+        assert_eq!(barycentric(c, [a, b, c]), Vector3::new(0.0, 0.0, 1.0));
+    }
+
     #[test]
     fn handle_nan() {
         let ray_vector = Vector3::new(f32::NAN, f32::NAN, f32::NAN);
@@ -0,0 +1,191 @@
+//! Contains [HeadlessContext], an OpenGL context without a window for rendering on machines
+//! without a display. It is made through EGL on Mesa's surfaceless platform, or through OSMesa
+//! where that is missing. Both libraries are loaded when needed, so neither is required to run
+//! the application normally.
+
+use anyhow::{anyhow, Context, Result};
+use khronos_egl as egl;
+use osmesa_sys::{OSMesaContext, OsMesa};
+use std::{
+    ffi::{c_void, CString},
+    path::Path,
+    ptr,
+};
+
+/// `EGL_PLATFORM_SURFACELESS_MESA`, from the `EGL_MESA_platform_surfaceless` extension.
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+/// Names the OSMesa library is tried under, with and without the development symlink.
+const OSMESA_LIBRARIES: [&str; 2] = ["libOSMesa.so.8", "libOSMesa.so"];
+
+/// A current OpenGL 4.1 core context that isn't tied to a window. Rendering has to go to
+/// framebuffers of its own, as there is no default framebuffer to show. The context is
+/// destroyed when dropped.
+pub enum HeadlessContext {
+    Egl {
+        egl: Box<egl::DynamicInstance<egl::EGL1_5>>,
+        display: egl::Display,
+        context: egl::Context,
+    },
+    OsMesa {
+        osmesa: OsMesa,
+        context: OSMesaContext,
+        /// Pixel the context is made current with, as OSMesa always needs a color buffer.
+        _buffer: Box<[u8; 4]>,
+    },
+}
+
+impl HeadlessContext {
+    /// Creates a context and loads the OpenGL functions from it, trying EGL before OSMesa.
+    pub fn new() -> Result<Self> {
+        Self::egl().or_else(|egl_error| {
+            Self::osmesa().map_err(|osmesa_error| {
+                anyhow!(
+                    "Couldn't create an OpenGL context without a window.\n\
+                    EGL: {:#}\nOSMesa: {:#}",
+                    egl_error,
+                    osmesa_error
+                )
+            })
+        })
+    }
+
+    /// Returns which library made the context.
+    pub fn backend(&self) -> &'static str {
+        match self {
+            HeadlessContext::Egl { .. } => "EGL (surfaceless)",
+            HeadlessContext::OsMesa { .. } => "OSMesa",
+        }
+    }
+
+    fn egl() -> Result<Self> {
+        // Safety: libEGL is loaded by its standard name, and only used through its standard API.
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+            .map_err(|e| anyhow!("Failed to load libEGL: {}", e))?;
+        // Safety: The surfaceless platform takes no native display.
+        let display = unsafe {
+            egl.get_platform_display(
+                PLATFORM_SURFACELESS_MESA,
+                egl::DEFAULT_DISPLAY,
+                &[egl::ATTRIB_NONE],
+            )
+        }
+        .context("The surfaceless platform is not available")?;
+        egl.initialize(display)?;
+        egl.bind_api(egl::OPENGL_API)?;
+        let config = egl
+            .choose_first_config(
+                display,
+                &[
+                    egl::SURFACE_TYPE,
+                    egl::PBUFFER_BIT,
+                    egl::RENDERABLE_TYPE,
+                    egl::OPENGL_BIT,
+                    egl::NONE,
+                ],
+            )?
+            .ok_or_else(|| anyhow!("No EGL configuration supports OpenGL."))?;
+        let context = egl.create_context(
+            display,
+            config,
+            None,
+            &[
+                egl::CONTEXT_MAJOR_VERSION,
+                4,
+                egl::CONTEXT_MINOR_VERSION,
+                1,
+                egl::CONTEXT_OPENGL_PROFILE_MASK,
+                egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                egl::NONE,
+            ],
+        )?;
+        egl.make_current(display, None, None, Some(context))?;
+        gl::load_with(|s| {
+            egl.get_proc_address(s)
+                .map_or(ptr::null(), |f| f as *const c_void)
+        });
+        Ok(HeadlessContext::Egl {
+            egl: Box::new(egl),
+            display,
+            context,
+        })
+    }
+
+    fn osmesa() -> Result<Self> {
+        let osmesa = OSMESA_LIBRARIES
+            .iter()
+            .find_map(|name| OsMesa::open(Path::new(name)).ok())
+            .ok_or_else(|| anyhow!("Failed to load libOSMesa."))?;
+        let attributes = [
+            osmesa_sys::OSMESA_FORMAT,
+            osmesa_sys::OSMESA_RGBA as i32,
+            osmesa_sys::OSMESA_DEPTH_BITS,
+            24,
+            osmesa_sys::OSMESA_STENCIL_BITS,
+            8,
+            osmesa_sys::OSMESA_PROFILE,
+            osmesa_sys::OSMESA_CORE_PROFILE,
+            osmesa_sys::OSMESA_CONTEXT_MAJOR_VERSION,
+            4,
+            osmesa_sys::OSMESA_CONTEXT_MINOR_VERSION,
+            1,
+            0,
+        ];
+        // Safety: The attribute list is terminated by zero.
+        let context =
+            unsafe { (osmesa.OSMesaCreateContextAttribs)(attributes.as_ptr(), ptr::null_mut()) };
+        if context.is_null() {
+            return Err(anyhow!("OSMesa has no OpenGL 4.1 core context."));
+        }
+        let mut buffer = Box::new([0u8; 4]);
+        // Safety: The buffer holds the single RGBA pixel the context is made current with, and
+        // is kept alive along with the context.
+        let current = unsafe {
+            (osmesa.OSMesaMakeCurrent)(
+                context,
+                buffer.as_mut_ptr() as *mut c_void,
+                gl::UNSIGNED_BYTE,
+                1,
+                1,
+            )
+        };
+        if current == 0 {
+            // Safety: The context was just created and is not current.
+            unsafe { (osmesa.OSMesaDestroyContext)(context) };
+            return Err(anyhow!("Failed to make the OSMesa context current."));
+        }
+        gl::load_with(|s| {
+            let Ok(name) = CString::new(s) else {
+                return ptr::null();
+            };
+            // Safety: The name is a valid C string.
+            unsafe { (osmesa.OSMesaGetProcAddress)(name.as_ptr()) }
+                .map_or(ptr::null(), |f| f as *const c_void)
+        });
+        Ok(HeadlessContext::OsMesa {
+            osmesa,
+            context,
+            _buffer: buffer,
+        })
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        match self {
+            HeadlessContext::Egl {
+                egl,
+                display,
+                context,
+            } => {
+                // Failing to tear down is harmless, as the process is about to end.
+                let _ = egl.make_current(*display, None, None, None);
+                let _ = egl.destroy_context(*display, *context);
+                let _ = egl.terminate(*display);
+            }
+            // Safety: The context is only destroyed here, after all rendering.
+            HeadlessContext::OsMesa {
+                osmesa, context, ..
+            } => unsafe { (osmesa.OSMesaDestroyContext)(*context) },
+        }
+    }
+}
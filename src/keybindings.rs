@@ -0,0 +1,283 @@
+//! Contains [KeyBindings], mapping keys to [Action]s, and the user-editable configuration file
+//! they are read from.
+
+use crate::camera::Camera;
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+
+/// Name of the key binding file in the resource directory.
+pub const CONFIG_FILE: &str = "keybindings.cfg";
+/// Mouse movement units per second the model turns by while an orbit key is held.
+const ORBIT_SPEED: f32 = 250.0;
+/// Mouse movement units per second the model moves by while a pan key is held.
+const PAN_SPEED: f32 = 400.0;
+/// Mousewheel steps per second the camera zooms by while a zoom key is held.
+const ZOOM_SPEED: f32 = 4.0;
+
+/// Something a key can be bound to.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Action {
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    ZoomIn,
+    ZoomOut,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ResetView,
+    PresetPlain,
+    PresetAerial,
+    PresetColoredDepth,
+    ToggleHatching,
+    ToggleDebugBuffers,
+    ToggleHelp,
+}
+
+impl Action {
+    pub const ALL: [Action; 17] = [
+        Action::OrbitLeft,
+        Action::OrbitRight,
+        Action::OrbitUp,
+        Action::OrbitDown,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::PanLeft,
+        Action::PanRight,
+        Action::PanUp,
+        Action::PanDown,
+        Action::ResetView,
+        Action::PresetPlain,
+        Action::PresetAerial,
+        Action::PresetColoredDepth,
+        Action::ToggleHatching,
+        Action::ToggleDebugBuffers,
+        Action::ToggleHelp,
+    ];
+
+    /// Name of the action in the configuration file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::OrbitLeft => "orbit_left",
+            Action::OrbitRight => "orbit_right",
+            Action::OrbitUp => "orbit_up",
+            Action::OrbitDown => "orbit_down",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::PanLeft => "pan_left",
+            Action::PanRight => "pan_right",
+            Action::PanUp => "pan_up",
+            Action::PanDown => "pan_down",
+            Action::ResetView => "reset_view",
+            Action::PresetPlain => "preset_plain",
+            Action::PresetAerial => "preset_aerial",
+            Action::PresetColoredDepth => "preset_colored_depth",
+            Action::ToggleHatching => "toggle_hatching",
+            Action::ToggleDebugBuffers => "toggle_debug_buffers",
+            Action::ToggleHelp => "toggle_help",
+        }
+    }
+
+    /// Whether the action lasts for as long as the key is held, rather than happening once.
+    pub fn is_continuous(&self) -> bool {
+        matches!(
+            self,
+            Action::OrbitLeft
+                | Action::OrbitRight
+                | Action::OrbitUp
+                | Action::OrbitDown
+                | Action::ZoomIn
+                | Action::ZoomOut
+                | Action::PanLeft
+                | Action::PanRight
+                | Action::PanUp
+                | Action::PanDown
+        )
+    }
+
+    /// Keys bound to the action when the configuration file doesn't say otherwise.
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::OrbitLeft => &["Left", "A"],
+            Action::OrbitRight => &["Right", "D"],
+            Action::OrbitUp => &["Up", "W"],
+            Action::OrbitDown => &["Down", "S"],
+            Action::ZoomIn => &["=", "Keypad +"],
+            Action::ZoomOut => &["-", "Keypad -"],
+            Action::PanLeft => &["J"],
+            Action::PanRight => &["L"],
+            Action::PanUp => &["I"],
+            Action::PanDown => &["K"],
+            Action::ResetView => &["Home", "R"],
+            Action::PresetPlain => &["1"],
+            Action::PresetAerial => &["2"],
+            Action::PresetColoredDepth => &["3"],
+            Action::ToggleHatching => &["H"],
+            Action::ToggleDebugBuffers => &["B"],
+            Action::ToggleHelp => &["F1"],
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Action::OrbitLeft => write!(f, "Turn the model left"),
+            Action::OrbitRight => write!(f, "Turn the model right"),
+            Action::OrbitUp => write!(f, "Turn the model up"),
+            Action::OrbitDown => write!(f, "Turn the model down"),
+            Action::ZoomIn => write!(f, "Zoom in"),
+            Action::ZoomOut => write!(f, "Zoom out"),
+            Action::PanLeft => write!(f, "Move the model left"),
+            Action::PanRight => write!(f, "Move the model right"),
+            Action::PanUp => write!(f, "Move the model up"),
+            Action::PanDown => write!(f, "Move the model down"),
+            Action::ResetView => write!(f, "Reset the view"),
+            Action::PresetPlain => write!(f, "Plain preset"),
+            Action::PresetAerial => write!(f, "Aerial preset"),
+            Action::PresetColoredDepth => write!(f, "Colored depth preset"),
+            Action::ToggleHatching => write!(f, "Switch between shadows and hatching"),
+            Action::ToggleDebugBuffers => write!(f, "Show or hide shader buffers"),
+            Action::ToggleHelp => write!(f, "Show or hide key bindings"),
+        }
+    }
+}
+
+/// Maps SDL key names, like `A`, `Left` or `Keypad +`, to actions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    /// Keys bound to each action, in the order of [Action::ALL].
+    keys: Vec<Vec<String>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: Action::ALL
+                .iter()
+                .map(|action| {
+                    action
+                        .default_keys()
+                        .iter()
+                        .map(|&k| k.to_owned())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Parses a configuration file. Each line binds an action to a comma separated list of keys,
+    /// as in `zoom_in = =, Keypad +`, and lines starting with `#` are comments. Actions that
+    /// aren't mentioned keep their default keys, and an empty list leaves an action unbound.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut bindings = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, keys) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Line {}: expected `action = keys`.", number + 1))?;
+            let name = name.trim();
+            let index = Action::ALL
+                .iter()
+                .position(|action| action.name() == name)
+                .ok_or_else(|| anyhow!("Line {}: unknown action `{}`.", number + 1, name))?;
+            bindings.keys[index] = keys
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(str::to_owned)
+                .collect();
+        }
+        Ok(bindings)
+    }
+
+    /// Writes the bindings in the format read by [KeyBindings::parse], with an explanation at the
+    /// top for whoever edits the file.
+    pub fn to_config(&self) -> String {
+        let mut text = String::from(
+            "# Key bindings. Each line binds an action to a comma separated list of keys, using\n\
+             # SDL key names such as `A`, `Left`, `Keypad +` or `F1`. Changes apply when saved.\n",
+        );
+        for (action, keys) in Action::ALL.iter().zip(&self.keys) {
+            text.push_str(&format!("{} = {}\n", action.name(), keys.join(", ")));
+        }
+        text
+    }
+
+    /// Returns the action bound to the key with the given SDL name, ignoring case.
+    pub fn action(&self, key_name: &str) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .zip(&self.keys)
+            .find(|(_, keys)| keys.iter().any(|key| key.eq_ignore_ascii_case(key_name)))
+            .map(|(&action, _)| action)
+    }
+
+    /// Returns the keys bound to an action.
+    pub fn keys(&self, action: Action) -> &[String] {
+        let index = Action::ALL.iter().position(|&a| a == action).unwrap_or(0);
+        &self.keys[index]
+    }
+}
+
+/// Moves the camera for the held continuous actions, scaled by the time since the last frame.
+/// Returns true if the view has changed.
+pub fn move_camera(
+    camera: &mut Camera,
+    held: &HashSet<Action>,
+    frame_time: std::time::Duration,
+) -> bool {
+    let dt = frame_time.as_secs_f32();
+    let axis = |positive: Action, negative: Action| {
+        held.contains(&positive) as i32 as f32 - held.contains(&negative) as i32 as f32
+    };
+    let orbit = (
+        axis(Action::OrbitRight, Action::OrbitLeft),
+        axis(Action::OrbitDown, Action::OrbitUp),
+    );
+    let pan = (
+        axis(Action::PanRight, Action::PanLeft),
+        axis(Action::PanDown, Action::PanUp),
+    );
+    let zoom = axis(Action::ZoomIn, Action::ZoomOut);
+    if orbit != (0.0, 0.0) {
+        camera.orbit(orbit.0 * ORBIT_SPEED * dt, orbit.1 * ORBIT_SPEED * dt);
+    }
+    if pan != (0.0, 0.0) {
+        camera.pan(pan.0 * PAN_SPEED * dt, pan.1 * PAN_SPEED * dt);
+    }
+    if zoom != 0.0 {
+        camera.zoom(zoom * ZOOM_SPEED * dt);
+    }
+    orbit != (0.0, 0.0) || pan != (0.0, 0.0) || zoom != 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_round_trip() {
+        let bindings = KeyBindings::default();
+        assert_eq!(KeyBindings::parse(&bindings.to_config()).unwrap(), bindings);
+    }
+
+    #[test]
+    fn config_overrides_defaults() {
+        let text = "# Comment\n\nzoom_in = =, Page Up\nreset_view =\n";
+        let bindings = KeyBindings::parse(text).unwrap();
+        assert_eq!(bindings.action("page up"), Some(Action::ZoomIn));
+        assert_eq!(bindings.action("="), Some(Action::ZoomIn));
+        assert_eq!(bindings.action("Home"), None);
+        assert_eq!(bindings.action("left"), Some(Action::OrbitLeft));
+        assert!(KeyBindings::parse("spin = X").is_err());
+        assert!(KeyBindings::parse("zoom_in").is_err());
+    }
+}
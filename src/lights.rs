@@ -0,0 +1,163 @@
+//! Contains the [Light] description used for shading and shadows, and [LightBuffer], which hands
+//! the lights to the model shader through a uniform buffer.
+
+use crate::render_gl::buffer::UniformBuffer;
+use nalgebra as na;
+use serde::{Deserialize, Serialize};
+
+/// Largest number of lights the model shader handles. Must match `MAX_LIGHTS` in `model.frag`.
+pub const MAX_LIGHTS: usize = 8;
+/// Uniform buffer binding point of the `Lights` block in `model.frag`.
+const LIGHTS_BINDING: gl::types::GLuint = 0;
+
+/// Represents how light is emitted from a [Light].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum LightKind {
+    /// Parallel light arriving from a direction, like sunlight.
+    #[default]
+    Directional = 0,
+    /// Light spreading out from a position. Point lights are not attenuated with distance.
+    Point = 1,
+}
+
+impl std::fmt::Display for LightKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            LightKind::Directional => write!(f, "Directional"),
+            LightKind::Point => write!(f, "Point"),
+        }
+    }
+}
+
+/// A light source shading the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Position of a point light, or the direction towards a directional light. When
+    /// [Light::camera_attached] is set this is in view space, with X to the right, Y up and Z
+    /// towards the viewer.
+    pub position: na::Vector3<f32>,
+    /// Moves the light along with the camera instead of keeping it fixed in the world.
+    pub camera_attached: bool,
+}
+
+impl Light {
+    /// Returns the light's position or direction in world space, given the camera view matrix.
+    pub fn world_position(&self, view_matrix: &na::Matrix4<f32>) -> na::Vector3<f32> {
+        if !self.camera_attached {
+            return self.position;
+        }
+        let inverse = view_matrix
+            .try_inverse()
+            .unwrap_or_else(na::Matrix4::identity);
+        match self.kind {
+            LightKind::Directional => inverse.transform_vector(&self.position),
+            LightKind::Point => inverse.transform_point(&self.position.into()).coords,
+        }
+    }
+
+    /// A white headlight at the camera, matching the original single light.
+    pub fn headlight() -> Self {
+        Self {
+            kind: LightKind::Point,
+            color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+            position: na::Vector3::zeros(),
+            camera_attached: true,
+        }
+    }
+
+    /// A world-fixed directional light.
+    pub fn directional(direction: na::Vector3<f32>, intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional,
+            color: [1.0, 1.0, 1.0],
+            intensity,
+            position: direction,
+            camera_attached: false,
+        }
+    }
+}
+
+/// Layout of a single light in the `Lights` uniform block, following std140 rules.
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+struct LightData {
+    /// World position or direction, with the [LightKind] in `w`.
+    position: [f32; 4],
+    /// Linear colour, with the intensity in `w`.
+    color: [f32; 4],
+}
+
+/// Layout of the `Lights` uniform block, following std140 rules.
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+struct LightBlock {
+    lights: [LightData; MAX_LIGHTS],
+    count: u32,
+    _padding: [u32; 3],
+}
+
+/// Uniform buffer holding the lights for the model shader.
+pub struct LightBuffer {
+    buffer: UniformBuffer,
+}
+
+impl LightBuffer {
+    /// Creates the uniform buffer.
+    pub fn new() -> Self {
+        Self {
+            buffer: UniformBuffer::new(),
+        }
+    }
+
+    /// Uploads the given lights, resolving camera-attached lights with the view matrix, and binds
+    /// the buffer to the `Lights` block. Lights past [MAX_LIGHTS] are ignored.
+    pub fn upload(&self, lights: &[Light], view_matrix: &na::Matrix4<f32>) {
+        let mut block = LightBlock::default();
+        for (data, light) in block.lights.iter_mut().zip(lights) {
+            let p = light.world_position(view_matrix);
+            data.position = [p.x, p.y, p.z, light.kind as u32 as f32];
+            let [r, g, b] = light.color;
+            data.color = [r, g, b, light.intensity];
+        }
+        block.count = lights.len().min(MAX_LIGHTS) as u32;
+        self.buffer.dynamic_draw_data(std::slice::from_ref(&block));
+        self.buffer.bind_base(LIGHTS_BINDING);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_block_layout() {
+        // Two vec4s per light, then the count padded to a vec4.
+        assert_eq!(std::mem::size_of::<LightData>(), 32);
+        assert_eq!(std::mem::size_of::<LightBlock>(), 32 * MAX_LIGHTS + 16);
+    }
+
+    #[test]
+    fn camera_attached_follows_view() {
+        let eye = na::Point3::new(0.0, 0.0, 5.0);
+        let view = na::Isometry3::look_at_rh(&eye, &na::Point3::origin(), &na::Vector3::y())
+            .to_homogeneous();
+        let headlight = Light::headlight();
+        assert!((headlight.world_position(&view) - eye.coords).norm() < 1e-5);
+
+        let mut directional = Light::directional(na::Vector3::z(), 1.0);
+        directional.camera_attached = true;
+        let rotated = na::Isometry3::look_at_rh(
+            &na::Point3::new(5.0, 0.0, 0.0),
+            &na::Point3::origin(),
+            &na::Vector3::y(),
+        )
+        .to_homogeneous();
+        assert!((directional.world_position(&rotated) - na::Vector3::x()).norm() < 1e-5);
+    }
+}
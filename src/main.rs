@@ -1,20 +1,52 @@
 #![doc = include_str!("../README.md")]
 #![allow(clippy::missing_safety_doc)]
 
+mod animation;
+mod annotations;
+mod antialiasing;
+mod batch;
+mod bvh;
 mod camera;
+mod capture;
+mod clipping;
+mod colormap;
+mod curvature;
+mod flythrough;
 mod geometry;
+mod headless;
+mod keybindings;
+mod lights;
+mod measurement;
 mod model;
 pub mod render_gl;
 pub mod resources;
+mod settings;
+mod stereo;
 mod texture_tester;
+mod transparency;
 mod ui;
+mod views;
 
 use nalgebra as na;
-use sdl2::event::Event;
-use std::path::Path;
+use sdl2::{event::Event, mouse::MouseButton};
+use std::{collections::HashSet, path::Path};
 use texture_tester::TextureTester;
 
-use crate::{model::Model, resources::Resources, ui::UI};
+use crate::{
+    animation::{CameraPose, SequenceExport},
+    annotations::Annotation,
+    antialiasing::{AntiAliasing, SceneBuffer},
+    camera::{Camera, DragMode},
+    capture::{CaptureSettings, OffscreenTarget},
+    flythrough::{CameraPath, Flythrough},
+    keybindings::{Action, KeyBindings},
+    model::Model,
+    resources::Resources,
+    settings::SettingsFile,
+    stereo::{StereoBuffer, StereoMode},
+    ui::{ClickTool, UiActions, UI},
+    views::SavedView,
+};
 
 #[cfg(debug_assertions)]
 #[cfg(target_os = "linux")]
@@ -25,11 +57,30 @@ const ASSETS_PATH: &str = "..\\..\\assets";
 #[cfg(not(debug_assertions))]
 const ASSETS_PATH: &str = "assets";
 const DEFAULT_MODEL_PATH: &str = "model.obj";
+/// Length of the animated move to a saved or standard view.
+const VIEW_TRANSITION: std::time::Duration = std::time::Duration::from_millis(750);
+/// Pixels the mouse may move between pressing and releasing a button for it to count as a click.
+const CLICK_TOLERANCE: f32 = 4.0;
 
 fn main() {
     let res =
         Resources::from_relative_exe_path(Path::new(&ASSETS_PATH)).expect("Failed to find assets");
 
+    // Batch rendering runs without a window, before anything of the UI is set up.
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--batch") {
+        let Some(job_file) = args.get(i + 1) else {
+            eprintln!("Usage: ivrigst --batch <job file>");
+            std::process::exit(2);
+        };
+        if let Err(e) = batch::run(&res, Path::new(job_file)) {
+            eprintln!("Batch rendering failed:");
+            eprintln!("{:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let sdl = sdl2::init().unwrap();
     let video_subsystem = sdl.video().unwrap();
 
@@ -37,11 +88,14 @@ fn main() {
 
     gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
     gl_attr.set_context_version(4, 1);
+    // Clipping plane caps are drawn with a stencil test.
+    gl_attr.set_stencil_size(8);
 
     let window = video_subsystem
         .window("IVRIGST", 1200, 800)
         .opengl()
         .resizable()
+        .allow_highdpi()
         .build()
         .unwrap();
 
@@ -54,26 +108,37 @@ fn main() {
         ui.apply_preset(model);
     }
 
-    // set up shared state for window
-    let mut viewport =
-        render_gl::Viewport::for_window(window.size().0 as i32, window.size().1 as i32);
+    // set up shared state for window. The viewport is in physical pixels, while egui and mouse
+    // events use the logical window size.
+    let (drawable_w, drawable_h) = window.drawable_size();
+    let mut viewport = render_gl::Viewport::for_window(drawable_w as i32, drawable_h as i32);
+    let mut pixels_per_point = drawable_w as f32 / window.size().0.max(1) as f32;
     viewport.set_used();
-    let mut color_buffer = render_gl::ColorBuffer::from_color(na::Vector3::new(0.3, 0.3, 0.5));
+    let mut color_buffer = render_gl::ColorBuffer::from_color(settings::DEFAULT_BACKGROUND.into());
 
     // Camera and projection
     let model_isometry = na::Isometry3::new(na::Vector3::zeros(), na::zero());
-    let mut camera = camera::Camera::new();
-    camera.set_dist(
-        model
-            .as_ref()
-            .map(|m| m.get_size().magnitude() * 1.2)
-            .unwrap_or(100.0),
-    );
+    let mut camera = Camera::new();
+    camera.set_screen_size(window.size());
+    if let Some(model) = model.as_ref() {
+        camera.set_scene_radius(model.get_size().magnitude() / 2.0);
+    }
+    camera.set_state(&camera.home_state());
+
+    let mut key_bindings = load_key_bindings(&res);
+    // Camera movement keys currently held down, and key presses to handle this frame.
+    let mut held_actions = HashSet::new();
+    let mut pressed_actions = Vec::new();
 
     render_gl::check_gl_error();
 
-    let time = std::time::Instant::now();
+    // Animation time only advances while not frozen, so animations resume where they stopped.
+    let mut last_frame = std::time::Instant::now();
+    let mut animation_time_ms = 0.0;
     let mut texture_tester = TextureTester::new(&res).expect("Failed to set up texture tester.");
+    let mut scene_buffer = SceneBuffer::new(&res).expect("Failed to set up scene buffer.");
+    let mut stereo_buffer = StereoBuffer::new(&res).expect("Failed to set up stereo buffer.");
+    let mut offscreen = OffscreenTarget::new(&res).expect("Failed to set up offscreen target.");
 
     let mut cursor: sdl2::mouse::Cursor;
     let ctx = egui::Context::default();
@@ -82,15 +147,57 @@ fn main() {
         Some(_) => DEFAULT_MODEL_PATH.to_owned(),
         None => String::new(),
     };
-    let mut ui_actions = ui::UiActions {
+    let (annotations, saved_views) = model
+        .as_ref()
+        .map(|m| load_sidecar(&res, &current_model_file, m))
+        .unwrap_or_default();
+    let mut ui_actions = UiActions {
         show_debug: false,
         file_to_load: current_model_file.clone(),
         clear_color: color_buffer.color.xyz(),
+        anti_aliasing: Default::default(),
+        msaa_samples: 4,
+        stereo: Default::default(),
+        projection: Default::default(),
+        rotation_mode: Default::default(),
+        reversed_z: false,
+        camera_state: camera.state(),
+        camera_request: None,
+        flythrough: None,
+        centerline_to_load: None,
+        surface_readout: true,
+        hovered: None,
+        click_tool: ClickTool::None,
+        surface_click: None,
+        measurements_to_export: None,
+        annotations,
+        saved_views,
+        save_sidecar: false,
+        pivot_request: None,
+        screenshot_request: None,
+        screenshot_status: None,
+        sequence_request: None,
+        sequence_progress: None,
+        cancel_sequence: false,
+        sequence_status: None,
+        settings_to_save: None,
+        settings_to_load: None,
+        restore_camera: true,
     };
+    // Image sequence being exported, which makes the loop advance by a fixed time step.
+    let mut sequence: Option<SequenceExport> = None;
 
     let mut event_pump = sdl.event_pump().unwrap();
+    // Last known mouse position, for zooming towards the cursor.
+    let mut cursor_position = (0.0, 0.0);
+    let mut hover_needs_update = false;
+    // Where the left mouse button was pressed, to tell clicks from drags.
+    let mut press_position = None;
     'main: loop {
-        let mut raw_input: egui::RawInput = Default::default();
+        let mut raw_input = egui::RawInput {
+            pixels_per_point: Some(pixels_per_point),
+            ..Default::default()
+        };
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => break 'main,
@@ -98,8 +205,12 @@ fn main() {
                     win_event: sdl2::event::WindowEvent::Resized(w, h),
                     ..
                 } => {
-                    viewport.update_size(w, h);
+                    let (drawable_w, drawable_h) = window.drawable_size();
+                    viewport.update_size(drawable_w as i32, drawable_h as i32);
                     viewport.set_used();
+                    pixels_per_point = drawable_w as f32 / w.max(1) as f32;
+                    camera.set_screen_size((w as u32, h as u32));
+                    raw_input.pixels_per_point = Some(pixels_per_point);
                     mvp_needs_update = true;
                     raw_input.screen_rect = Some(egui::Rect::from_min_size(
                         egui::Pos2::ZERO,
@@ -107,9 +218,49 @@ fn main() {
                     ));
                 }
                 Event::MouseButtonDown {
-                    mouse_btn, x, y, ..
+                    mouse_btn,
+                    clicks,
+                    x,
+                    y,
+                    ..
                 } => {
-                    camera.mousedown();
+                    let shift = sdl.keyboard().mod_state().intersects(
+                        sdl2::keyboard::Mod::LSHIFTMOD | sdl2::keyboard::Mod::RSHIFTMOD,
+                    );
+                    // Grabbing the view stops the camera from flying on.
+                    if let Some(flight) = ui_actions
+                        .flythrough
+                        .as_mut()
+                        .filter(|_| !ctx.wants_pointer_input())
+                    {
+                        flight.playing = false;
+                    }
+                    camera.mousedown(match mouse_btn {
+                        MouseButton::Middle => DragMode::Pan,
+                        MouseButton::Left if shift => DragMode::Pan,
+                        _ => DragMode::Rotate,
+                    });
+
+                    if mouse_btn == MouseButton::Left && !ctx.wants_pointer_input() {
+                        press_position = Some((x as f32, y as f32));
+                    }
+
+                    // Double-clicking the model makes the camera orbit around the clicked point.
+                    if clicks == 2
+                        && mouse_btn == MouseButton::Left
+                        && !ctx.wants_pointer_input()
+                        && ui_actions.click_tool == ClickTool::None
+                    {
+                        let (origin, direction) = camera.ray((x as f32, y as f32));
+                        if let Some(point) = model
+                            .as_ref()
+                            .and_then(|m| m.pick(&origin, &direction))
+                            .map(|hit| hit.position)
+                        {
+                            camera.set_pivot(point);
+                            mvp_needs_update = true;
+                        }
+                    }
                     raw_input.events.push(egui::Event::PointerButton {
                         pos: egui::pos2(x as f32, y as f32),
                         button: ui::sdl2_to_egui_pointerbutton(mouse_btn),
@@ -121,6 +272,19 @@ fn main() {
                     mouse_btn, x, y, ..
                 } => {
                     camera.mouseup();
+
+                    // Clicking without dragging picks a point on the surface for the click tool.
+                    let clicked = press_position.take().filter(|&(px, py): &(f32, f32)| {
+                        mouse_btn == MouseButton::Left
+                            && (px - x as f32).hypot(py - y as f32) < CLICK_TOLERANCE
+                    });
+                    if let Some(position) =
+                        clicked.filter(|_| ui_actions.click_tool != ClickTool::None)
+                    {
+                        let (origin, direction) = camera.ray(position);
+                        ui_actions.surface_click =
+                            model.as_ref().and_then(|m| m.pick(&origin, &direction));
+                    }
                     raw_input.events.push(egui::Event::PointerButton {
                         pos: egui::pos2(x as f32, y as f32),
                         button: ui::sdl2_to_egui_pointerbutton(mouse_btn),
@@ -134,6 +298,8 @@ fn main() {
                     raw_input
                         .events
                         .push(egui::Event::PointerMoved(egui::pos2(x as f32, y as f32)));
+                    cursor_position = (x as f32, y as f32);
+                    hover_needs_update = true;
 
                     if !ctx.wants_pointer_input() {
                         let view_updated = camera.mousemove(xrel, yrel);
@@ -141,7 +307,7 @@ fn main() {
                     }
                 }
                 Event::MouseWheel { y, .. } => {
-                    camera.mousewheel(y);
+                    camera.mousewheel(y, Some(cursor_position));
                     raw_input.events.push(egui::Event::Scroll(egui::Vec2 {
                         x: 0.0,
                         y: y as f32,
@@ -151,8 +317,18 @@ fn main() {
                 Event::KeyDown {
                     keycode: Some(keycode),
                     keymod,
+                    repeat,
                     ..
                 } => {
+                    if !ctx.wants_keyboard_input() {
+                        match key_bindings.action(&keycode.name()) {
+                            Some(action) if action.is_continuous() => {
+                                held_actions.insert(action);
+                            }
+                            Some(action) if !repeat => pressed_actions.push(action),
+                            _ => {}
+                        }
+                    }
                     if let Some(event) = ui::sdl2_to_egui_key(keycode, keymod, true) {
                         raw_input.events.push(event);
                     }
@@ -165,6 +341,9 @@ fn main() {
                     keymod,
                     ..
                 } => {
+                    if let Some(action) = key_bindings.action(&keycode.name()) {
+                        held_actions.remove(&action);
+                    }
                     if let Some(event) = ui::sdl2_to_egui_key(keycode, keymod, false) {
                         raw_input.events.push(event);
                     }
@@ -174,8 +353,111 @@ fn main() {
         }
 
         // UI handling
+        for action in pressed_actions.drain(..) {
+            if action == Action::ResetView {
+                camera.animate_to(camera.home_state(), VIEW_TRANSITION);
+            } else {
+                ui.handle_action(action, &mut model, &mut ui_actions);
+            }
+        }
         ctx.begin_frame(raw_input);
-        ui.build_ui(&ctx, &mut model, &mut ui_actions);
+        ui_actions.camera_state = camera.state();
+        ui.build_ui(&ctx, &mut model, &mut ui_actions, &key_bindings);
+        if let Some(state) = ui_actions.camera_request.take() {
+            camera.animate_to(state, VIEW_TRANSITION);
+        }
+        if let Some(point) = ui_actions.pivot_request.take() {
+            camera.set_pivot(point);
+            mvp_needs_update = true;
+        }
+        if std::mem::take(&mut ui_actions.save_sidecar) {
+            if let Some(model) = model.as_ref() {
+                save_sidecar(&res, &current_model_file, model, &ui_actions);
+            }
+        }
+        if let Some(file) = ui_actions.settings_to_save.take() {
+            if let Some(model) = model.as_ref() {
+                if let Err(e) = current_settings(model, &camera, &ui_actions)
+                    .to_json()
+                    .and_then(|json| res.save_string(&file, &json))
+                {
+                    eprintln!("Couldn't save settings to {}:", file);
+                    eprintln!("{}", e);
+                }
+            }
+        }
+        if let Some(file) = ui_actions.settings_to_load.take() {
+            match SettingsFile::load(&res.path(&file)) {
+                Ok(settings) => {
+                    if let Some(model) = model.as_mut() {
+                        model.set_attributes(settings.apply(model.get_attributes()));
+                    }
+                    ui_actions.clear_color = settings.background.into();
+                    ui_actions.anti_aliasing = settings.anti_aliasing;
+                    ui_actions.msaa_samples = settings.msaa_samples;
+                    ui_actions.projection = settings.projection;
+                    if let Some(state) = settings.camera.filter(|_| ui_actions.restore_camera) {
+                        camera.animate_to(state, VIEW_TRANSITION);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Couldn't load settings from {}:", file);
+                    eprintln!("{}", e);
+                }
+            }
+        }
+        if let Some(csv) = ui_actions.measurements_to_export.take() {
+            let file = sidecar_name(&current_model_file, "_measurements.csv");
+            if let Err(e) = res.save_string(&file, &csv) {
+                eprintln!("Couldn't save measurements to {}:", file);
+                eprintln!("{}", e);
+            }
+        }
+        if let Some(settings) = ui_actions.sequence_request.take() {
+            let name = format!(
+                "{}_{}",
+                current_model_file.trim_end_matches(".obj"),
+                unix_time()
+            );
+            let attributes = model.as_ref().map(|m| m.get_attributes().clone());
+            match SequenceExport::new(
+                settings,
+                &name,
+                camera.state(),
+                &attributes.unwrap_or_default(),
+                ui_actions.flythrough.as_ref(),
+                animation_time_ms,
+            ) {
+                Ok(export) => {
+                    ui_actions.sequence_status = None;
+                    sequence = Some(export);
+                }
+                Err(e) => {
+                    eprintln!("Couldn't start the sequence export:");
+                    eprintln!("{}", e);
+                    ui_actions.sequence_status = Some(e.to_string());
+                }
+            }
+        }
+        if std::mem::take(&mut ui_actions.cancel_sequence) {
+            if let Some(export) = sequence.take() {
+                ui_actions.sequence_status =
+                    Some(match finish_sequence(export, &mut camera, model.as_mut()) {
+                        Ok(saved) => format!("Cancelled. {}", saved),
+                        Err(e) => format!("Cancelled. {}", e),
+                    });
+                mvp_needs_update = true;
+            }
+        }
+        if let Some(file) = ui_actions.centerline_to_load.take() {
+            match load_centerline(&res, &file, model.as_ref()) {
+                Ok(path) => ui_actions.flythrough = Some(Flythrough::new(path)),
+                Err(e) => {
+                    eprintln!("Couldn't load centerline {}:", file);
+                    eprintln!("{}", e);
+                }
+            }
+        }
         let full_output = ctx.end_frame();
         let clipped_primitives: Vec<egui::ClippedPrimitive> = ctx.tessellate(full_output.shapes);
         ui.renderer.egui_texture_delta(full_output.textures_delta);
@@ -193,46 +475,102 @@ fn main() {
             }
         }
 
+        // Draw the model offscreen so it can be anti-aliased before the UI is drawn on top.
+        // In stereo, each eye is anti-aliased before the images are combined instead.
+        let scene_anti_aliasing = match ui_actions.stereo.mode {
+            StereoMode::Off => ui_actions.anti_aliasing,
+            _ => AntiAliasing::Off,
+        };
+        scene_buffer.begin(&viewport, scene_anti_aliasing, ui_actions.msaa_samples);
+        let reversed_z = ui_actions.reversed_z && render_gl::reversed_z_supported();
+        // Safety: Reversed-Z is only used when supported.
+        unsafe {
+            render_gl::set_depth_convention(reversed_z);
+        }
         color_buffer.clear();
 
-        // Update camera if necessary.
-        if mvp_needs_update && model.is_some() {
-            let model = model.as_mut().unwrap();
-            let mut attr = model.get_attributes().clone();
-
+        // Update camera if necessary. While exporting a sequence, time advances by a fixed step
+        // per frame, however long the frames take to render.
+        let frame_time = match sequence.as_ref() {
+            Some(export) => export.frame_time(),
+            None => last_frame.elapsed(),
+        };
+        last_frame = std::time::Instant::now();
+        if ui_actions.projection != camera.projection() {
+            camera.set_projection(ui_actions.projection);
+            mvp_needs_update = true;
+        }
+        if ui_actions.rotation_mode != camera.rotation_mode() {
+            camera.set_rotation_mode(ui_actions.rotation_mode);
+            mvp_needs_update = true;
+        }
+        if reversed_z != camera.reversed_z() {
+            camera.set_reversed_z(reversed_z);
+            mvp_needs_update = true;
+        }
+        mvp_needs_update |= camera.update(frame_time);
+        mvp_needs_update |= keybindings::move_camera(&mut camera, &held_actions, frame_time);
+        if let Some(flight) = ui_actions.flythrough.as_mut() {
+            if let Some((eye, orientation)) = flight.update(frame_time) {
+                camera.fly_to(eye, orientation);
+                mvp_needs_update = true;
+            }
+        }
+        if let Some(export) = sequence.as_ref() {
+            match export.camera_pose() {
+                CameraPose::Orbit(state) => camera.set_state(&state),
+                CameraPose::Flight(eye, orientation) => camera.fly_to(eye, orientation),
+            }
+            mvp_needs_update = true;
+        }
+        if let Some(model) = model.as_mut().filter(|_| mvp_needs_update) {
             let aspect = viewport.size().0 as f32 / viewport.size().1 as f32;
-            let model_view_projection = camera.construct_mvp(aspect, model_isometry);
-            let c = camera.position();
-            attr.camera_position = na::Vector3::new(c[0], c[1], c[2]);
-            attr.projection_matrix = model_view_projection;
+            model.set_camera(&camera, model_isometry, aspect);
+            let mut attr = model.get_attributes().clone();
+            attr.pixels_per_point = pixels_per_point;
             model.set_attributes(attr);
             mvp_needs_update = false;
+            hover_needs_update = true;
+        }
+
+        // Find the surface under the cursor again when the cursor or the view has moved.
+        if hover_needs_update && ui_actions.surface_readout {
+            let (origin, direction) = camera.ray(cursor_position);
+            ui_actions.hovered = model.as_ref().and_then(|m| m.pick(&origin, &direction));
+            hover_needs_update = false;
         }
 
         // Render the model
         if let Some(model) = model.as_mut() {
-            let elapsed = time.elapsed();
             let mut attr = model.get_attributes().clone();
-            attr.elapsed = elapsed.as_millis() as f32;
+            if !attr.freeze_time {
+                animation_time_ms = match sequence.as_ref() {
+                    Some(export) => export.elapsed_ms(),
+                    None => animation_time_ms + frame_time.as_secs_f32() * 1000.0,
+                };
+            }
+            attr.elapsed = animation_time_ms;
             model.set_attributes(attr);
-            model.render(&viewport);
+            if ui_actions.stereo.mode == StereoMode::Off {
+                model.render(&viewport);
+            } else {
+                stereo_buffer.render(
+                    model,
+                    &camera,
+                    model_isometry,
+                    &viewport,
+                    &ui_actions.stereo,
+                    ui_actions.anti_aliasing,
+                    ui_actions.msaa_samples,
+                );
+            }
+        }
+        unsafe {
+            scene_buffer.finish(&viewport);
         }
 
         // Render the UI
-        for egui::ClippedPrimitive {
-            clip_rect,
-            primitive,
-        } in clipped_primitives.into_iter()
-        {
-            let mesh = match primitive {
-                egui::epaint::Primitive::Mesh(mesh) => mesh,
-                _ => panic!("Custom paint callbacks and other primitives are not supported."),
-            };
-            debug_assert!(mesh.is_valid());
-
-            ui.renderer
-                .render(&mesh.vertices, &mesh.indices, clip_rect, viewport.size());
-        }
+        render_ui(&ui.renderer, &clipped_primitives, window.size());
 
         // Render debug textures if chosen
         if let Some(model) = model.as_mut() {
@@ -245,13 +583,127 @@ fn main() {
             }
         }
 
+        // Render the requested screenshot offscreen, at its own size.
+        if let (Some(options), Some(model)) = (ui_actions.screenshot_request.take(), model.as_mut())
+        {
+            let stereo = (ui_actions.stereo.mode != StereoMode::Off)
+                .then_some((&mut stereo_buffer, &ui_actions.stereo));
+            let settings = CaptureSettings {
+                size: options.size,
+                transparent: options.transparent,
+                anti_aliasing: ui_actions.anti_aliasing,
+                msaa_samples: ui_actions.msaa_samples,
+                pixels_per_point: pixels_per_point * options.size.1 as f32
+                    / viewport.size().1.max(1) as f32,
+            };
+            let image = offscreen.render(
+                model,
+                &camera,
+                model_isometry,
+                stereo,
+                &settings,
+                &ui_actions.clear_color,
+                |_| {
+                    if options.include_ui {
+                        render_ui(&ui.renderer, &clipped_primitives, window.size());
+                    }
+                },
+            );
+            viewport.set_used();
+            let settings = current_settings(model, &camera, &ui_actions);
+            let stem = format!(
+                "{}_{}",
+                current_model_file.trim_end_matches(".obj"),
+                unix_time()
+            );
+            let path = capture::unique_path(Path::new(&options.folder), &stem);
+            ui_actions.screenshot_status = Some(
+                match image.and_then(|image| {
+                    let text = capture::metadata(&current_model_file, &settings)?;
+                    capture::save_png(&path, &image, &text)
+                }) {
+                    Ok(()) => format!("Saved {}", path.display()),
+                    Err(e) => {
+                        eprintln!("Couldn't save the screenshot: {}", e);
+                        format!("Couldn't save the screenshot: {}", e)
+                    }
+                },
+            );
+        }
+
+        // Render the next frame of the sequence being exported.
+        if let (Some(export), Some(model)) = (sequence.as_mut(), model.as_mut()) {
+            let stereo = (ui_actions.stereo.mode != StereoMode::Off)
+                .then_some((&mut stereo_buffer, &ui_actions.stereo));
+            let settings = CaptureSettings {
+                size: export.settings.size,
+                transparent: export.settings.transparent,
+                anti_aliasing: ui_actions.anti_aliasing,
+                msaa_samples: ui_actions.msaa_samples,
+                pixels_per_point: pixels_per_point * export.settings.size.1 as f32
+                    / viewport.size().1.max(1) as f32,
+            };
+            let mut attr = model.get_attributes().clone();
+            export.animate(&mut attr);
+            model.set_attributes(attr);
+            let image = offscreen.render(
+                model,
+                &camera,
+                model_isometry,
+                stereo,
+                &settings,
+                &ui_actions.clear_color,
+                |_| {},
+            );
+            viewport.set_used();
+            let settings = current_settings(model, &camera, &ui_actions);
+            let added = image.and_then(|image| {
+                let mut text = capture::metadata(&current_model_file, &settings)?;
+                text.push((
+                    "Frame",
+                    format!("{} of {}", export.frame + 1, export.settings.frames),
+                ));
+                export.add_frame(&image, &text)
+            });
+            ui_actions.sequence_progress = Some((export.frame, export.settings.frames));
+            if let Err(e) = added {
+                eprintln!("Couldn't export the sequence:");
+                eprintln!("{}", e);
+                ui_actions.sequence_status = Some(format!("Stopped: {}", e));
+                // Still let ffmpeg finish, so it doesn't wait for frames forever.
+                let export = sequence.take().expect("The sequence is being exported");
+                if let Err(e) = finish_sequence(export, &mut camera, Some(model)) {
+                    eprintln!("Couldn't finish the sequence export:");
+                    eprintln!("{}", e);
+                }
+                mvp_needs_update = true;
+            } else if export.is_done() {
+                let export = sequence.take().expect("The sequence is being exported");
+                ui_actions.sequence_status = Some(
+                    finish_sequence(export, &mut camera, Some(model)).unwrap_or_else(|e| {
+                        eprintln!("Couldn't finish the sequence export:");
+                        eprintln!("{}", e);
+                        e.to_string()
+                    }),
+                );
+                mvp_needs_update = true;
+            }
+        }
+        if sequence.is_none() {
+            ui_actions.sequence_progress = None;
+        }
+
         // Check if model should be reloaded
         if ui_actions.file_to_load != current_model_file {
             let mut path = ui_actions.file_to_load.clone();
             path.push_str(".obj");
             if let Ok(mut new_model) = Model::new(&res, &path) {
-                camera.set_dist(new_model.get_size().magnitude() * 1.2);
+                camera.set_scene_radius(new_model.get_size().magnitude() / 2.0);
+                camera.set_state(&camera.home_state());
                 ui.apply_preset(&mut new_model);
+                ui.clear_measurements();
+                (ui_actions.annotations, ui_actions.saved_views) =
+                    load_sidecar(&res, &ui_actions.file_to_load, &new_model);
                 model = Some(new_model);
                 mvp_needs_update = true;
                 current_model_file = ui_actions.file_to_load.clone();
@@ -272,6 +724,145 @@ fn main() {
                 model.check_shader_update(&path, &res);
             }
             texture_tester.check_shader_update(&path, &res);
+            scene_buffer.check_shader_update(&path, &res);
+            stereo_buffer.check_shader_update(&path, &res);
+            if path.file_name() == Some(keybindings::CONFIG_FILE.as_ref()) {
+                key_bindings = load_key_bindings(&res);
+            }
+        }
+    }
+}
+
+/// Reads the key bindings from the resource directory, writing the defaults there first if the
+/// file doesn't exist yet so they can be edited.
+fn load_key_bindings(res: &Resources) -> KeyBindings {
+    if !res.path(keybindings::CONFIG_FILE).exists() {
+        let bindings = KeyBindings::default();
+        if let Err(e) = res.save_string(keybindings::CONFIG_FILE, &bindings.to_config()) {
+            eprintln!("Could not write key bindings: {}", e);
+        }
+        return bindings;
+    }
+    match res
+        .load_string(keybindings::CONFIG_FILE)
+        .and_then(|text| KeyBindings::parse(&text))
+    {
+        Ok(bindings) => bindings,
+        Err(e) => {
+            eprintln!("Key binding error, using defaults: {}", e);
+            KeyBindings::default()
         }
     }
 }
+
+/// Reads a centerline from the resource directory and makes a camera path of it, moving its
+/// points from the obj file's coordinates into those of the loaded model.
+fn load_centerline(
+    res: &Resources,
+    file: &str,
+    model: Option<&Model>,
+) -> anyhow::Result<CameraPath> {
+    let offset = model.map_or_else(na::Vector3::zeros, |m| *m.get_center());
+    let points = flythrough::parse_centerline(&res.load_string(file)?)?;
+    CameraPath::new(points.into_iter().map(|p| p - offset).collect())
+}
+
+/// Returns the render settings in use, with the current camera.
+fn current_settings(model: &Model, camera: &Camera, ui_actions: &UiActions) -> SettingsFile {
+    SettingsFile {
+        attributes: model.get_attributes().clone(),
+        background: ui_actions.clear_color.into(),
+        anti_aliasing: ui_actions.anti_aliasing,
+        msaa_samples: ui_actions.msaa_samples,
+        projection: ui_actions.projection,
+        camera: Some(camera.state()),
+        ..Default::default()
+    }
+}
+
+/// Ends a sequence export, putting the camera and the model's keyframed settings back as they
+/// were before it.
+fn finish_sequence(
+    export: SequenceExport,
+    camera: &mut Camera,
+    model: Option<&mut Model>,
+) -> anyhow::Result<String> {
+    let Some(model) = model else {
+        return export.finish(camera, None);
+    };
+    let mut attr = model.get_attributes().clone();
+    let finished = export.finish(camera, Some(&mut attr));
+    model.set_attributes(attr);
+    finished
+}
+
+/// Returns the name of a file kept next to a model file, replacing its `.obj` with `suffix`.
+fn sidecar_name(model_file: &str, suffix: &str) -> String {
+    format!("{}{}", model_file.trim_end_matches(".obj"), suffix)
+}
+
+/// Reads the annotations and saved views kept next to a model file, if there are any.
+fn load_sidecar(
+    res: &Resources,
+    model_file: &str,
+    model: &Model,
+) -> (Vec<Annotation>, Vec<SavedView>) {
+    let file = sidecar_name(model_file, annotations::SIDECAR_SUFFIX);
+    if !res.path(&file).exists() {
+        return Default::default();
+    }
+    match res
+        .load_string(&file)
+        .and_then(|text| annotations::from_json(&text, model.get_center()))
+    {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Couldn't load annotations from {}:", file);
+            eprintln!("{}", e);
+            Default::default()
+        }
+    }
+}
+
+/// Writes the annotations and saved views next to a model file.
+fn save_sidecar(res: &Resources, model_file: &str, model: &Model, ui_actions: &UiActions) {
+    let file = sidecar_name(model_file, annotations::SIDECAR_SUFFIX);
+    if let Err(e) = annotations::to_json(
+        &ui_actions.annotations,
+        &ui_actions.saved_views,
+        model.get_center(),
+    )
+    .and_then(|json| res.save_string(&file, &json))
+    {
+        eprintln!("Couldn't save annotations to {}:", file);
+        eprintln!("{}", e);
+    }
+}
+
+/// Draws the tessellated UI over the bound framebuffer.
+fn render_ui(
+    renderer: &ui::UIRenderer,
+    primitives: &[egui::ClippedPrimitive],
+    window_size: (u32, u32),
+) {
+    for egui::ClippedPrimitive {
+        clip_rect,
+        primitive,
+    } in primitives
+    {
+        let mesh = match primitive {
+            egui::epaint::Primitive::Mesh(mesh) => mesh,
+            _ => panic!("Custom paint callbacks and other primitives are not supported."),
+        };
+        debug_assert!(mesh.is_valid());
+
+        renderer.render(&mesh.vertices, &mesh.indices, *clip_rect, window_size);
+    }
+}
+
+/// Returns the seconds since the Unix epoch, for naming saved images.
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,226 @@
+//! Contains [Measurement]s between points on the model surface, geodesic distances along it, and
+//! their export to CSV.
+
+use crate::curvature::weld;
+use na::{Point3, Vector3};
+use nalgebra as na;
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+/// A distance measured between two surface points.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub name: String,
+    pub start: Point3<f32>,
+    pub end: Point3<f32>,
+    /// Shortest distance along the surface, if it was asked for and the points are connected.
+    pub geodesic: Option<f32>,
+}
+
+impl Measurement {
+    /// Straight-line distance between the points.
+    pub fn distance(&self) -> f32 {
+        (self.end - self.start).norm()
+    }
+}
+
+/// Writes the measurements as CSV, with one measurement per row. `offset` is added to the points,
+/// to write them in the coordinates of the model file.
+pub fn to_csv(measurements: &[Measurement], offset: &Vector3<f32>) -> String {
+    let mut csv =
+        String::from("name,start_x,start_y,start_z,end_x,end_y,end_z,distance,geodesic_distance\n");
+    for m in measurements {
+        let (start, end) = (m.start + offset, m.end + offset);
+        csv.push_str(&format!(
+            "\"{}\",{},{},{},{},{},{},{},{}\n",
+            m.name.replace('"', "\"\""),
+            start.x,
+            start.y,
+            start.z,
+            end.x,
+            end.y,
+            end.z,
+            m.distance(),
+            m.geodesic.map(|g| g.to_string()).unwrap_or_default(),
+        ));
+    }
+    csv
+}
+
+/// A point on a triangle of the mesh.
+pub struct MeshPoint {
+    pub triangle: usize,
+    pub position: Point3<f32>,
+}
+
+/// Vertex and the distance to it, ordered so [BinaryHeap] pops the nearest first.
+#[derive(PartialEq)]
+struct Candidate(f32, usize);
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0)
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The mesh edges as a graph, with vertices at the same position merged so seams in the mesh
+/// don't block the way. It takes a pass over every triangle to build, so it is built once per
+/// model and reused for every measurement.
+pub struct SurfaceGraph {
+    vertices: Vec<Vector3<f32>>,
+    /// Merged vertex of each vertex of the mesh.
+    remap: Vec<usize>,
+    neighbours: Vec<Vec<usize>>,
+}
+
+impl SurfaceGraph {
+    pub fn new(positions: &[Vector3<f32>], indices: &[u32]) -> Self {
+        let (vertices, remap) = weld(positions);
+        let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); vertices.len()];
+        for triangle in indices.chunks_exact(3) {
+            for k in 0..3 {
+                let (a, b) = (
+                    remap[triangle[k] as usize],
+                    remap[triangle[(k + 1) % 3] as usize],
+                );
+                if a != b && !neighbours[a].contains(&b) {
+                    neighbours[a].push(b);
+                    neighbours[b].push(a);
+                }
+            }
+        }
+        Self {
+            vertices,
+            remap,
+            neighbours,
+        }
+    }
+
+    /// Finds the shortest distance along the surface between two points, using Dijkstra's
+    /// algorithm over the mesh edges. `indices` are the triangles the graph was built from. Paths
+    /// have to follow edges between the points' triangles, so the result is somewhat longer than
+    /// the true geodesic, less so on finer meshes. Returns `None` if the points are on
+    /// disconnected parts of the mesh.
+    pub fn geodesic_distance(
+        &self,
+        indices: &[u32],
+        start: &MeshPoint,
+        end: &MeshPoint,
+    ) -> Option<f32> {
+        if start.triangle == end.triangle {
+            return Some((end.position - start.position).norm());
+        }
+        let vertices = &self.vertices;
+        let corners = |point: &MeshPoint| {
+            let triangle = &indices[point.triangle * 3..point.triangle * 3 + 3];
+            triangle
+                .iter()
+                .map(|&i| self.remap[i as usize])
+                .collect::<Vec<_>>()
+        };
+
+        let mut distances = vec![f32::INFINITY; vertices.len()];
+        let mut queue = BinaryHeap::new();
+        for i in corners(start) {
+            let distance = (vertices[i] - start.position.coords).norm();
+            if distance < distances[i] {
+                distances[i] = distance;
+                queue.push(Candidate(distance, i));
+            }
+        }
+        let targets = corners(end);
+        let mut best = f32::INFINITY;
+        while let Some(Candidate(distance, i)) = queue.pop() {
+            if distance > distances[i] {
+                continue;
+            }
+            if distance >= best {
+                break;
+            }
+            if targets.contains(&i) {
+                best = best.min(distance + (end.position.coords - vertices[i]).norm());
+            }
+            for &j in &self.neighbours[i] {
+                let next = distance + (vertices[j] - vertices[i]).norm();
+                if next < distances[j] {
+                    distances[j] = next;
+                    queue.push(Candidate(next, j));
+                }
+            }
+        }
+        best.is_finite().then_some(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geodesic_goes_around_cylinder() {
+        let (rings, segments) = (8u32, 64u32);
+        let mut positions = Vec::new();
+        for ring in 0..=rings {
+            // The last segment repeats the first, leaving a seam to be welded.
+            for segment in 0..=segments {
+                let phi = (segment % segments) as f32 / segments as f32 * std::f32::consts::TAU;
+                positions.push(Vector3::new(phi.cos(), phi.sin(), ring as f32 * 0.1));
+            }
+        }
+        let mut indices = Vec::new();
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let i = ring * (segments + 1) + segment;
+                let above = i + segments + 1;
+                indices.extend([i, i + 1, above + 1, i, above + 1, above]);
+            }
+        }
+        // Points on opposite sides of the cylinder, half its circumference apart.
+        let point = |triangle: usize| {
+            let t = &indices[triangle * 3..triangle * 3 + 3];
+            let sum: Vector3<f32> = t.iter().map(|&i| positions[i as usize]).sum();
+            MeshPoint {
+                triangle,
+                position: Point3::from(sum / 3.0),
+            }
+        };
+        let segment_triangles = 2 * 2 * segments as usize;
+        let (start, end) = (point(segment_triangles + 1), point(segment_triangles + 65));
+        let straight = (end.position - start.position).norm();
+        let graph = SurfaceGraph::new(&positions, &indices);
+        let geodesic = graph.geodesic_distance(&indices, &start, &end).unwrap();
+        assert!(geodesic > straight);
+        assert!(
+            (geodesic - std::f32::consts::PI).abs() < 0.05,
+            "{}",
+            geodesic
+        );
+        // The short way round crosses the seam.
+        let across_seam = point(segment_triangles + 2 * 63 + 1);
+        let geodesic = graph
+            .geodesic_distance(&indices, &start, &across_seam)
+            .unwrap();
+        assert!(geodesic < 0.3, "{}", geodesic);
+    }
+
+    #[test]
+    fn csv_export() {
+        let measurements = [Measurement {
+            name: "Neck \"A\"".to_owned(),
+            start: Point3::new(0.0, 0.0, 0.0),
+            end: Point3::new(3.0, 4.0, 0.0),
+            geodesic: None,
+        }];
+        let csv = to_csv(&measurements, &Vector3::new(1.0, 0.0, 0.0));
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1], "\"Neck \"\"A\"\"\",1,0,0,4,4,0,5,");
+    }
+}
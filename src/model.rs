@@ -3,18 +3,28 @@
 //! future.
 
 use crate::{
-    geometry::intersect_box_and_line,
+    bvh::Bvh,
+    camera::{Camera, Projection},
+    clipping::{self, ClipPlane},
+    colormap::{Colormap, HueDirection},
+    curvature,
+    geometry::{barycentric, fit_orthographic_to_box, intersect_box_and_line},
+    lights::{Light, LightBuffer},
+    measurement::{MeshPoint, SurfaceGraph},
     render_gl::{
         self,
         buffer::{self, FrameBuffer, Texture},
-        data::{self, f32_f32_f32},
+        data::{self, f32_f32, f32_f32_f32, f32_f32_f32_f32},
         Viewport,
     },
     resources::Resources,
+    transparency::TransparencyBuffer,
 };
 use anyhow::{Context, Result};
 use nalgebra as na;
 use render_gl_derive::VertexAttribPointers;
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
 
 const MAIN_SHADER_PATH: &str = "shaders/model";
 const MAIN_SHADER_NAME: &str = "model";
@@ -22,11 +32,11 @@ const SHADOW_SHADER_PATH: &str = "shaders/shadow";
 const SHADOW_SHADER_NAME: &str = "shadow";
 const HATCHING_SHADER_PATH: &str = "shaders/hatching";
 const HATCHING_SHADER_NAME: &str = "hatching";
-const HATCHING_FAR_PLANE: f32 = 1000.0;
-const SHADOW_WIDTH: gl::types::GLsizei = 2048;
-const SHADOW_HEIGHT: gl::types::GLsizei = 2048;
+const CLIP_CAP_SHADER_PATH: &str = "shaders/clip_cap";
+const CLIP_CAP_SHADER_NAME: &str = "clip_cap";
 const TEXTURE_UNIT_SHADOW: gl::types::GLenum = gl::TEXTURE0;
 const TEXTURE_UNIT_HATCH: gl::types::GLenum = gl::TEXTURE1;
+const TEXTURE_UNIT_COLORMAP: gl::types::GLenum = gl::TEXTURE2;
 
 #[derive(Copy, Clone, Debug, VertexAttribPointers)]
 #[repr(C, packed)]
@@ -37,24 +47,44 @@ pub struct Vertex {
     pub normal: data::f32_f32_f32,
     #[location = 2]
     pub color: data::f32_f32_f32,
+    #[location = 3]
+    pub curvature_direction: data::f32_f32_f32,
+    /// The largest and smallest principal curvature.
+    #[location = 4]
+    pub principal_curvatures: data::f32_f32,
+    /// Mean curvature, Gaussian curvature, shape index and the derivative of the dominant
+    /// principal curvature along [Vertex::curvature_direction].
+    #[location = 5]
+    pub curvature_measures: data::f32_f32_f32_f32,
+}
+
+impl Vertex {
+    /// Returns the curvature measure the surface can be coloured by, unscaled.
+    fn curvature(&self, coloring: CurvatureColoring) -> Option<f32> {
+        let (principal, measures) = (self.principal_curvatures, self.curvature_measures);
+        match coloring {
+            CurvatureColoring::None => None,
+            CurvatureColoring::Mean => Some(measures.d0),
+            CurvatureColoring::Gaussian => Some(measures.d1),
+            CurvatureColoring::ShapeIndex => Some(measures.d2),
+            CurvatureColoring::MaxPrincipal => Some(principal.d0),
+            CurvatureColoring::MinPrincipal => Some(principal.d1),
+        }
+    }
 }
 
 /// Represents which color channel the distance shading shader should use.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 #[repr(C)]
 pub enum DistanceShadingChannel {
+    #[default]
     None = 0,
     Hue = 1,
     Saturation = 2,
     Value = 3,
 }
 
-impl Default for DistanceShadingChannel {
-    fn default() -> Self {
-        DistanceShadingChannel::None
-    }
-}
-
 impl std::fmt::Display for DistanceShadingChannel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         match self {
@@ -66,27 +96,224 @@ impl std::fmt::Display for DistanceShadingChannel {
     }
 }
 
-/// Represents shader attributes in use.
-#[derive(Debug, Clone)]
+/// Represents how shadow map samples are filtered.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum ShadowFiltering {
+    /// Fixed size Poisson disk percentage-closer filtering.
+    #[default]
+    Pcf = 0,
+    /// Percentage-closer soft shadows, widening the penumbra with distance to the occluder.
+    Pcss = 1,
+}
+
+impl std::fmt::Display for ShadowFiltering {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ShadowFiltering::Pcf => write!(f, "Poisson PCF"),
+            ShadowFiltering::Pcss => write!(f, "Soft (PCSS)"),
+        }
+    }
+}
+
+/// Represents how hatching strokes are laid out.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum HatchingMode {
+    /// Diagonal strokes fixed to the screen.
+    #[default]
+    ScreenSpace = 0,
+    /// Strokes fixed to the surface along parallel planes through the model.
+    ObjectSpace = 1,
+    /// Strokes following the direction of strongest surface curvature.
+    Curvature = 2,
+}
+
+impl HatchingMode {
+    pub const ALL: [HatchingMode; 3] = [
+        HatchingMode::ScreenSpace,
+        HatchingMode::ObjectSpace,
+        HatchingMode::Curvature,
+    ];
+}
+
+impl std::fmt::Display for HatchingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            HatchingMode::ScreenSpace => write!(f, "Screen space"),
+            HatchingMode::ObjectSpace => write!(f, "Object space"),
+            HatchingMode::Curvature => write!(f, "Curvature aligned"),
+        }
+    }
+}
+
+/// Represents the reflectance model used for standard shading.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum LightingModel {
+    /// Diffuse reflection only.
+    Lambert = 0,
+    /// Lambertian diffuse reflection with Blinn-Phong highlights.
+    BlinnPhong = 1,
+    /// Oren-Nayar diffuse reflection for rough surfaces, with Blinn-Phong highlights.
+    #[default]
+    OrenNayar = 2,
+}
+
+impl LightingModel {
+    pub const ALL: [LightingModel; 3] = [
+        LightingModel::Lambert,
+        LightingModel::BlinnPhong,
+        LightingModel::OrenNayar,
+    ];
+}
+
+impl std::fmt::Display for LightingModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            LightingModel::Lambert => write!(f, "Lambert"),
+            LightingModel::BlinnPhong => write!(f, "Blinn-Phong"),
+            LightingModel::OrenNayar => write!(f, "Oren-Nayar"),
+        }
+    }
+}
+
+/// Represents which curvature measure, if any, the surface is coloured by.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum CurvatureColoring {
+    #[default]
+    None = 0,
+    Mean = 1,
+    Gaussian = 2,
+    ShapeIndex = 3,
+    MaxPrincipal = 4,
+    MinPrincipal = 5,
+}
+
+impl CurvatureColoring {
+    pub const ALL: [CurvatureColoring; 6] = [
+        CurvatureColoring::None,
+        CurvatureColoring::Mean,
+        CurvatureColoring::Gaussian,
+        CurvatureColoring::ShapeIndex,
+        CurvatureColoring::MaxPrincipal,
+        CurvatureColoring::MinPrincipal,
+    ];
+}
+
+impl std::fmt::Display for CurvatureColoring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            CurvatureColoring::None => write!(f, "None"),
+            CurvatureColoring::Mean => write!(f, "Mean curvature"),
+            CurvatureColoring::Gaussian => write!(f, "Gaussian curvature"),
+            CurvatureColoring::ShapeIndex => write!(f, "Shape index"),
+            CurvatureColoring::MaxPrincipal => write!(f, "Largest principal curvature"),
+            CurvatureColoring::MinPrincipal => write!(f, "Smallest principal curvature"),
+        }
+    }
+}
+
+/// Represents shader attributes in use. The attributes following from the camera, the model and
+/// the frame being drawn are left out when the attributes are saved as settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Attributes {
+    #[serde(skip)]
     pub projection_matrix: na::Matrix4<f32>,
+    #[serde(skip)]
     pub camera_position: na::Vector3<f32>,
-    pub light_position: na::Vector3<f32>,
+    /// Transform from world to camera space, used to place camera-attached lights.
+    #[serde(skip)]
+    pub view_matrix: na::Matrix4<f32>,
+    /// Whether [Attributes::projection_matrix] is a parallel projection, where the view
+    /// direction is the same for every point.
+    #[serde(skip)]
+    pub orthographic: bool,
+    /// Distance to the camera's near plane, which lies behind the camera when zoomed in on an
+    /// orthographic view. Depths in the hatching map are stored relative to the near and far
+    /// planes.
+    #[serde(skip)]
+    pub near_plane: f32,
+    /// Distance to the camera's far plane.
+    #[serde(skip)]
+    pub far_plane: f32,
+    /// Whether [Attributes::projection_matrix] uses reversed-Z, so depth buffers are cleared to
+    /// and compared against the opposite values.
+    #[serde(skip)]
+    pub reversed_z: bool,
+    /// Whether the camera is inside the model, where the inner walls are seen from behind, so
+    /// front faces are culled instead of back faces.
+    #[serde(skip)]
+    pub inside_model: bool,
     pub color: na::Vector3<f32>,
+    #[serde(skip)]
     pub model_size: f32,
     pub distance_shading_power: f32,
     pub toon_factor: f32,
+    pub lighting_model: LightingModel,
+    pub ambient_reflection: f32,
+    pub diffuse_reflection: f32,
+    pub specular_reflection: f32,
+    pub shininess: f32,
+    /// Surface roughness for [LightingModel::OrenNayar], in the range [0…1].
+    pub roughness: f32,
+    pub lights: Vec<Light>,
+    /// Index into [Attributes::lights] of the light casting the shadow map.
+    pub shadow_light: usize,
     pub distance_shading_channel: DistanceShadingChannel,
+    pub distance_shading_colormap: Colormap,
+    pub hue_near: f32,
+    pub hue_far: f32,
+    pub hue_direction: HueDirection,
+    pub hue_saturation: f32,
     pub shadow_intensity: f32,
-    pub shadows_follow: bool,
     pub shadows_orbit_radius: f32,
+    /// Rotate the shadow light around its orbit over time.
+    pub light_orbit_animate: bool,
+    /// Seconds per revolution of an animated light orbit.
+    pub light_orbit_period: f32,
+    /// Current position along the light orbit, in degrees.
+    pub light_orbit_angle: f32,
+    /// Stops [Attributes::elapsed] from advancing, pausing all animation.
+    pub freeze_time: bool,
+    pub shadow_map_size: u32,
+    pub shadow_filtering: ShadowFiltering,
+    pub shadow_light_size: f32,
+    #[serde(skip)]
     pub elapsed: f32,
     pub vertex_color_mix: f32,
     pub hatching_depth: f32,
-    pub hatching_frequency: u32,
+    pub curvature_coloring: CurvatureColoring,
+    /// Curvature mapped to the ends of the colour scale, relative to the model size.
+    pub curvature_range: f32,
+    pub ridge_valley_lines: bool,
+    /// Smallest dominant curvature, relative to the model size, drawn as a ridge or valley.
+    pub ridge_threshold: f32,
+    /// Width of ridge and valley lines in logical pixels.
+    pub ridge_line_width: f32,
+    pub hatching_spacing: f32,
+    pub hatching_mode: HatchingMode,
+    /// Hatching spacing in object- and curvature-aligned modes, as a fraction of the model size.
+    pub hatching_object_spacing: f32,
+    pub cross_hatching: bool,
+    /// Tone, from 0 for bright to 1 for black, above which cross-hatching strokes are added.
+    pub cross_hatching_threshold: f32,
+    pub hatch_map_scale: f32,
+    #[serde(skip)]
+    pub pixels_per_point: f32,
     pub hatching_steps: u32,
     pub hatching_intensity: f32,
     pub replace_shadows_with_hatching: bool,
+    pub mesh_opacity: Vec<f32>,
+    pub clip_planes: Vec<ClipPlane>,
+    /// Colour filling the cross-sections cut by [Attributes::clip_planes].
+    pub clip_cap_color: [f32; 3],
 }
 
 impl Default for Attributes {
@@ -94,42 +321,121 @@ impl Default for Attributes {
         Self {
             projection_matrix: Default::default(),
             camera_position: Default::default(),
-            light_position: na::Vector3::new(0.45, 0.25, 0.6),
+            view_matrix: na::Matrix4::identity(),
+            orthographic: false,
+            near_plane: 0.0,
+            far_plane: 1000.0,
+            reversed_z: false,
+            inside_model: false,
             color: na::Vector3::new(1.0, 0.56, 0.72),
             model_size: Default::default(),
             distance_shading_power: 0.4,
             toon_factor: 0.7,
+            lighting_model: LightingModel::OrenNayar,
+            ambient_reflection: 0.2,
+            diffuse_reflection: 0.8,
+            specular_reflection: 0.1,
+            shininess: 10.0,
+            roughness: 0.3,
+            // The headlight shades the model, while the fixed light only casts shadows.
+            lights: vec![
+                Light::headlight(),
+                Light::directional(na::Vector3::new(0.45, 0.25, 0.6), 0.0),
+            ],
+            shadow_light: 1,
             distance_shading_channel: DistanceShadingChannel::None,
+            distance_shading_colormap: Colormap::Hsv,
+            hue_near: 360.0,
+            hue_far: 220.0,
+            hue_direction: HueDirection::Decreasing,
+            hue_saturation: 0.6,
             shadow_intensity: 0.6,
-            shadows_follow: false,
             shadows_orbit_radius: 25.0,
+            light_orbit_animate: false,
+            light_orbit_period: 2.0,
+            light_orbit_angle: 0.0,
+            freeze_time: false,
+            shadow_map_size: 2048,
+            shadow_filtering: ShadowFiltering::Pcf,
+            shadow_light_size: 0.02,
             elapsed: 0.0,
             vertex_color_mix: 1.0,
             hatching_depth: 1.0,
             hatching_steps: 150,
-            hatching_frequency: 4,
+            curvature_coloring: CurvatureColoring::None,
+            curvature_range: 50.0,
+            ridge_valley_lines: false,
+            ridge_threshold: 50.0,
+            ridge_line_width: 1.5,
+            hatching_spacing: 4.0,
+            hatching_mode: HatchingMode::ScreenSpace,
+            hatching_object_spacing: 0.01,
+            cross_hatching: false,
+            cross_hatching_threshold: 0.5,
+            hatch_map_scale: 1.0,
+            pixels_per_point: 1.0,
             hatching_intensity: 0.5,
             replace_shadows_with_hatching: true,
+            mesh_opacity: Vec::new(),
+            clip_planes: Vec::new(),
+            clip_cap_color: [0.95, 0.85, 0.35],
         }
     }
 }
 
+/// A named object in the loaded obj file, drawn from a range of the shared index buffer.
+pub struct SubMesh {
+    pub name: String,
+    offset: usize,
+    count: i32,
+}
+
+/// A point on the surface of the model, as found by [Model::pick].
+#[derive(Copy, Clone, Debug)]
+pub struct SurfacePoint {
+    pub position: na::Point3<f32>,
+    /// Index of the triangle the point lies on, counting from the start of the model.
+    pub triangle: usize,
+    /// Index of the object the triangle belongs to.
+    pub mesh: usize,
+    /// Unit normal, interpolated from the vertex normals when the model has them.
+    pub normal: na::Vector3<f32>,
+    /// Value of the curvature measure the surface is coloured by, if it is.
+    pub curvature: Option<f32>,
+}
+
 /// [Model] is where the main logic for the mesh rendering happens. Ideally, shadow- and hatching
 /// texture rendering should be refactored to it's own module in the future.
 pub struct Model {
     program: render_gl::Program,
     shadow_program: render_gl::Program,
     hatching_program: render_gl::Program,
+    clip_cap_program: render_gl::Program,
     vao: buffer::VertexArray,
     _vbo: buffer::ArrayBuffer,
     ibo: buffer::ElementArrayBuffer,
-    indices: i32,
+    meshes: Vec<SubMesh>,
+    /// Vertices and triangle indices kept on the CPU, to describe picked points.
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    /// Hierarchy over the triangles, for picking.
+    bvh: Bvh,
+    /// Mesh edges for geodesic distances, built on the first measurement.
+    surface_graph: OnceCell<SurfaceGraph>,
     size: na::Vector3<f32>,
+    /// Center of the model in the obj file's coordinates, which is moved to the origin.
+    center: na::Vector3<f32>,
     attributes: Attributes,
     depth_map: Texture,
     depth_map_fbo: FrameBuffer,
     hatch_map: Texture,
     hatch_map_fbo: FrameBuffer,
+    hatch_map_size: (i32, i32),
+    colormap: Texture,
+    transparency: TransparencyBuffer,
+    light_buffer: LightBuffer,
+    /// Value of [Attributes::elapsed] when animations were last advanced.
+    last_elapsed: f32,
 }
 
 impl Model {
@@ -138,11 +444,14 @@ impl Model {
         // set up shader program
         let program = render_gl::Program::from_res(res, MAIN_SHADER_PATH)?;
 
-        let model = res.load_model(filename).context("Failed to load model.")?;
+        let models = res.load_model(filename).context("Failed to load model.")?;
 
         let mut min = na::Vector3::from_element(f32::MAX);
         let mut max = na::Vector3::from_element(f32::MIN);
-        for pos in model.positions.chunks_exact(3) {
+        for pos in models
+            .iter()
+            .flat_map(|model| model.mesh.positions.chunks_exact(3))
+        {
             min[0] = min[0].min(pos[0]);
             max[0] = max[0].max(pos[0]);
             min[1] = min[1].min(pos[1]);
@@ -153,20 +462,61 @@ impl Model {
         let center = min + (max - min) / 2.0;
         let model_size = (max - min).magnitude();
 
-        let vertices: Vec<Vertex> = model
-            .positions
-            .chunks_exact(3)
-            .zip(model.normals.chunks_exact(3))
-            .zip(model.vertex_color.chunks_exact(3))
-            .map(|((p, n), c)| {
-                (
-                    f32_f32_f32::from((p[0] - center[0], p[1] - center[1], p[2] - center[2])),
-                    f32_f32_f32::from((n[0], n[1], n[2])),
-                    f32_f32_f32::from((c[0], c[1], c[2])),
-                )
-            })
-            .map(|(pos, normal, color)| Vertex { pos, normal, color })
+        // Merge all objects into one vertex buffer, remembering which range of indices belongs to
+        // which object. Missing normals or colours are filled in so the offsets stay consistent.
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut meshes = Vec::with_capacity(models.len());
+        for model in &models {
+            let mesh = &model.mesh;
+            let base_vertex = vertices.len() as u32;
+            let normals = mesh
+                .normals
+                .chunks_exact(3)
+                .chain(std::iter::repeat(&[0.0, 0.0, 0.0][..]));
+            let colors = mesh
+                .vertex_color
+                .chunks_exact(3)
+                .chain(std::iter::repeat(&[1.0, 1.0, 1.0][..]));
+            vertices.extend(
+                mesh.positions
+                    .chunks_exact(3)
+                    .zip(normals)
+                    .zip(colors)
+                    .map(|((p, n), c)| {
+                        (
+                            f32_f32_f32::from((
+                                p[0] - center[0],
+                                p[1] - center[1],
+                                p[2] - center[2],
+                            )),
+                            f32_f32_f32::from((n[0], n[1], n[2])),
+                            f32_f32_f32::from((c[0], c[1], c[2])),
+                        )
+                    })
+                    .map(|(pos, normal, color)| Vertex {
+                        pos,
+                        normal,
+                        color,
+                        curvature_direction: f32_f32_f32::from((0.0, 0.0, 0.0)),
+                        principal_curvatures: f32_f32::from((0.0, 0.0)),
+                        curvature_measures: f32_f32_f32_f32::from((0.0, 0.0, 0.0, 0.0)),
+                    }),
+            );
+            meshes.push(SubMesh {
+                name: model.name.clone(),
+                offset: indices.len(),
+                count: mesh.indices.len() as i32,
+            });
+            indices.extend(mesh.indices.iter().map(|i| i + base_vertex));
+        }
+        let typical_curvature = add_curvature(&mut vertices, &indices);
+        let positions: Vec<na::Point3<f32>> = vertices
+            .iter()
+            .map(|v| na::Point3::new(v.pos.d0, v.pos.d1, v.pos.d2))
             .collect();
+        let bvh = Bvh::new(&positions, &indices);
+
         let vbo = buffer::ArrayBuffer::new();
         vbo.bind();
         vbo.static_draw_data(&vertices);
@@ -179,18 +529,29 @@ impl Model {
         // indices buffer
         let ibo = buffer::ElementArrayBuffer::new();
         ibo.bind();
-        ibo.static_draw_data(&model.indices);
+        ibo.static_draw_data(&indices);
         ibo.unbind();
         vbo.unbind();
         vao.unbind();
 
+        // Default to a range where most of the surface stays within the colour scale.
+        let relative_curvature = (typical_curvature * model_size).max(1.0);
+        let attributes = Attributes {
+            model_size,
+            curvature_range: relative_curvature,
+            ridge_threshold: relative_curvature / 2.0,
+            mesh_opacity: vec![1.0; meshes.len()],
+            ..Default::default()
+        };
+
         // Shadowstuff
         let shadow_program = render_gl::Program::from_res(res, SHADOW_SHADER_PATH)?;
         shadow_program.set_used();
 
         let depth_map = Texture::new(TEXTURE_UNIT_SHADOW);
+        let shadow_map_size = attributes.shadow_map_size as i32;
         depth_map.load_texture(
-            (SHADOW_WIDTH, SHADOW_HEIGHT),
+            (shadow_map_size, shadow_map_size),
             None,
             gl::DEPTH_COMPONENT as gl::types::GLint,
             gl::DEPTH_COMPONENT,
@@ -205,21 +566,10 @@ impl Model {
         depth_map_fbo.bind_texture(gl::DEPTH_ATTACHMENT, &depth_map);
         depth_map_fbo.unbind();
 
-        let attributes = Attributes {
-            model_size,
-            ..Default::default()
-        };
-
         let hatching_program = render_gl::Program::from_res(res, HATCHING_SHADER_PATH)?;
+        // The hatching map storage is allocated to match the viewport on the first render.
         let hatch_map = Texture::new(TEXTURE_UNIT_HATCH);
-        hatch_map.load_texture(
-            (SHADOW_WIDTH, SHADOW_HEIGHT),
-            None,
-            gl::DEPTH_COMPONENT as gl::types::GLint,
-            gl::DEPTH_COMPONENT,
-            gl::FLOAT,
-            false,
-        );
+        hatch_map.bind();
         hatch_map.set_border_color(&[1.0, 1.0, 1.0, 1.0]);
 
         let hatch_map_fbo = FrameBuffer::new();
@@ -228,25 +578,61 @@ impl Model {
         hatch_map_fbo.bind_texture(gl::DEPTH_ATTACHMENT, &hatch_map);
         hatch_map_fbo.unbind();
 
+        let colormap = Texture::with_target(TEXTURE_UNIT_COLORMAP, gl::TEXTURE_1D);
+        colormap.load_texture_1d(
+            &attributes.distance_shading_colormap.texture_data(),
+            gl::RGBA8 as gl::types::GLint,
+        );
+        colormap.unbind();
+
+        let transparency = TransparencyBuffer::new(res)?;
+        let clip_cap_program = render_gl::Program::from_res(res, CLIP_CAP_SHADER_PATH)?;
+
         let value = Self {
             program,
             shadow_program,
             hatching_program,
+            clip_cap_program,
             _vbo: vbo,
             vao,
             ibo,
-            indices: model.indices.len() as i32,
+            meshes,
+            vertices,
+            indices,
+            bvh,
+            surface_graph: OnceCell::new(),
             size: max - min,
+            center,
             attributes,
             depth_map,
             depth_map_fbo,
             hatch_map,
             hatch_map_fbo,
+            hatch_map_size: (0, 0),
+            colormap,
+            transparency,
+            light_buffer: LightBuffer::new(),
+            last_elapsed: 0.0,
         };
         value.reset_all_attributes();
         Ok(value)
     }
 
+    /// Sets the attributes that depend on the camera, for an image with the given aspect ratio.
+    pub fn set_camera(&mut self, camera: &Camera, isometry: na::Isometry3<f32>, aspect: f32) {
+        let mut attr = self.attributes.clone();
+        attr.camera_position = camera.position().coords;
+        attr.projection_matrix = camera.construct_mvp(aspect, isometry);
+        attr.view_matrix = camera.view().to_homogeneous();
+        attr.orthographic = camera.projection() == Projection::Orthographic;
+        (attr.near_plane, attr.far_plane) = camera.clip_range();
+        attr.reversed_z = camera.reversed_z();
+        // Zooming in, picking a pivot or flying can all take the camera inside a vessel, so this is
+        // checked whenever the camera moves. The BVH keeps it cheap on large meshes.
+        attr.inside_model = self.contains(&camera.position());
+        self.set_attributes(attr);
+    }
+
     /// Get the shader attributes.
     pub fn get_attributes(&self) -> &Attributes {
         &self.attributes
@@ -277,6 +663,18 @@ impl Model {
                     .set_uniform_matrix4("projection_matrix", &new.projection_matrix);
                 self.program.set_used();
             }
+            if new.view_matrix != old.view_matrix {
+                self.program
+                    .set_uniform_3f_na("view_direction", view_direction(&new.view_matrix));
+            }
+            if new.orthographic != old.orthographic {
+                self.program
+                    .set_uniform_ui("orthographic", new.orthographic as u32);
+            }
+            if new.reversed_z != old.reversed_z {
+                self.program
+                    .set_uniform_ui("reversed_z", new.reversed_z as u32);
+            }
             if new.camera_position != old.camera_position {
                 self.program
                     .set_uniform_3f_na("camera_position", new.camera_position);
@@ -300,6 +698,31 @@ impl Model {
                     new.distance_shading_channel as u32,
                 )
             }
+            if new.distance_shading_colormap != old.distance_shading_colormap {
+                self.program.set_uniform_ui(
+                    "distance_shading_colormap",
+                    new.distance_shading_colormap as u32,
+                );
+                self.colormap.load_texture_1d(
+                    &new.distance_shading_colormap.texture_data(),
+                    gl::RGBA8 as gl::types::GLint,
+                );
+                self.colormap.unbind();
+            }
+            if (new.hue_near - old.hue_near).abs() > f32::EPSILON {
+                self.program.set_uniform_f("hue_near", new.hue_near / 360.0);
+            }
+            if (new.hue_far - old.hue_far).abs() > f32::EPSILON {
+                self.program.set_uniform_f("hue_far", new.hue_far / 360.0);
+            }
+            if new.hue_direction != old.hue_direction {
+                self.program
+                    .set_uniform_ui("hue_direction", new.hue_direction as u32);
+            }
+            if (new.hue_saturation - old.hue_saturation).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("hue_saturation", new.hue_saturation);
+            }
             if (new.shadow_intensity - old.shadow_intensity).abs() < f32::EPSILON {
                 self.program
                     .set_uniform_f("shadow_intensity", new.shadow_intensity)
@@ -312,9 +735,87 @@ impl Model {
                 self.program
                     .set_uniform_f("hatching_intensity", new.hatching_intensity)
             }
-            if new.hatching_frequency != old.hatching_frequency {
+            if (new.hatching_spacing - old.hatching_spacing).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("hatching_spacing", new.hatching_spacing)
+            }
+            if new.lighting_model != old.lighting_model {
+                self.program
+                    .set_uniform_ui("lighting_model", new.lighting_model as u32);
+            }
+            if (new.ambient_reflection - old.ambient_reflection).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("ambient_reflection", new.ambient_reflection);
+            }
+            if (new.diffuse_reflection - old.diffuse_reflection).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("diffuse_reflection", new.diffuse_reflection);
+            }
+            if (new.specular_reflection - old.specular_reflection).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("specular_reflection", new.specular_reflection);
+            }
+            if (new.shininess - old.shininess).abs() > f32::EPSILON {
+                self.program.set_uniform_f("shininess", new.shininess);
+            }
+            if (new.roughness - old.roughness).abs() > f32::EPSILON {
+                self.program.set_uniform_f("roughness", new.roughness);
+            }
+            if new.curvature_coloring != old.curvature_coloring {
+                self.program
+                    .set_uniform_ui("curvature_coloring", new.curvature_coloring as u32);
+            }
+            if (new.curvature_range - old.curvature_range).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("curvature_range", new.curvature_range);
+            }
+            if new.ridge_valley_lines != old.ridge_valley_lines {
+                self.program
+                    .set_uniform_ui("ridge_valley_lines", new.ridge_valley_lines as u32);
+            }
+            if (new.ridge_threshold - old.ridge_threshold).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("ridge_threshold", new.ridge_threshold);
+            }
+            if (new.ridge_line_width - old.ridge_line_width).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("ridge_line_width", new.ridge_line_width);
+            }
+            if new.hatching_mode != old.hatching_mode {
+                self.program
+                    .set_uniform_ui("hatching_mode", new.hatching_mode as u32);
+            }
+            if (new.hatching_object_spacing - old.hatching_object_spacing).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("hatching_object_spacing", new.hatching_object_spacing);
+            }
+            if new.cross_hatching != old.cross_hatching {
+                self.program
+                    .set_uniform_ui("cross_hatching", new.cross_hatching as u32);
+            }
+            if (new.cross_hatching_threshold - old.cross_hatching_threshold).abs() > f32::EPSILON {
                 self.program
-                    .set_uniform_ui("hatching_frequency", new.hatching_frequency)
+                    .set_uniform_f("cross_hatching_threshold", new.cross_hatching_threshold);
+            }
+            if (new.pixels_per_point - old.pixels_per_point).abs() > f32::EPSILON {
+                self.program
+                    .set_uniform_f("pixels_per_point", new.pixels_per_point)
+            }
+            if new.shadow_map_size != old.shadow_map_size {
+                let size = new.shadow_map_size as i32;
+                self.depth_map.load_texture(
+                    (size, size),
+                    None,
+                    gl::DEPTH_COMPONENT as gl::types::GLint,
+                    gl::DEPTH_COMPONENT,
+                    gl::FLOAT,
+                    false,
+                );
+                self.depth_map.unbind();
+            }
+            if new.shadow_filtering != old.shadow_filtering {
+                self.program
+                    .set_uniform_ui("shadow_filtering", new.shadow_filtering as u32);
             }
             if new.replace_shadows_with_hatching != old.replace_shadows_with_hatching {
                 self.program.set_uniform_ui(
@@ -338,6 +839,12 @@ impl Model {
 
             self.program
                 .set_uniform_3f_na("camera_position", att.camera_position);
+            self.program
+                .set_uniform_3f_na("view_direction", view_direction(&att.view_matrix));
+            self.program
+                .set_uniform_ui("orthographic", att.orthographic as u32);
+            self.program
+                .set_uniform_ui("reversed_z", att.reversed_z as u32);
             self.program.set_uniform_3f_na("color", att.color);
             self.program.set_uniform_f("model_size", att.model_size);
             self.program
@@ -347,14 +854,56 @@ impl Model {
                 "distance_shading_channel",
                 att.distance_shading_channel as u32,
             );
+            self.program.set_uniform_ui(
+                "distance_shading_colormap",
+                att.distance_shading_colormap as u32,
+            );
+            self.program.set_uniform_f("hue_near", att.hue_near / 360.0);
+            self.program.set_uniform_f("hue_far", att.hue_far / 360.0);
+            self.program
+                .set_uniform_ui("hue_direction", att.hue_direction as u32);
+            self.program
+                .set_uniform_f("hue_saturation", att.hue_saturation);
             self.program
                 .set_uniform_f("shadow_intensity", att.shadow_intensity);
+            self.program
+                .set_uniform_ui("shadow_filtering", att.shadow_filtering as u32);
             self.program
                 .set_uniform_f("vertex_color_mix", att.vertex_color_mix);
             self.program
                 .set_uniform_f("hatching_intensity", att.hatching_intensity);
             self.program
-                .set_uniform_ui("hatching_frequency", att.hatching_frequency);
+                .set_uniform_f("hatching_spacing", att.hatching_spacing);
+            self.program
+                .set_uniform_f("pixels_per_point", att.pixels_per_point);
+            self.program
+                .set_uniform_ui("hatching_mode", att.hatching_mode as u32);
+            self.program
+                .set_uniform_ui("curvature_coloring", att.curvature_coloring as u32);
+            self.program
+                .set_uniform_ui("lighting_model", att.lighting_model as u32);
+            self.program
+                .set_uniform_f("ambient_reflection", att.ambient_reflection);
+            self.program
+                .set_uniform_f("diffuse_reflection", att.diffuse_reflection);
+            self.program
+                .set_uniform_f("specular_reflection", att.specular_reflection);
+            self.program.set_uniform_f("shininess", att.shininess);
+            self.program.set_uniform_f("roughness", att.roughness);
+            self.program
+                .set_uniform_f("curvature_range", att.curvature_range);
+            self.program
+                .set_uniform_ui("ridge_valley_lines", att.ridge_valley_lines as u32);
+            self.program
+                .set_uniform_f("ridge_threshold", att.ridge_threshold);
+            self.program
+                .set_uniform_f("ridge_line_width", att.ridge_line_width);
+            self.program
+                .set_uniform_f("hatching_object_spacing", att.hatching_object_spacing);
+            self.program
+                .set_uniform_ui("cross_hatching", att.cross_hatching as u32);
+            self.program
+                .set_uniform_f("cross_hatching_threshold", att.cross_hatching_threshold);
             self.program.set_uniform_ui(
                 "replace_shadows_with_hatching",
                 att.replace_shadows_with_hatching as u32,
@@ -363,19 +912,155 @@ impl Model {
         self.program.unset_used();
     }
 
+    /// Gets the objects in the loaded model.
+    pub fn get_meshes(&self) -> &[SubMesh] {
+        &self.meshes
+    }
+
+    /// Finds the nearest point where the given ray hits a visible part of the model. Objects that
+    /// are fully transparent and parts cut away by clipping planes are ignored.
+    pub fn pick(
+        &self,
+        origin: &na::Point3<f32>,
+        direction: &na::Vector3<f32>,
+    ) -> Option<SurfacePoint> {
+        let clip_planes: Vec<na::Vector4<f32>> =
+            clipping::active_planes(&self.attributes.clip_planes)
+                .iter()
+                .map(|plane| plane.equation(&self.size).into())
+                .collect();
+        let is_clipped =
+            |p: &na::Point3<f32>| clip_planes.iter().any(|e| e.dot(&p.to_homogeneous()) < 0.0);
+
+        let hit = self.bvh.cast(origin, direction, |triangle, distance| {
+            self.mesh_opacity(self.mesh_of_triangle(triangle)) > 0.0
+                && !is_clipped(&(origin + direction * distance))
+        })?;
+        let position = origin + direction * hit.distance;
+        let corners =
+            [0, 1, 2].map(|k| &self.vertices[self.indices[hit.triangle * 3 + k] as usize]);
+        let points = corners.map(|v| na::Point3::new(v.pos.d0, v.pos.d1, v.pos.d2));
+        let weights = barycentric(&position, points.each_ref());
+        let interpolate = |value: &dyn Fn(&Vertex) -> f32| {
+            (0..3).map(|k| weights[k] * value(corners[k])).sum::<f32>()
+        };
+        let normal = na::Vector3::new(
+            interpolate(&|v| v.normal.d0),
+            interpolate(&|v| v.normal.d1),
+            interpolate(&|v| v.normal.d2),
+        )
+        .try_normalize(f32::EPSILON)
+        .or_else(|| {
+            (points[1] - points[0])
+                .cross(&(points[2] - points[0]))
+                .try_normalize(f32::EPSILON)
+        })
+        .unwrap_or_else(na::Vector3::z);
+        let coloring = self.attributes.curvature_coloring;
+        let curvature = (coloring != CurvatureColoring::None)
+            .then(|| interpolate(&|v| v.curvature(coloring).unwrap_or_default()));
+        Some(SurfacePoint {
+            position,
+            triangle: hit.triangle,
+            mesh: self.mesh_of_triangle(hit.triangle),
+            normal,
+            curvature,
+        })
+    }
+
+    /// Returns whether the point lies inside the visible objects, assuming their meshes are
+    /// closed. Counts how many surfaces a ray from the point crosses, which is odd inside.
+    pub fn contains(&self, point: &na::Point3<f32>) -> bool {
+        if (0..3).any(|i| point[i].abs() > self.size[i] / 2.0) {
+            return false;
+        }
+        // A slanted direction avoids hitting edges shared by two triangles.
+        let direction = na::Vector3::new(1.0, 0.123, 0.0457).normalize();
+        let crossings = self.bvh.count_hits(point, &direction, |triangle| {
+            self.mesh_opacity(self.mesh_of_triangle(triangle)) > 0.0
+        });
+        crossings % 2 == 1
+    }
+
+    /// Returns whether a point on or near the surface can be seen from the camera, or is hidden
+    /// behind other parts of the model.
+    pub fn is_visible(&self, point: &na::Point3<f32>) -> bool {
+        let attr = &self.attributes;
+        let (origin, direction) = if attr.orthographic {
+            let direction = view_direction(&attr.view_matrix);
+            (point - direction * attr.model_size, direction)
+        } else {
+            let origin = na::Point3::from(attr.camera_position);
+            (origin, (point - origin).normalize())
+        };
+        let distance = (point - origin).norm();
+        // Points picked from the surface lie almost exactly on it, and shouldn't hide themselves.
+        let tolerance = attr.model_size * 1e-3;
+        self.pick(&origin, &direction)
+            .is_none_or(|hit| (hit.position - origin).norm() > distance - tolerance)
+    }
+
+    /// Finds the shortest distance along the surface between two picked points, as described in
+    /// [SurfaceGraph::geodesic_distance].
+    pub fn geodesic_distance(&self, from: &SurfacePoint, to: &SurfacePoint) -> Option<f32> {
+        let graph = self.surface_graph.get_or_init(|| {
+            let positions: Vec<na::Vector3<f32>> = self
+                .vertices
+                .iter()
+                .map(|v| na::Vector3::new(v.pos.d0, v.pos.d1, v.pos.d2))
+                .collect();
+            SurfaceGraph::new(&positions, &self.indices)
+        });
+        let mesh_point = |point: &SurfacePoint| MeshPoint {
+            triangle: point.triangle,
+            position: point.position,
+        };
+        graph.geodesic_distance(&self.indices, &mesh_point(from), &mesh_point(to))
+    }
+
+    /// Returns the index of the object a triangle belongs to.
+    fn mesh_of_triangle(&self, triangle: usize) -> usize {
+        self.meshes
+            .partition_point(|mesh| mesh.offset <= triangle * 3)
+            .saturating_sub(1)
+    }
+
+    /// Gets the center of the model in the coordinates of the obj file. Subtracting it moves
+    /// points from the file's coordinates into the model's.
+    pub fn get_center(&self) -> &na::Vector3<f32> {
+        &self.center
+    }
+
     /// Gets the bounding box size of the loaded model.
     pub fn get_size(&self) -> &na::Vector3<f32> {
         &self.size
     }
 
     /// The main rendering function for the program.
-    pub fn render(&self, viewport: &Viewport) {
+    pub fn render(&mut self, viewport: &Viewport) {
+        self.advance_animation();
+
+        // Reallocating binds the targets to texture units, so do it before anything else.
+        self.resize_hatch_map(viewport);
+        let has_transparency = self.meshes_drawn(|opacity| !is_opaque(opacity)) > 0;
+        if has_transparency {
+            self.transparency.resize(viewport.size());
+        }
+
+        // The shadow and hatching passes bind their own framebuffers, so remember which one the
+        // caller wants the model drawn into.
+        let target = FrameBuffer::bound_id();
+
         // Safety: This is a non-stop stream of OpenGL calls. Ultimately, without a safe wrappe
         // around OpenGL (which even `glium` eventually had to give up on), this will likely never
         // be entirely safe.
         unsafe {
-            let (light_vector, light_space_matrix) = self.render_shadowmap();
-            let hatch_space_matrix = self.render_hatchmap(viewport);
+            let clip_planes = clipping::active_planes(&self.attributes.clip_planes);
+            self.set_clip_planes(&clip_planes);
+            let (light_vector, light_space_matrix, penumbra_scale) = self.render_shadowmap();
+            let hatch_space_matrix = self.render_hatchmap();
+            FrameBuffer::bind_id(target);
+            render_gl::set_depth_convention(self.attributes.reversed_z);
 
             // Calculate distance shading planes
             let cam = self.attributes.camera_position;
@@ -389,6 +1074,8 @@ impl Model {
             let closest = intersections[0];
             let furthest = intersections[1];
             self.program.set_used();
+            self.light_buffer
+                .upload(&self.attributes.lights, &self.attributes.view_matrix);
             self.program.set_uniform_3f(
                 "distance_shading_closest",
                 (closest.x, closest.y, closest.z),
@@ -410,60 +1097,264 @@ impl Model {
                 (light_vector[0], light_vector[1], light_vector[2]),
             );
             self.program
-                .set_uniform_f("hatching_far_plane", HATCHING_FAR_PLANE);
+                .set_uniform_f("shadow_penumbra_scale", penumbra_scale);
+            self.program
+                .set_uniform_f("hatching_near_plane", self.attributes.near_plane);
+            self.program
+                .set_uniform_f("hatching_far_plane", self.attributes.far_plane);
             gl::Enable(gl::CULL_FACE);
-            gl::CullFace(gl::BACK);
+            gl::CullFace(if self.attributes.inside_model {
+                gl::FRONT
+            } else {
+                gl::BACK
+            });
             viewport.set_used();
             self.vao.bind();
             self.ibo.bind();
             self.depth_map.bind_to(gl::TEXTURE0);
             self.hatch_map.bind_to(gl::TEXTURE0 + 1);
+            self.colormap.bind();
             if self.attributes.replace_shadows_with_hatching {
                 self.hatch_map
                     .set_texture_compare_mode(gl::COMPARE_REF_TO_TEXTURE);
             } else {
                 self.hatch_map.set_texture_compare_mode(gl::NONE);
             }
-            gl::DrawElements(
-                gl::TRIANGLES,
-                self.indices,
-                gl::UNSIGNED_INT,
-                std::ptr::null::<std::ffi::c_void>(),
-            );
+            self.program.set_uniform_ui("oit_pass", 0);
+            self.program.set_uniform_f("opacity", 1.0);
+            self.draw_meshes(None, is_opaque);
+            if !clip_planes.is_empty() {
+                self.render_clip_caps(&clip_planes);
+            }
+
+            if has_transparency {
+                self.render_transparent(target, viewport);
+            }
+            set_clip_distances_enabled(0..clipping::MAX_CLIP_PLANES, false);
         }
+        self.colormap.unbind();
         self.hatch_map.unbind();
         self.depth_map.unbind();
         self.ibo.unbind();
         self.vao.unbind();
     }
 
-    /// Renders the shadowmap to the shadows framebuffer.
+    /// Moves animated attributes forward by the time passed since the last frame.
+    fn advance_animation(&mut self) {
+        let delta_ms = (self.attributes.elapsed - self.last_elapsed).max(0.0);
+        self.last_elapsed = self.attributes.elapsed;
+        if self.attributes.light_orbit_animate {
+            let period_ms = self.attributes.light_orbit_period.max(0.01) * 1000.0;
+            self.attributes.light_orbit_angle = (self.attributes.light_orbit_angle
+                + delta_ms / period_ms * 360.0)
+                .rem_euclid(360.0);
+        }
+    }
+
+    /// Reallocates the hatching map if it no longer matches the viewport size multiplied by
+    /// [Attributes::hatch_map_scale].
+    fn resize_hatch_map(&mut self, viewport: &Viewport) {
+        let (w, h) = viewport.size();
+        let scale = self.attributes.hatch_map_scale;
+        let size = (
+            ((w as f32 * scale) as i32).max(1),
+            ((h as f32 * scale) as i32).max(1),
+        );
+        if size == self.hatch_map_size {
+            return;
+        }
+        self.hatch_map.load_texture(
+            size,
+            None,
+            gl::DEPTH_COMPONENT as gl::types::GLint,
+            gl::DEPTH_COMPONENT,
+            gl::FLOAT,
+            false,
+        );
+        self.hatch_map.unbind();
+        self.hatch_map_size = size;
+    }
+
+    /// Hands the clipping plane equations to every program drawing the model, and enables a clip
+    /// distance for each plane.
+    ///
+    /// ### Safety
+    ///
+    /// This function should only be called from [Model::render].
+    unsafe fn set_clip_planes(&self, planes: &[&ClipPlane]) {
+        let equations: Vec<[f32; 4]> = planes
+            .iter()
+            .map(|plane| plane.equation(&self.size))
+            .collect();
+        for program in [
+            &self.program,
+            &self.shadow_program,
+            &self.hatching_program,
+            &self.clip_cap_program,
+        ] {
+            program.set_used();
+            program.set_uniform_4fv("clip_planes", &equations);
+            program.set_uniform_ui("clip_plane_count", equations.len() as u32);
+        }
+        set_clip_distances_enabled(0..clipping::MAX_CLIP_PLANES, false);
+        set_clip_distances_enabled(0..planes.len(), true);
+        self.program.set_used();
+    }
+
+    /// Fills the cross-sections cut by the clipping planes with a solid colour. For each plane,
+    /// the stencil buffer is inverted for every surface in front of it, which leaves the pixels
+    /// where the plane lies inside the model marked, as long as the mesh is closed. The plane's
+    /// rectangle is then drawn over those pixels, clipped by the other planes.
+    ///
+    /// ### Safety
+    ///
+    /// Requires the bound framebuffer to have a stencil buffer and the opaque objects to be drawn.
+    /// This function should only be called from [Model::render].
+    unsafe fn render_clip_caps(&self, planes: &[&ClipPlane]) {
+        gl::Disable(gl::CULL_FACE);
+        gl::Enable(gl::STENCIL_TEST);
+        gl::StencilMask(0xff);
+        for (i, plane) in planes.iter().enumerate() {
+            // Count surfaces in front of the plane, clipped by this plane only.
+            gl::Clear(gl::STENCIL_BUFFER_BIT);
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            gl::DepthMask(gl::FALSE);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::StencilFunc(gl::ALWAYS, 0, 0xff);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::INVERT);
+            set_clip_distances_enabled(0..planes.len(), false);
+            set_clip_distances_enabled(i..i + 1, true);
+            self.shadow_program.set_used();
+            self.shadow_program
+                .set_uniform_matrix4("lightSpaceMatrix", &self.attributes.projection_matrix);
+            self.draw_meshes(None, is_opaque);
+
+            // Draw the cap where an odd number of surfaces were counted.
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            gl::DepthMask(gl::TRUE);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::StencilFunc(gl::NOTEQUAL, 0, 0xff);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            set_clip_distances_enabled(0..planes.len(), true);
+            set_clip_distances_enabled(i..i + 1, false);
+            let [u, v] = plane.cap_extents(&self.size);
+            self.clip_cap_program.set_used();
+            self.clip_cap_program
+                .set_uniform_matrix4("projection_matrix", &self.attributes.projection_matrix);
+            self.clip_cap_program
+                .set_uniform_3f_na("cap_origin", plane.origin(&self.size));
+            self.clip_cap_program.set_uniform_3f_na("cap_u", u);
+            self.clip_cap_program.set_uniform_3f_na("cap_v", v);
+            self.clip_cap_program
+                .set_uniform_3f_arr("cap_color", self.attributes.clip_cap_color);
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+        }
+        gl::Disable(gl::STENCIL_TEST);
+        set_clip_distances_enabled(0..planes.len(), true);
+        self.program.set_used();
+    }
+
+    /// Renders semi-transparent objects with weighted blended order-independent transparency and
+    /// composites them over the already rendered opaque objects.
+    ///
+    /// ### Safety
+    ///
+    /// Requires buffers and data in the struct to be appropriately set.
+    /// This function should only be called from [Model::render], after the opaque objects.
+    unsafe fn render_transparent(&self, target: gl::types::GLuint, viewport: &Viewport) {
+        // Depth-only pass of the opaque objects, using the shadow program from the camera's view.
+        self.transparency
+            .begin_depth_pass(self.attributes.reversed_z);
+        self.shadow_program.set_used();
+        self.shadow_program
+            .set_uniform_matrix4("lightSpaceMatrix", &self.attributes.projection_matrix);
+        self.draw_meshes(None, is_opaque);
+
+        self.transparency.begin_accumulation();
+        self.program.set_used();
+        self.program.set_uniform_ui("oit_pass", 1);
+        self.draw_meshes(Some(&self.program), |opacity| !is_opaque(opacity));
+        self.program.set_uniform_ui("oit_pass", 0);
+
+        self.transparency.composite(target, viewport);
+        self.program.set_used();
+    }
+
+    /// Draws every object whose opacity passes `filter`. If a program is given, its `opacity`
+    /// uniform is updated for each object.
+    ///
+    /// ### Safety
+    ///
+    /// Requires the vertex array and index buffer to be bound.
+    unsafe fn draw_meshes(
+        &self,
+        program: Option<&render_gl::Program>,
+        filter: impl Fn(f32) -> bool,
+    ) {
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            let opacity = self.mesh_opacity(i);
+            if !filter(opacity) {
+                continue;
+            }
+            if let Some(program) = program {
+                program.set_uniform_f("opacity", opacity);
+            }
+            gl::DrawElements(
+                gl::TRIANGLES,
+                mesh.count,
+                gl::UNSIGNED_INT,
+                (mesh.offset * std::mem::size_of::<u32>()) as *const std::ffi::c_void,
+            );
+        }
+    }
+
+    /// Counts the objects whose opacity passes `filter`.
+    fn meshes_drawn(&self, filter: impl Fn(f32) -> bool) -> usize {
+        (0..self.meshes.len())
+            .filter(|&i| filter(self.mesh_opacity(i)))
+            .count()
+    }
+
+    /// Gets the opacity of the object at the given index, defaulting to opaque.
+    fn mesh_opacity(&self, index: usize) -> f32 {
+        self.attributes
+            .mesh_opacity
+            .get(index)
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// Renders the shadowmap to the shadows framebuffer. Returns the light direction, the light
+    /// space matrix, and the penumbra width in shadow map coordinates per unit of depth used by
+    /// soft shadows.
     ///
     /// ### Safety
     ///
     /// Requires buffers and data in the struct to be appropriately set.
     /// This function should only be called from [Model::render].
-    unsafe fn render_shadowmap(&self) -> (na::OPoint<f32, na::Const<3>>, na::Matrix4<f32>) {
+    unsafe fn render_shadowmap(&self) -> (na::OPoint<f32, na::Const<3>>, na::Matrix4<f32>, f32) {
         gl::Disable(gl::CULL_FACE);
         gl::Disable(gl::BLEND);
         gl::Enable(gl::DEPTH_TEST);
-        gl::DepthFunc(gl::LESS);
+        // The light's projection and the linear hatching depth use the default convention.
+        render_gl::set_depth_convention(false);
         self.shadow_program.set_used();
-        let near_plane = 1.0;
-        let far_plane = 500.0;
-        let bound = 250.0;
-        let light_projection =
-            na::Orthographic3::new(-bound, bound, -bound, bound, near_plane, far_plane);
-        let light_pos = match self.attributes.shadows_follow {
-            true => self.attributes.camera_position,
-            false => self.attributes.light_position,
-        };
-        let light = light_pos.normalize() * self.attributes.camera_position.magnitude();
-        let cycle_speed_ms = 2000.0;
-        let degrees =
-            (self.attributes.elapsed % cycle_speed_ms) / cycle_speed_ms * std::f32::consts::TAU;
+        let light_pos = self
+            .attributes
+            .lights
+            .get(self.attributes.shadow_light)
+            .map(|light| light.world_position(&self.attributes.view_matrix))
+            .unwrap_or(self.attributes.camera_position);
+        // A zero light direction, or a point light at the origin, can't be normalized. Fall back
+        // to the camera's direction, as when there is no shadow light.
+        let light_dir = light_pos
+            .try_normalize(f32::EPSILON)
+            .or_else(|| self.attributes.camera_position.try_normalize(f32::EPSILON))
+            .unwrap_or_else(na::Vector3::z);
+        let light = light_dir * self.attributes.camera_position.magnitude();
         let axis = na::Unit::new_normalize(light);
-        let rotation = na::Matrix4::from_axis_angle(&axis, degrees);
+        let rotation =
+            na::Matrix4::from_axis_angle(&axis, self.attributes.light_orbit_angle.to_radians());
         let horizontal = na::Vector3::new(0.0, 1.0, 0.0).cross(&light);
         let up_vector = horizontal.cross(&light).normalize() * self.attributes.shadows_orbit_radius;
         let light = (rotation * (light + up_vector).to_homogeneous()).xyz();
@@ -474,22 +1365,22 @@ impl Model {
             &na::Vector3::new(0.0, 1.0, 0.0),
         );
         let light_vector = center - light;
+        let light_projection = fit_orthographic_to_box(&light_view, self.size);
         let light_space_matrix = light_projection.to_homogeneous() * light_view;
+        let penumbra_scale = self.attributes.shadow_light_size
+            * (light_projection.zfar() - light_projection.znear())
+            / (light_projection.right() - light_projection.left());
         self.shadow_program
             .set_uniform_matrix4("lightSpaceMatrix", &light_space_matrix);
-        gl::Viewport(0, 0, SHADOW_WIDTH, SHADOW_HEIGHT);
+        let size = self.attributes.shadow_map_size as i32;
+        gl::Viewport(0, 0, size, size);
         self.depth_map_fbo.bind();
         gl::Clear(gl::DEPTH_BUFFER_BIT);
         self.vao.bind();
         self.ibo.bind();
-        gl::DrawElements(
-            gl::TRIANGLES,
-            self.indices,
-            gl::UNSIGNED_INT,
-            std::ptr::null::<std::ffi::c_void>(),
-        );
+        self.draw_meshes(None, is_opaque);
         self.depth_map_fbo.unbind();
-        (light_vector, light_space_matrix)
+        (light_vector, light_space_matrix, penumbra_scale)
     }
 
     /// Renders the hatchmap to the hatching framebuffer.
@@ -498,7 +1389,7 @@ impl Model {
     ///
     /// Requires buffers and data in the struct to be appropriately set.
     /// This function should only be called from [Model::render].
-    unsafe fn render_hatchmap(&self, viewport: &Viewport) -> na::Matrix4<f32> {
+    unsafe fn render_hatchmap(&self) -> na::Matrix4<f32> {
         self.hatching_program.set_used();
         self.hatching_program
             .set_uniform_f("hatching_depth", self.attributes.hatching_depth);
@@ -506,48 +1397,39 @@ impl Model {
             .set_uniform_ui("steps", self.attributes.hatching_steps);
         self.hatch_map_fbo.bind();
 
-        let near_plane = 0.1;
-        let aspect = viewport.size().0 as f32 / viewport.size().1 as f32;
-        let hatch_projection = na::Perspective3::new(
-            aspect,
-            std::f32::consts::PI / 4.0,
-            near_plane,
-            HATCHING_FAR_PLANE,
-        );
-        let hatch_pos = self.attributes.camera_position;
-        let center = na::Point3::new(0.0, 0.0, 0.0);
-        let hatch_view = na::Matrix4::look_at_rh(
-            &na::Point3::from(hatch_pos),
-            &center,
-            &na::Vector3::new(0.0, 1.0, 0.0),
-        );
-
-        let hatch_space_matrix = hatch_projection.to_homogeneous() * hatch_view;
+        // The hatching map is seen from the camera, so it uses the same projection, whether that
+        // is perspective, orthographic or one eye of a stereo pair.
+        let hatch_space_matrix = self.attributes.projection_matrix;
         self.hatching_program
             .set_uniform_matrix4("projection_matrix", &hatch_space_matrix);
         self.hatching_program
-            .set_uniform_f("far_plane", HATCHING_FAR_PLANE);
+            .set_uniform_f("near_plane", self.attributes.near_plane);
+        self.hatching_program
+            .set_uniform_f("far_plane", self.attributes.far_plane);
+        self.hatching_program
+            .set_uniform_3f_na("camera_position", self.attributes.camera_position);
+        self.hatching_program.set_uniform_3f_na(
+            "view_direction",
+            view_direction(&self.attributes.view_matrix),
+        );
 
         gl::Disable(gl::CULL_FACE);
         gl::Disable(gl::BLEND);
         gl::Enable(gl::DEPTH_TEST);
-        gl::DepthFunc(gl::LESS);
-        gl::Viewport(0, 0, SHADOW_WIDTH, SHADOW_HEIGHT);
+        gl::Viewport(0, 0, self.hatch_map_size.0, self.hatch_map_size.1);
         gl::Clear(gl::DEPTH_BUFFER_BIT);
         self.vao.bind();
         self.ibo.bind();
-        gl::DrawElements(
-            gl::TRIANGLES,
-            self.indices,
-            gl::UNSIGNED_INT,
-            std::ptr::null::<std::ffi::c_void>(),
-        );
+        self.draw_meshes(None, is_opaque);
         self.hatch_map_fbo.unbind();
         hatch_space_matrix
     }
 
     /// Check if any of the shaders have been updated.
     pub fn check_shader_update(&mut self, path: &std::path::Path, res: &Resources) -> bool {
+        if self.transparency.check_shader_update(path, res) {
+            return true;
+        }
         let path = path.file_stem().map(|p| p.to_string_lossy().to_string());
         if path == Some(MAIN_SHADER_NAME.to_string()) {
             match render_gl::Program::from_res(res, MAIN_SHADER_PATH) {
@@ -569,6 +1451,15 @@ impl Model {
                 }
                 Err(e) => eprintln!("Shader reload error: {}", e),
             }
+        } else if path == Some(CLIP_CAP_SHADER_NAME.to_string()) {
+            match render_gl::Program::from_res(res, CLIP_CAP_SHADER_PATH) {
+                Ok(program) => {
+                    self.clip_cap_program.unset_used();
+                    self.clip_cap_program = program;
+                    return true;
+                }
+                Err(e) => eprintln!("Shader reload error: {}", e),
+            }
         } else if path == Some(HATCHING_SHADER_NAME.to_string()) {
             match render_gl::Program::from_res(res, HATCHING_SHADER_PATH) {
                 Ok(program) => {
@@ -583,3 +1474,72 @@ impl Model {
         false
     }
 }
+
+/// Estimates curvature on the merged mesh and stores it in the vertices. Returns the 95th
+/// percentile of the dominant principal curvature's magnitude, as a typical value for scaling.
+fn add_curvature(vertices: &mut [Vertex], indices: &[u32]) -> f32 {
+    let positions: Vec<na::Vector3<f32>> = vertices
+        .iter()
+        .map(|v| na::Vector3::new(v.pos.d0, v.pos.d1, v.pos.d2))
+        .collect();
+    let curvatures = curvature::estimate(&positions, indices);
+
+    // Principal directions have no sign, so orient them consistently to avoid them cancelling
+    // out when interpolated across a triangle.
+    let reference = na::Vector3::new(1.0, 1.0, 1.0);
+    let directions: Vec<na::Vector3<f32>> = curvatures
+        .iter()
+        .map(|curvature| {
+            let direction = curvature.dominant_direction();
+            if direction.dot(&reference) < 0.0 {
+                -direction
+            } else {
+                direction
+            }
+        })
+        .collect();
+    let dominant: Vec<f32> = curvatures.iter().map(|c| c.dominant()).collect();
+    let derivatives =
+        curvature::directional_derivatives(&positions, indices, &dominant, &directions);
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let (c, d) = (&curvatures[i], &directions[i]);
+        vertex.curvature_direction = f32_f32_f32::from((d.x, d.y, d.z));
+        vertex.principal_curvatures = f32_f32::from((c.k1, c.k2));
+        vertex.curvature_measures =
+            f32_f32_f32_f32::from((c.mean(), c.gaussian(), c.shape_index(), derivatives[i]));
+    }
+
+    let mut magnitudes: Vec<f32> = dominant.iter().map(|k| k.abs()).collect();
+    magnitudes.sort_by(f32::total_cmp);
+    magnitudes
+        .get(magnitudes.len() * 95 / 100)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Returns the world space direction the camera looks in, given its view matrix.
+fn view_direction(view_matrix: &na::Matrix4<f32>) -> na::Vector3<f32> {
+    -view_matrix.fixed_view::<1, 3>(2, 0).transpose()
+}
+
+/// Enables or disables the given range of `GL_CLIP_DISTANCEi` outputs.
+///
+/// ### Safety
+///
+/// Indices must be below the driver's `GL_MAX_CLIP_DISTANCES`, which is at least 8.
+unsafe fn set_clip_distances_enabled(indices: std::ops::Range<usize>, enabled: bool) {
+    for i in indices {
+        let capability = gl::CLIP_DISTANCE0 + i as gl::types::GLenum;
+        if enabled {
+            gl::Enable(capability);
+        } else {
+            gl::Disable(capability);
+        }
+    }
+}
+
+/// Objects at full opacity are drawn normally, cast shadows and take part in hatching.
+fn is_opaque(opacity: f32) -> bool {
+    opacity >= 1.0
+}
@@ -10,6 +10,9 @@ pub type ArrayBuffer = Buffer<{ gl::ARRAY_BUFFER }>;
 /// Represents an OpenGL element array buffer.
 pub type ElementArrayBuffer = Buffer<{ gl::ELEMENT_ARRAY_BUFFER }>;
 
+/// Represents an OpenGL uniform buffer.
+pub type UniformBuffer = Buffer<{ gl::UNIFORM_BUFFER }>;
+
 /// Generic buffer intended to handle both array buffers and element array buffers.
 pub struct Buffer<const T: gl::types::GLuint> {
     vbo: gl::types::GLuint,
@@ -38,6 +41,13 @@ impl<const T: gl::types::GLuint> Buffer<T> {
         }
     }
 
+    /// Binds the buffer to an indexed binding point, such as a uniform block binding.
+    pub fn bind_base(&self, index: gl::types::GLuint) {
+        unsafe {
+            gl::BindBufferBase(T, index, self.vbo);
+        }
+    }
+
     /// Uploads data to the buffer, informing the driver that this is static data.
     pub fn static_draw_data<S>(&self, data: &[S]) {
         self.draw_data(data, gl::STATIC_DRAW);
@@ -55,7 +65,7 @@ impl<const T: gl::types::GLuint> Buffer<T> {
         unsafe {
             gl::BufferData(
                 T,
-                (data.len() * ::std::mem::size_of::<S>()) as gl::types::GLsizeiptr,
+                std::mem::size_of_val(data) as gl::types::GLsizeiptr,
                 data.as_ptr() as *const gl::types::GLvoid,
                 usage,
             );
@@ -114,11 +124,17 @@ impl Drop for VertexArray {
 pub struct Texture {
     pub texture_id: gl::types::GLuint,
     texture_unit: gl::types::GLuint,
+    target: gl::types::GLenum,
 }
 
 impl Texture {
-    /// Generates a new texture unit.
+    /// Generates a new 2D texture unit.
     pub fn new(texture_unit: gl::types::GLenum) -> Self {
+        Self::with_target(texture_unit, gl::TEXTURE_2D)
+    }
+
+    /// Generates a new texture unit for the given target, such as `gl::TEXTURE_1D`.
+    pub fn with_target(texture_unit: gl::types::GLenum, target: gl::types::GLenum) -> Self {
         let mut texture_id: gl::types::GLuint = 0;
         unsafe {
             gl::GenTextures(1, &mut texture_id);
@@ -127,6 +143,7 @@ impl Texture {
         Self {
             texture_id,
             texture_unit,
+            target,
         }
     }
 
@@ -158,7 +175,7 @@ impl Texture {
 
             pixels.as_ptr() as *const std::ffi::c_void
         } else {
-            std::ptr::null() as *const std::ffi::c_void
+            std::ptr::null()
         };
 
         // Safety: the size of the data **MUST** be correct, as checked above.
@@ -191,6 +208,62 @@ impl Texture {
         }
     }
 
+    /// Loads RGBA texel data into this texture as a linearly filtered 1D lookup table.
+    pub fn load_texture_1d(&self, pixels: &[u8], internal_format: gl::types::GLint) {
+        assert_eq!(pixels.len() % 4, 0, "Inconsistent texture size!");
+        self.bind();
+
+        // Safety: the size of the data is derived from the slice length, as checked above.
+        unsafe {
+            gl::TexImage1D(
+                gl::TEXTURE_1D,
+                0,
+                internal_format,
+                (pixels.len() / 4) as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const std::ffi::c_void,
+            );
+            gl::TexParameteri(gl::TEXTURE_1D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_1D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_1D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        }
+    }
+
+    /// Allocates storage for a multisampled texture. The texture must have been created with the
+    /// `gl::TEXTURE_2D_MULTISAMPLE` target.
+    pub fn load_texture_multisample(
+        &self,
+        dimensions: (i32, i32),
+        samples: i32,
+        internal_format: gl::types::GLenum,
+    ) {
+        assert!(dimensions.0 >= 0);
+        assert!(dimensions.1 >= 0);
+        assert_eq!(self.target, gl::TEXTURE_2D_MULTISAMPLE);
+        self.bind();
+        unsafe {
+            gl::TexImage2DMultisample(
+                gl::TEXTURE_2D_MULTISAMPLE,
+                samples,
+                internal_format,
+                dimensions.0,
+                dimensions.1,
+                gl::TRUE, // Use the same sample locations for every texel.
+            );
+        }
+    }
+
+    /// Sets the minification and magnification filter, such as `gl::LINEAR`.
+    pub fn set_filter(&self, filter: gl::types::GLenum) {
+        self.bind();
+        unsafe {
+            gl::TexParameteri(self.target, gl::TEXTURE_MIN_FILTER, filter as i32);
+            gl::TexParameteri(self.target, gl::TEXTURE_MAG_FILTER, filter as i32);
+        }
+    }
+
     /// Update part of the texture. Will error out if dimensions + offset is out of bounds.
     pub fn update_subtexture(
         &self,
@@ -219,7 +292,7 @@ impl Texture {
 
             pixels.as_ptr() as *const std::ffi::c_void
         } else {
-            std::ptr::null() as *const std::ffi::c_void
+            std::ptr::null()
         };
 
         // Safety: this isn't. The size of the data must be correct, and yet, we're writing to
@@ -243,18 +316,14 @@ impl Texture {
     /// Sets the border color of the texture.
     pub fn set_border_color(&self, border_color: &[f32; 4]) {
         unsafe {
-            gl::TexParameterfv(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_BORDER_COLOR,
-                border_color.as_ptr(),
-            );
+            gl::TexParameterfv(self.target, gl::TEXTURE_BORDER_COLOR, border_color.as_ptr());
         }
     }
 
     pub fn bind(&self) {
         unsafe {
             gl::ActiveTexture(self.texture_unit);
-            gl::BindTexture(gl::TEXTURE_2D, self.texture_id);
+            gl::BindTexture(self.target, self.texture_id);
         }
     }
 
@@ -263,7 +332,7 @@ impl Texture {
         self.bind();
         unsafe {
             gl::TexParameteri(
-                gl::TEXTURE_2D,
+                self.target,
                 gl::TEXTURE_COMPARE_MODE,
                 mode as gl::types::GLint,
             );
@@ -273,14 +342,14 @@ impl Texture {
     pub fn bind_to(&self, texture_unit: gl::types::GLenum) {
         unsafe {
             gl::ActiveTexture(texture_unit);
-            gl::BindTexture(gl::TEXTURE_2D, self.texture_id);
+            gl::BindTexture(self.target, self.texture_id);
         }
     }
 
     pub fn unbind(&self) {
         unsafe {
             gl::ActiveTexture(self.texture_unit);
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindTexture(self.target, 0);
         }
     }
 }
@@ -316,6 +385,14 @@ impl FrameBuffer {
         }
     }
 
+    /// Set the color attachments fragment shader outputs are written to, in output location order.
+    pub fn set_draw_buffers(&self, buffers: &[gl::types::GLenum]) {
+        self.bind();
+        unsafe {
+            gl::DrawBuffers(buffers.len() as gl::types::GLsizei, buffers.as_ptr());
+        }
+    }
+
     /// Bind a texture as the target for this buffer.
     pub fn bind_texture(&self, attachment: gl::types::GLenum, texture: &Texture) {
         self.bind();
@@ -323,13 +400,67 @@ impl FrameBuffer {
             gl::FramebufferTexture2D(
                 gl::FRAMEBUFFER,
                 attachment,
-                gl::TEXTURE_2D,
+                texture.target,
                 texture.texture_id,
                 0,
             );
         }
     }
 
+    /// Whether the attachments form a framebuffer that can be rendered to.
+    pub fn is_complete(&self) -> bool {
+        self.bind();
+        unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) == gl::FRAMEBUFFER_COMPLETE }
+    }
+
+    /// Copies the colour attachment into the framebuffer with the given id, as returned by
+    /// [FrameBuffer::bound_id], resolving multisampled images. Leaves that framebuffer bound.
+    pub fn blit_to(&self, target: gl::types::GLuint, (w, h): (i32, i32)) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target);
+            gl::BlitFramebuffer(0, 0, w, h, 0, 0, w, h, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+        }
+        Self::bind_id(target);
+    }
+
+    /// Reads the colour attachment as 8-bit RGBA, with rows from the bottom of the image up.
+    pub fn read_pixels(&self, (w, h): (u32, u32)) -> Vec<u8> {
+        let mut pixels = vec![0; w as usize * h as usize * 4];
+        self.bind();
+        unsafe {
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                w as i32,
+                h as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut std::ffi::c_void,
+            );
+        }
+        pixels
+    }
+
+    /// Returns the id of the framebuffer currently bound for drawing, where 0 is the default
+    /// framebuffer. Lets render passes that use their own framebuffers restore the caller's target.
+    pub fn bound_id() -> gl::types::GLuint {
+        let mut id: gl::types::GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut id);
+        }
+        id as gl::types::GLuint
+    }
+
+    /// Binds a framebuffer by the id returned from [FrameBuffer::bound_id].
+    pub fn bind_id(id: gl::types::GLuint) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+        }
+    }
+
     pub fn bind(&self) {
         unsafe {
             gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
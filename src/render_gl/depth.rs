@@ -0,0 +1,67 @@
+//! Contains functions switching between the default depth convention and reversed-Z.
+
+use std::sync::OnceLock;
+
+/// Returns whether the driver can map clip space depth to `[0, 1]`, which reversed-Z needs to
+/// keep its precision. This is core in OpenGL 4.5, and otherwise needs `ARB_clip_control`.
+pub fn reversed_z_supported() -> bool {
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+    *SUPPORTED.get_or_init(|| {
+        if !gl::ClipControl::is_loaded() {
+            return false;
+        }
+        let (mut major, mut minor, mut count) = (0, 0, 0);
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        }
+        (major, minor) >= (4, 5)
+            || (0..count.max(0) as u32).any(|i| {
+                // Safety: The index is below the extension count, so a string is returned.
+                let name = unsafe {
+                    std::ffi::CStr::from_ptr(gl::GetStringi(gl::EXTENSIONS, i) as *const _)
+                };
+                name.to_bytes() == b"GL_ARB_clip_control"
+            })
+    })
+}
+
+/// Returns the depth buffer value of the far plane, which depth buffers are cleared to.
+pub fn far_depth(reversed: bool) -> f32 {
+    if reversed {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+/// Returns the depth comparison letting nearer fragments pass.
+pub fn depth_func(reversed: bool) -> gl::types::GLenum {
+    if reversed {
+        gl::GREATER
+    } else {
+        gl::LESS
+    }
+}
+
+/// Sets up clip control, the depth clear value and the depth comparison for either convention.
+/// Reversed-Z maps the near plane to 1 and the far plane to 0, which spreads the precision of
+/// floating point depth evenly over distance, and has to be paired with a projection doing the
+/// same, like [crate::camera::Camera] makes when asked to.
+///
+/// ### Safety
+///
+/// Reversed-Z should only be requested if [reversed_z_supported] returns true.
+pub unsafe fn set_depth_convention(reversed: bool) {
+    if reversed_z_supported() {
+        let depth_mode = if reversed {
+            gl::ZERO_TO_ONE
+        } else {
+            gl::NEGATIVE_ONE_TO_ONE
+        };
+        gl::ClipControl(gl::LOWER_LEFT, depth_mode);
+    }
+    gl::ClearDepth(far_depth(reversed) as f64);
+    gl::DepthFunc(depth_func(reversed));
+}
@@ -0,0 +1,72 @@
+//! Contains [FullscreenPass], the screen-covering triangle post-processing passes draw with.
+
+use super::{buffer::VertexArray, Program, Shader};
+use crate::resources::Resources;
+use anyhow::Result;
+
+const VERTEX_SHADER_PATH: &str = "shaders/fullscreen.vert";
+const VERTEX_SHADER_NAME: &str = "fullscreen";
+
+/// A program drawn over the whole viewport, made from the shared `fullscreen.vert` and a fragment
+/// shader of its own.
+pub struct FullscreenPass {
+    program: Program,
+    /// The triangle is generated from `gl_VertexID`, but core profile still requires a vertex
+    /// array to be bound.
+    vao: VertexArray,
+    /// Resource path of the fragment shader, without its extension.
+    fragment_path: &'static str,
+}
+
+impl FullscreenPass {
+    /// Set up [FullscreenPass], compiling the shaders.
+    pub fn new(res: &Resources, fragment_path: &'static str) -> Result<Self> {
+        Ok(Self {
+            program: Self::compile(res, fragment_path)?,
+            vao: VertexArray::new(),
+            fragment_path,
+        })
+    }
+
+    /// Compiles and links the shared vertex shader with the given fragment shader.
+    fn compile(res: &Resources, fragment_path: &str) -> Result<Program> {
+        let shaders = [
+            Shader::from_res(res, VERTEX_SHADER_PATH)?,
+            Shader::from_res(res, &format!("{}.frag", fragment_path))?,
+        ];
+        Program::from_shaders(&shaders)
+    }
+
+    /// Returns the program, for setting it up and its uniforms before drawing.
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Draws the triangle covering the viewport.
+    ///
+    /// ### Safety
+    ///
+    /// Should only be called with [FullscreenPass::program] in use.
+    pub unsafe fn draw(&self) {
+        self.vao.bind();
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        self.vao.unbind();
+    }
+
+    /// Check if the shader has been updated.
+    pub fn check_shader_update(&mut self, path: &std::path::Path, res: &Resources) -> bool {
+        let path = path.file_stem().map(|p| p.to_string_lossy().to_string());
+        let fragment_name = self.fragment_path.rsplit('/').next();
+        if path.as_deref() == Some(VERTEX_SHADER_NAME) || path.as_deref() == fragment_name {
+            match Self::compile(res, self.fragment_path) {
+                Ok(program) => {
+                    self.program.unset_used();
+                    self.program = program;
+                    return true;
+                }
+                Err(e) => eprintln!("Shader reload error: {}", e),
+            }
+        }
+        false
+    }
+}
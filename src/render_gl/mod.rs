@@ -3,10 +3,14 @@
 pub mod buffer;
 mod color_buffer;
 pub mod data;
+mod depth;
+mod fullscreen;
 mod shader;
 mod viewport;
 
 pub use self::color_buffer::ColorBuffer;
+pub use self::depth::{depth_func, far_depth, reversed_z_supported, set_depth_convention};
+pub use self::fullscreen::FullscreenPass;
 pub use self::shader::{Program, Shader};
 pub use self::viewport::Viewport;
 
@@ -95,6 +95,16 @@ impl Program {
         gl::Uniform2f(uniform_location, data.0, data.1);
     }
 
+    /// Sets a `vec4` array uniform, starting from its first element.
+    pub unsafe fn set_uniform_4fv(&self, uniform_id: &str, data: &[[f32; 4]]) {
+        let uniform_location = self.get_uniform_location(uniform_id);
+        gl::Uniform4fv(
+            uniform_location,
+            data.len() as gl::types::GLsizei,
+            data.as_ptr() as *const f32,
+        );
+    }
+
     pub unsafe fn set_uniform_f(&self, uniform_id: &str, data: f32) {
         let uniform_location = self.get_uniform_location(uniform_id);
         gl::Uniform1f(uniform_location, data);
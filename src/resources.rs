@@ -61,31 +61,59 @@ impl Resources {
         file.read_to_end(&mut buffer)?;
 
         // check for nul byte
-        if buffer.iter().any(|i| *i == 0) {
+        if buffer.contains(&0) {
             return Err(anyhow!("Resource contains NUL byte."));
         }
 
         Ok(unsafe { ffi::CString::from_vec_unchecked(buffer) })
     }
 
-    /// Attempts to load the given obj file.
-    pub fn load_model(&self, resource_name: &str) -> Result<tobj::Mesh> {
+    /// Attempts to load the given text file as a [String].
+    pub fn load_string(&self, resource_name: &str) -> Result<String> {
+        fs::read_to_string(resource_name_to_path(&self.root_path, resource_name))
+            .context("Failed to read resource file")
+    }
+
+    /// Attempts to write the given text to a file in the resource directory.
+    pub fn save_string(&self, resource_name: &str, contents: &str) -> Result<()> {
+        fs::write(
+            resource_name_to_path(&self.root_path, resource_name),
+            contents,
+        )
+        .context("Failed to write resource file")
+    }
+
+    /// Returns the full path of a file in the resource directory.
+    pub fn path(&self, resource_name: &str) -> PathBuf {
+        resource_name_to_path(&self.root_path, resource_name)
+    }
+
+    /// Attempts to load the given obj file, returning every object in it.
+    pub fn load_model(&self, resource_name: &str) -> Result<Vec<tobj::Model>> {
         let path = resource_name_to_path(&self.root_path, resource_name);
         let settings = tobj::LoadOptions {
             single_index: true,
             triangulate: true,
             ..Default::default()
         };
-        let (mut models, _) = tobj::load_obj(path, &settings)?;
-        let model = models
-            .pop()
-            .ok_or_else(|| anyhow!("Obj file has no model!"))?;
+        let (models, _) = tobj::load_obj(path, &settings)?;
+        if models.is_empty() {
+            return Err(anyhow!("Obj file has no model!"));
+        }
 
-        Ok(model.mesh)
+        Ok(models)
     }
 
     /// Lists all models found in the root resource directory.
     pub fn list_models(&self) -> Vec<String> {
+        self.list_files(&["obj"])
+            .into_iter()
+            .map(|file| file.trim_end_matches(".obj").to_owned())
+            .collect()
+    }
+
+    /// Lists the files in the root resource directory with one of the given extensions.
+    pub fn list_files(&self, extensions: &[&str]) -> Vec<String> {
         std::fs::read_dir(&self.root_path)
             .and_then(|readdir| {
                 readdir
@@ -94,14 +122,21 @@ impl Resources {
             })
             .unwrap_or_default()
             .into_iter()
-            .filter(|entry| entry.ends_with(".obj"))
-            .map(|file| file.trim_end_matches(".obj").to_owned())
+            .filter(|entry| {
+                Path::new(entry)
+                    .extension()
+                    .is_some_and(|e| extensions.iter().any(|&x| e == x))
+            })
             .collect()
     }
 }
 
-/// Joins a location string to a root directory path.
+/// Joins a location string to a root directory path. Absolute paths are used as they are, so
+/// files outside the directory can be loaded too.
 fn resource_name_to_path(root_dir: &Path, location: &str) -> PathBuf {
+    if Path::new(location).is_absolute() {
+        return location.into();
+    }
     let mut path: PathBuf = root_dir.into();
 
     for part in location.split('/') {
@@ -0,0 +1,172 @@
+//! Contains [SettingsFile], the render settings saved as JSON to be applied again later, either
+//! in the application or when batch rendering. Captured images store them too, so they can be
+//! loaded from a screenshot as well.
+
+use crate::{
+    antialiasing::AntiAliasing,
+    camera::{CameraState, Projection},
+    model::Attributes,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+/// File in the resource directory settings are saved to unless another is chosen.
+pub const DEFAULT_FILE: &str = "settings.json";
+/// Background colour the application starts with.
+pub const DEFAULT_BACKGROUND: [f32; 3] = [0.3, 0.3, 0.5];
+/// Keyword of the PNG text chunk captured images store their settings in.
+pub const PNG_KEYWORD: &str = "Settings";
+/// Version of the settings format written by [SettingsFile::to_json].
+const FORMAT_VERSION: u32 = 1;
+
+/// Everything deciding how a model is drawn, apart from the model itself. Settings missing from
+/// a file keep their defaults, so files can be written by hand with only the settings of
+/// interest.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsFile {
+    pub version: u32,
+    pub attributes: Attributes,
+    pub background: [f32; 3],
+    pub anti_aliasing: AntiAliasing,
+    pub msaa_samples: u32,
+    pub projection: Projection,
+    /// Viewpoint the settings were saved from, in the coordinates of the centered model.
+    pub camera: Option<CameraState>,
+}
+
+impl Default for SettingsFile {
+    fn default() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            attributes: Attributes::default(),
+            background: DEFAULT_BACKGROUND,
+            anti_aliasing: AntiAliasing::default(),
+            msaa_samples: 4,
+            projection: Projection::default(),
+            camera: None,
+        }
+    }
+}
+
+impl SettingsFile {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        let settings: Self = serde_json::from_str(text)?;
+        if settings.version > FORMAT_VERSION {
+            return Err(anyhow!(
+                "The settings were saved by a newer version (format {}).",
+                settings.version
+            ));
+        }
+        Ok(settings)
+    }
+
+    /// Reads settings from a JSON file, or from a PNG image captured by the application.
+    pub fn load(path: &Path) -> Result<Self> {
+        let is_png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if is_png {
+            let data = fs::read(path).context("Failed to read the image")?;
+            return Self::from_png(&data);
+        }
+        Self::from_json(&fs::read_to_string(path).context("Failed to read the settings file")?)
+    }
+
+    /// Reads the settings stored in a PNG image.
+    pub fn from_png(data: &[u8]) -> Result<Self> {
+        let reader = png::Decoder::new(data).read_info()?;
+        let chunk = reader
+            .info()
+            .utf8_text
+            .iter()
+            .find(|chunk| chunk.keyword == PNG_KEYWORD)
+            .ok_or_else(|| anyhow!("The image has no settings stored in it."))?;
+        Self::from_json(&chunk.get_text()?)
+    }
+
+    /// Returns `current` with the saved attributes applied, keeping those that follow from the
+    /// camera and the loaded model. Object opacities are only applied to models with as many
+    /// objects as the one they were saved with.
+    pub fn apply(&self, current: &Attributes) -> Attributes {
+        let mut attributes = self.attributes.clone();
+        attributes.projection_matrix = current.projection_matrix;
+        attributes.camera_position = current.camera_position;
+        attributes.view_matrix = current.view_matrix;
+        attributes.orthographic = current.orthographic;
+        attributes.near_plane = current.near_plane;
+        attributes.far_plane = current.far_plane;
+        attributes.reversed_z = current.reversed_z;
+        attributes.inside_model = current.inside_model;
+        attributes.model_size = current.model_size;
+        attributes.elapsed = current.elapsed;
+        attributes.pixels_per_point = current.pixels_per_point;
+        if attributes.mesh_opacity.len() != current.mesh_opacity.len() {
+            attributes.mesh_opacity = current.mesh_opacity.clone();
+        }
+        attributes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        capture::{self, Image},
+        model::HatchingMode,
+    };
+
+    #[test]
+    fn settings_round_trip_and_apply() {
+        let mut settings = SettingsFile::default();
+        settings.attributes.hatching_mode = HatchingMode::Curvature;
+        settings.attributes.mesh_opacity = vec![1.0, 0.5];
+        settings.attributes.model_size = 99.0;
+        settings.projection = Projection::Orthographic;
+        let loaded = SettingsFile::from_json(&settings.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.attributes.hatching_mode, HatchingMode::Curvature);
+        assert_eq!(loaded.projection, Projection::Orthographic);
+
+        let current = Attributes {
+            model_size: 3.0,
+            mesh_opacity: vec![1.0],
+            ..Default::default()
+        };
+        let applied = loaded.apply(&current);
+        assert_eq!(applied.hatching_mode, HatchingMode::Curvature);
+        assert_eq!(applied.model_size, 3.0);
+        assert_eq!(applied.mesh_opacity, vec![1.0]);
+
+        let partial = SettingsFile::from_json("{\"attributes\": {\"toon_factor\": 0.25}}").unwrap();
+        assert_eq!(partial.attributes.toon_factor, 0.25);
+        assert_eq!(partial.msaa_samples, 4);
+        assert!(SettingsFile::from_json("{\"version\": 99}").is_err());
+    }
+
+    #[test]
+    fn settings_read_from_png() {
+        let mut settings = SettingsFile::default();
+        settings.attributes.toon_factor = 0.25;
+        let image = Image {
+            size: (1, 1),
+            pixels: vec![0, 0, 0, 255],
+        };
+        let text = [
+            ("Software", "IVRIGST".to_owned()),
+            (PNG_KEYWORD, settings.to_json().unwrap()),
+        ];
+        let mut data = Vec::new();
+        capture::write_png(&mut data, &image, &text).unwrap();
+        let loaded = SettingsFile::from_png(&data).unwrap();
+        assert_eq!(loaded.attributes.toon_factor, 0.25);
+
+        data.clear();
+        capture::write_png(&mut data, &image, &text[..1]).unwrap();
+        assert!(SettingsFile::from_png(&data).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! Contains [StereoBuffer], which renders the model once per eye and combines the two images for
+//! anaglyph glasses, 3D monitors or row-interlaced displays.
+
+use crate::{
+    antialiasing::{AntiAliasing, SceneBuffer},
+    camera::{Camera, Eye},
+    model::Model,
+    render_gl::{
+        buffer::{FrameBuffer, Texture},
+        FullscreenPass, Viewport,
+    },
+    resources::Resources,
+};
+use anyhow::Result;
+use nalgebra as na;
+
+const SHADER_PATH: &str = "shaders/stereo_composite";
+const TEXTURE_UNIT_LEFT: gl::types::GLenum = gl::TEXTURE0;
+const TEXTURE_UNIT_RIGHT: gl::types::GLenum = gl::TEXTURE1;
+
+/// Represents how the two eye images are combined on screen.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub enum StereoMode {
+    /// A single view from the camera.
+    #[default]
+    Off = 0,
+    /// Red-cyan anaglyph, with the left eye in the red channel.
+    Anaglyph = 1,
+    /// Left eye in the left half and right eye in the right half, each squeezed to half width.
+    SideBySide = 2,
+    /// Left eye in the top half and right eye in the bottom half, each squeezed to half height.
+    TopBottom = 3,
+    /// Alternating rows, starting with the left eye on the top row.
+    Interlaced = 4,
+}
+
+impl StereoMode {
+    pub const ALL: [StereoMode; 5] = [
+        StereoMode::Off,
+        StereoMode::Anaglyph,
+        StereoMode::SideBySide,
+        StereoMode::TopBottom,
+        StereoMode::Interlaced,
+    ];
+}
+
+impl std::fmt::Display for StereoMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            StereoMode::Off => write!(f, "Off"),
+            StereoMode::Anaglyph => write!(f, "Red-cyan anaglyph"),
+            StereoMode::SideBySide => write!(f, "Side-by-side"),
+            StereoMode::TopBottom => write!(f, "Top-bottom"),
+            StereoMode::Interlaced => write!(f, "Row-interlaced"),
+        }
+    }
+}
+
+/// Stereo output settings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StereoSettings {
+    pub mode: StereoMode,
+    /// Eye separation as a fraction of the convergence distance.
+    pub interocular: f32,
+    /// Distance to the zero-parallax plane as a fraction of the camera distance. Geometry in
+    /// front of the plane appears to stick out of the screen.
+    pub convergence: f32,
+    /// Swaps the eyes, for glasses or displays with the opposite convention.
+    pub swap_eyes: bool,
+}
+
+impl Default for StereoSettings {
+    fn default() -> Self {
+        Self {
+            mode: StereoMode::Off,
+            interocular: 0.03,
+            convergence: 1.0,
+            swap_eyes: false,
+        }
+    }
+}
+
+/// Render target for one eye. The eye is drawn through its own [SceneBuffer], so it is
+/// anti-aliased before the images are combined.
+struct EyeTarget {
+    fbo: FrameBuffer,
+    color: Texture,
+    depth: Texture,
+    scene: SceneBuffer,
+}
+
+impl EyeTarget {
+    fn new(res: &Resources, texture_unit: gl::types::GLenum) -> Result<Self> {
+        Ok(Self {
+            fbo: FrameBuffer::new(),
+            color: Texture::new(texture_unit),
+            depth: Texture::new(texture_unit),
+            scene: SceneBuffer::new(res)?,
+        })
+    }
+
+    fn allocate(&mut self, (w, h): (u32, u32)) {
+        let dimensions = (w as i32, h as i32);
+        self.color.load_texture(
+            dimensions,
+            None,
+            gl::RGBA8 as gl::types::GLint,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            false,
+        );
+        self.depth.load_texture(
+            dimensions,
+            None,
+            gl::DEPTH32F_STENCIL8 as gl::types::GLint,
+            gl::DEPTH_STENCIL,
+            gl::FLOAT_32_UNSIGNED_INT_24_8_REV,
+            false,
+        );
+        self.depth.unbind();
+        self.fbo.bind_texture(gl::COLOR_ATTACHMENT0, &self.color);
+        self.fbo
+            .bind_texture(gl::DEPTH_STENCIL_ATTACHMENT, &self.depth);
+        self.fbo.unbind();
+    }
+}
+
+/// Holds the per-eye render targets and the pass combining them. The targets are reallocated
+/// when the window size or stereo mode changes the size of an eye image.
+pub struct StereoBuffer {
+    pass: FullscreenPass,
+    eyes: [EyeTarget; 2],
+    eye_size: (u32, u32),
+}
+
+impl StereoBuffer {
+    /// Set up [StereoBuffer], compiling shaders and creating the framebuffers.
+    pub fn new(res: &Resources) -> Result<Self> {
+        Ok(Self {
+            pass: FullscreenPass::new(res, SHADER_PATH)?,
+            eyes: [
+                EyeTarget::new(res, TEXTURE_UNIT_LEFT)?,
+                EyeTarget::new(res, TEXTURE_UNIT_RIGHT)?,
+            ],
+            eye_size: (0, 0),
+        })
+    }
+
+    /// Returns the size each eye is rendered at for the given mode and output size.
+    pub fn eye_size(mode: StereoMode, (w, h): (u32, u32)) -> (u32, u32) {
+        match mode {
+            StereoMode::SideBySide => ((w / 2).max(1), h),
+            StereoMode::TopBottom => (w, (h / 2).max(1)),
+            _ => (w, h),
+        }
+    }
+
+    /// Renders the model once per eye and combines the images into the framebuffer bound when
+    /// called, which is left bound. The eye views are derived from `camera`; the model's camera
+    /// attributes are restored afterwards. Each eye is anti-aliased with `anti_aliasing`, so the
+    /// framebuffer drawn into shouldn't be anti-aliased again.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        model: &mut Model,
+        camera: &Camera,
+        model_isometry: na::Isometry3<f32>,
+        viewport: &Viewport,
+        settings: &StereoSettings,
+        anti_aliasing: AntiAliasing,
+        msaa_samples: u32,
+    ) {
+        let target = FrameBuffer::bound_id();
+        let size = Self::eye_size(settings.mode, viewport.size());
+        if size != self.eye_size {
+            for eye in self.eyes.iter_mut() {
+                eye.allocate(size);
+            }
+            self.eye_size = size;
+        }
+
+        // Squeezed modes keep the output aspect ratio, as 3D displays stretch each half back.
+        let aspect = viewport.size().0 as f32 / viewport.size().1.max(1) as f32;
+        let eye_viewport = Viewport::for_window(size.0 as i32, size.1 as i32);
+        let mono = model.get_attributes().clone();
+        let eyes = match settings.swap_eyes {
+            false => [Eye::Left, Eye::Right],
+            true => [Eye::Right, Eye::Left],
+        };
+        for (eye, target) in eyes.into_iter().zip(self.eyes.iter_mut()) {
+            let view = camera.construct_eye_mvp(
+                aspect,
+                model_isometry,
+                eye,
+                settings.interocular,
+                settings.convergence,
+            );
+            let mut attr = model.get_attributes().clone();
+            attr.projection_matrix = view.model_view_projection;
+            attr.view_matrix = view.view;
+            attr.camera_position = view.position.coords;
+            model.set_attributes(attr);
+
+            target.fbo.bind();
+            target
+                .scene
+                .begin(&eye_viewport, anti_aliasing, msaa_samples);
+            unsafe {
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            }
+            model.render(&eye_viewport);
+            // Safety: The scene began above with the same viewport.
+            unsafe {
+                target.scene.finish(&eye_viewport);
+            }
+        }
+
+        // Only the camera is restored, so changes made while rendering, like the light orbit,
+        // carry over to the next frame.
+        let mut attr = model.get_attributes().clone();
+        attr.projection_matrix = mono.projection_matrix;
+        attr.view_matrix = mono.view_matrix;
+        attr.camera_position = mono.camera_position;
+        model.set_attributes(attr);
+
+        FrameBuffer::bind_id(target);
+        viewport.set_used();
+        unsafe {
+            self.composite(settings.mode);
+        }
+    }
+
+    /// Draws the combined eye images over the bound framebuffer.
+    ///
+    /// ### Safety
+    ///
+    /// Should only be called once both eyes have been rendered.
+    unsafe fn composite(&self, mode: StereoMode) {
+        self.pass.program().set_used();
+        self.pass
+            .program()
+            .set_uniform_ui("stereo_mode", mode as u32);
+        for eye in self.eyes.iter() {
+            eye.color.bind();
+        }
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::BLEND);
+        self.pass.draw();
+        gl::Enable(gl::DEPTH_TEST);
+        for eye in self.eyes.iter() {
+            eye.color.unbind();
+        }
+        self.pass.program().unset_used();
+    }
+
+    /// Check if the shader has been updated.
+    pub fn check_shader_update(&mut self, path: &std::path::Path, res: &Resources) -> bool {
+        let mut updated = false;
+        for eye in self.eyes.iter_mut() {
+            updated |= eye.scene.check_shader_update(path, res);
+        }
+        updated |= self.pass.check_shader_update(path, res);
+        updated
+    }
+}
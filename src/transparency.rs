@@ -0,0 +1,156 @@
+//! Contains [TransparencyBuffer], which composites semi-transparent surfaces using weighted blended
+//! order-independent transparency as described by
+//! [McGuire and Bavoil](https://jcgt.org/published/0002/02/09/).
+
+use crate::{
+    render_gl::{
+        self,
+        buffer::{FrameBuffer, Texture},
+        FullscreenPass, Viewport,
+    },
+    resources::Resources,
+};
+use anyhow::Result;
+
+const SHADER_PATH: &str = "shaders/oit_composite";
+const TEXTURE_UNIT_ACCUM: gl::types::GLenum = gl::TEXTURE0;
+const TEXTURE_UNIT_REVEALAGE: gl::types::GLenum = gl::TEXTURE1;
+
+/// Holds the accumulation targets for transparent surfaces and composites them over the opaque
+/// image. The targets are sized to the viewport and reallocated when it changes.
+pub struct TransparencyBuffer {
+    pass: FullscreenPass,
+    fbo: FrameBuffer,
+    accum: Texture,
+    revealage: Texture,
+    depth: Texture,
+    size: (u32, u32),
+}
+
+impl TransparencyBuffer {
+    /// Set up [TransparencyBuffer], compiling shaders and creating the framebuffer.
+    pub fn new(res: &Resources) -> Result<Self> {
+        let value = Self {
+            pass: FullscreenPass::new(res, SHADER_PATH)?,
+            fbo: FrameBuffer::new(),
+            accum: Texture::new(TEXTURE_UNIT_ACCUM),
+            revealage: Texture::new(TEXTURE_UNIT_REVEALAGE),
+            depth: Texture::new(TEXTURE_UNIT_ACCUM),
+            size: (0, 0),
+        };
+        Ok(value)
+    }
+
+    /// Reallocates the render targets if the viewport size has changed.
+    pub fn resize(&mut self, (w, h): (u32, u32)) {
+        if self.size == (w, h) {
+            return;
+        }
+        let dimensions = (w as i32, h as i32);
+        self.accum.load_texture(
+            dimensions,
+            None,
+            gl::RGBA16F as gl::types::GLint,
+            gl::RGBA,
+            gl::FLOAT,
+            false,
+        );
+        self.revealage.load_texture(
+            dimensions,
+            None,
+            gl::R16F as gl::types::GLint,
+            gl::RED,
+            gl::FLOAT,
+            false,
+        );
+        self.depth.load_texture(
+            dimensions,
+            None,
+            gl::DEPTH_COMPONENT32F as gl::types::GLint,
+            gl::DEPTH_COMPONENT,
+            gl::FLOAT,
+            false,
+        );
+        self.depth.unbind();
+
+        self.fbo.bind();
+        self.fbo.bind_texture(gl::COLOR_ATTACHMENT0, &self.accum);
+        self.fbo
+            .bind_texture(gl::COLOR_ATTACHMENT1, &self.revealage);
+        self.fbo.bind_texture(gl::DEPTH_ATTACHMENT, &self.depth);
+        self.fbo
+            .set_draw_buffers(&[gl::COLOR_ATTACHMENT0, gl::COLOR_ATTACHMENT1]);
+        self.fbo.unbind();
+        self.size = (w, h);
+    }
+
+    /// Binds the framebuffer for a depth-only pass of the opaque geometry, so transparent
+    /// surfaces hidden behind it are rejected. `reversed_z` selects the depth convention of the
+    /// camera's projection.
+    ///
+    /// ### Safety
+    ///
+    /// [TransparencyBuffer::resize] must have been called with the current viewport size.
+    pub unsafe fn begin_depth_pass(&self, reversed_z: bool) {
+        self.fbo.bind();
+        gl::Viewport(0, 0, self.size.0 as i32, self.size.1 as i32);
+        gl::DepthMask(gl::TRUE);
+        gl::ClearBufferfv(gl::DEPTH, 0, [render_gl::far_depth(reversed_z)].as_ptr());
+        gl::Disable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+        gl::DepthFunc(render_gl::depth_func(reversed_z));
+        gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+    }
+
+    /// Clears the accumulation targets and sets up blending for the transparent geometry.
+    ///
+    /// ### Safety
+    ///
+    /// Should only be called after [TransparencyBuffer::begin_depth_pass].
+    pub unsafe fn begin_accumulation(&self) {
+        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+        gl::ClearBufferfv(gl::COLOR, 0, [0.0f32, 0.0, 0.0, 0.0].as_ptr());
+        gl::ClearBufferfv(gl::COLOR, 1, [1.0f32, 0.0, 0.0, 0.0].as_ptr());
+        gl::DepthMask(gl::FALSE);
+        gl::Enable(gl::BLEND);
+        gl::BlendFunci(0, gl::ONE, gl::ONE);
+        gl::BlendFunci(1, gl::ZERO, gl::ONE_MINUS_SRC_COLOR);
+    }
+
+    /// Blends the accumulated transparent surfaces over the `target` framebuffer, which is
+    /// left bound.
+    ///
+    /// ### Safety
+    ///
+    /// Should only be called after the transparent geometry has been drawn following
+    /// [TransparencyBuffer::begin_accumulation].
+    pub unsafe fn composite(&self, target: gl::types::GLuint, viewport: &Viewport) {
+        FrameBuffer::bind_id(target);
+        viewport.set_used();
+        self.pass.program().set_used();
+        self.accum.bind();
+        self.revealage.bind();
+        gl::DepthMask(gl::TRUE);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Enable(gl::BLEND);
+        // Alpha is accumulated as coverage, so images with a transparent background come out
+        // with the right opacity.
+        gl::BlendFuncSeparate(
+            gl::SRC_ALPHA,
+            gl::ONE_MINUS_SRC_ALPHA,
+            gl::ONE,
+            gl::ONE_MINUS_SRC_ALPHA,
+        );
+        self.pass.draw();
+        gl::Disable(gl::BLEND);
+        gl::Enable(gl::DEPTH_TEST);
+        self.revealage.unbind();
+        self.accum.unbind();
+        self.pass.program().unset_used();
+    }
+
+    /// Check if the shader has been updated.
+    pub fn check_shader_update(&mut self, path: &std::path::Path, res: &Resources) -> bool {
+        self.pass.check_shader_update(path, res)
+    }
+}
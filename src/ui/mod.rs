@@ -6,4 +6,4 @@ mod ui_builder;
 
 pub use render::UIRenderer;
 pub use sdl2_egui_translation::*;
-pub use ui_builder::{UiActions, UI};
+pub use ui_builder::{ClickTool, UiActions, UI};
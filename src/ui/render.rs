@@ -89,7 +89,7 @@ impl UIRenderer {
     /// left unimplemented.
     pub fn egui_texture_delta(&self, textures_delta: egui::TexturesDelta) {
         // Free texture_ids no longer in use:
-        for _texture_id in textures_delta.free {
+        if !textures_delta.free.is_empty() {
             unimplemented!("Freeing egui textures is not currently implemented.");
         }
 
@@ -1,12 +1,31 @@
 //! Contains the UI construction and interaction code.
 
 use crate::{
-    model::{Attributes, DistanceShadingChannel},
+    animation::{self, AnimatedSetting, AnimationKind, AttributeTrack, SequenceSettings},
+    annotations::{Annotation, AnnotationKind},
+    antialiasing::{AntiAliasing, SceneBuffer},
+    camera::{CameraState, Projection, RotationMode},
+    capture::{OffscreenTarget, ScreenshotOptions},
+    clipping::{ClipAxis, ClipPlane, MAX_CLIP_PLANES},
+    colormap::{self, Colormap, HueDirection},
+    flythrough::{CameraPath, Flythrough, PathOrientation},
+    keybindings::{self, Action, KeyBindings},
+    lights::{Light, LightKind, MAX_LIGHTS},
+    measurement::{self, Measurement},
+    model::{
+        Attributes, CurvatureColoring, DistanceShadingChannel, HatchingMode, LightingModel,
+        ShadowFiltering, SurfacePoint,
+    },
+    render_gl,
     resources::Resources,
+    settings,
+    stereo::{StereoMode, StereoSettings},
     ui::sdl2_egui_translation::egui_to_sdl2_cursor,
+    views::{AnatomicalView, AxisDirection, PatientFrame, SavedView},
 };
 use anyhow::{anyhow, Result};
 use nalgebra as na;
+use std::ops::RangeInclusive;
 
 use super::UIRenderer;
 
@@ -15,6 +34,33 @@ pub struct UI {
     pub renderer: UIRenderer,
     preset: Preset,
     model_files: Vec<String>,
+    show_legend: bool,
+    /// Name entered for the next saved view.
+    view_name: String,
+    patient_frame: PatientFrame,
+    show_key_help: bool,
+    /// Centerline files in the resource directory, and the one chosen for a flythrough.
+    path_files: Vec<String>,
+    path_file: String,
+    /// Viewpoints recorded for a flythrough.
+    flight_keyframes: Vec<CameraState>,
+    measurements: Vec<Measurement>,
+    /// First point of the measurement being made.
+    measurement_start: Option<SurfacePoint>,
+    /// Also measure distances along the surface.
+    measure_geodesic: bool,
+    /// Kind of annotation placed by clicking.
+    annotation_kind: AnnotationKind,
+    /// Hide annotations behind the model, rather than drawing them faded through it.
+    occlude_annotations: bool,
+    screenshot: ScreenshotOptions,
+    /// Largest screenshot width or height the driver can render.
+    max_screenshot_size: u32,
+    sequence: SequenceSettings,
+    /// Whether ffmpeg was found, to encode sequences into videos.
+    ffmpeg_available: bool,
+    /// Settings file in the resource directory to save to or load from.
+    settings_file: String,
 }
 
 /// Describes actions the UI wishes the backend to execute.
@@ -22,8 +68,75 @@ pub struct UiActions {
     pub show_debug: bool,
     pub file_to_load: String,
     pub clear_color: na::Vector3<f32>,
+    pub anti_aliasing: AntiAliasing,
+    /// Samples per pixel for [AntiAliasing::Msaa].
+    pub msaa_samples: u32,
+    pub stereo: StereoSettings,
+    pub projection: Projection,
+    pub rotation_mode: RotationMode,
+    /// Use reversed-Z depth, for more depth precision on large or very small models.
+    pub reversed_z: bool,
+    /// The camera's viewpoint this frame, for saving views.
+    pub camera_state: CameraState,
+    /// Viewpoint the camera should move to.
+    pub camera_request: Option<CameraState>,
+    /// Flight along a path that moves the camera while it exists.
+    pub flythrough: Option<Flythrough>,
+    /// Centerline file to load and fly along.
+    pub centerline_to_load: Option<String>,
+    /// Describe the surface under the mouse cursor.
+    pub surface_readout: bool,
+    /// The surface point under the mouse cursor, when [UiActions::surface_readout] is on.
+    pub hovered: Option<SurfacePoint>,
+    /// What clicking the model does.
+    pub click_tool: ClickTool,
+    /// Surface point clicked while a [ClickTool] is in use.
+    pub surface_click: Option<SurfacePoint>,
+    /// Measurements as CSV, to be saved next to the model.
+    pub measurements_to_export: Option<String>,
+    /// Landmarks and labels on the model, loaded with it.
+    pub annotations: Vec<Annotation>,
+    /// Camera bookmarks, loaded with the model.
+    pub saved_views: Vec<SavedView>,
+    /// Save the annotations and saved views next to the model.
+    pub save_sidecar: bool,
+    /// Point the camera should orbit around.
+    pub pivot_request: Option<na::Point3<f32>>,
+    /// Screenshot to render and save this frame.
+    pub screenshot_request: Option<ScreenshotOptions>,
+    /// Outcome of the last screenshot, shown in the UI.
+    pub screenshot_status: Option<String>,
+    /// Image sequence to start exporting.
+    pub sequence_request: Option<SequenceSettings>,
+    /// Frames rendered and in total, while a sequence is being exported.
+    pub sequence_progress: Option<(u32, u32)>,
+    /// Stop exporting the sequence.
+    pub cancel_sequence: bool,
+    /// Outcome of the last sequence export, shown in the UI.
+    pub sequence_status: Option<String>,
+    /// Settings file to save the current settings and camera to.
+    pub settings_to_save: Option<String>,
+    /// Settings file to load and apply.
+    pub settings_to_load: Option<String>,
+    /// Move the camera to the view saved in a loaded settings file.
+    pub restore_camera: bool,
 }
 
+/// Represents what clicking the model does.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ClickTool {
+    /// Nothing, leaving double-clicks to move the pivot.
+    #[default]
+    None,
+    /// Picks the ends of a measurement.
+    Measure,
+    /// Places an annotation.
+    Annotate,
+}
+
+/// Extensions of the centerline files offered for flythroughs.
+const CENTERLINE_EXTENSIONS: &[&str] = &["txt", "csv"];
+
 /// Describes visualization presets.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Preset {
@@ -54,10 +167,28 @@ impl UI {
         let renderer = UIRenderer::new(res)?;
         let preset = Preset::Plain;
         let model_files = res.list_models();
+        let path_files = res.list_files(CENTERLINE_EXTENSIONS);
         Ok(Self {
             renderer,
             preset,
             model_files,
+            show_legend: true,
+            view_name: String::new(),
+            patient_frame: PatientFrame::default(),
+            show_key_help: false,
+            path_file: path_files.first().cloned().unwrap_or_default(),
+            path_files,
+            flight_keyframes: Vec::new(),
+            measurements: Vec::new(),
+            measurement_start: None,
+            measure_geodesic: false,
+            annotation_kind: AnnotationKind::default(),
+            occlude_annotations: true,
+            screenshot: ScreenshotOptions::default(),
+            max_screenshot_size: OffscreenTarget::max_size(),
+            sequence: SequenceSettings::default(),
+            ffmpeg_available: animation::ffmpeg_available(),
+            settings_file: settings::DEFAULT_FILE.to_owned(),
         })
     }
 
@@ -67,6 +198,7 @@ impl UI {
         ctx: &egui::Context,
         model: &mut Option<crate::Model>,
         ui_actions: &mut UiActions,
+        key_bindings: &KeyBindings,
     ) {
         // Disable window shadow.
         let shadow = egui::epaint::Shadow {
@@ -188,11 +320,292 @@ impl UI {
                                 ui.checkbox(&mut ui_actions.show_debug, "");
                                 ui.end_row();
 
+                                ui.label("Projection");
+                                egui::ComboBox::from_id_source("projection")
+                                    .selected_text(ui_actions.projection.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for projection in Projection::ALL {
+                                            ui.selectable_value(
+                                                &mut ui_actions.projection,
+                                                projection,
+                                                projection.to_string(),
+                                            );
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text(
+                                        "Orthographic projection keeps sizes independent of depth.",
+                                    );
+                                ui.end_row();
+
+                                ui.label("Rotation");
+                                egui::ComboBox::from_id_source("rotation_mode")
+                                    .selected_text(ui_actions.rotation_mode.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for mode in RotationMode::ALL {
+                                            ui.selectable_value(
+                                                &mut ui_actions.rotation_mode,
+                                                mode,
+                                                mode.to_string(),
+                                            );
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text(
+                                        "Arcball rotates freely over the poles and keeps spinning \
+                                        briefly when released.",
+                                    );
+                                ui.end_row();
+
+                                ui.label("Reversed-Z depth");
+                                ui.add_enabled(
+                                    render_gl::reversed_z_supported(),
+                                    egui::Checkbox::new(&mut ui_actions.reversed_z, ""),
+                                )
+                                .on_hover_text(
+                                    "Spreads depth precision evenly over distance. Needs OpenGL \
+                                    4.5 or ARB_clip_control.",
+                                )
+                                .on_disabled_hover_text("Not supported by the graphics driver.");
+                                ui.end_row();
+
+                                ui.label("Anti-aliasing");
+                                egui::ComboBox::from_id_source("anti_aliasing")
+                                    .selected_text(ui_actions.anti_aliasing.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for mode in AntiAliasing::ALL {
+                                            ui.selectable_value(
+                                                &mut ui_actions.anti_aliasing,
+                                                mode,
+                                                mode.to_string(),
+                                            );
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text(
+                                        "Falls back to FXAA if multisampling is unavailable.",
+                                    );
+                                ui.end_row();
+
+                                ui.label("MSAA samples");
+                                ui.add_enabled_ui(
+                                    ui_actions.anti_aliasing == AntiAliasing::Msaa,
+                                    |ui| {
+                                        egui::ComboBox::from_id_source("msaa_samples")
+                                            .selected_text(format!("{}×", ui_actions.msaa_samples))
+                                            .show_ui(ui, |ui| {
+                                                let max_samples = SceneBuffer::max_samples();
+                                                for samples in [2, 4, 8, 16] {
+                                                    if samples <= max_samples {
+                                                        ui.selectable_value(
+                                                            &mut ui_actions.msaa_samples,
+                                                            samples,
+                                                            format!("{}×", samples),
+                                                        );
+                                                    }
+                                                }
+                                            });
+                                    },
+                                );
+                                ui.end_row();
+
+                                ui.label("Freeze time");
+                                ui.checkbox(&mut attr.freeze_time, "")
+                                    .on_hover_text("Pause all animation.");
+                                ui.end_row();
+
                                 ui.label("Use hatching instead of shadows");
                                 ui.checkbox(&mut attr.replace_shadows_with_hatching, "");
                                 ui.end_row();
                             });
 
+                        ui.collapsing("Colored depth settings", |ui| {
+                            egui::Grid::new("colored_depth_settings_grid")
+                                .striped(true)
+                                .spacing([40.0, 4.0])
+                                .show(ui, |ui| {
+                                    ui.set_enabled(
+                                        attr.distance_shading_channel
+                                            == DistanceShadingChannel::Hue,
+                                    );
+                                    ui.label("Colormap");
+                                    egui::ComboBox::from_id_source("distance_shading_colormap")
+                                        .selected_text(attr.distance_shading_colormap.to_string())
+                                        .show_ui(ui, |ui| {
+                                            for colormap in Colormap::ALL {
+                                                ui.selectable_value(
+                                                    &mut attr.distance_shading_colormap,
+                                                    colormap,
+                                                    colormap.to_string(),
+                                                );
+                                            }
+                                        });
+                                    ui.end_row();
+
+                                    let hsv = attr.distance_shading_colormap == Colormap::Hsv;
+                                    ui.label("Near hue");
+                                    ui.add_enabled(
+                                        hsv,
+                                        egui::Slider::new(&mut attr.hue_near, 0.0..=360.0)
+                                            .suffix("°"),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Far hue");
+                                    ui.add_enabled(
+                                        hsv,
+                                        egui::Slider::new(&mut attr.hue_far, 0.0..=360.0)
+                                            .suffix("°"),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Hue direction");
+                                    ui.add_enabled_ui(hsv, |ui| {
+                                        egui::ComboBox::from_id_source("hue_direction")
+                                            .selected_text(attr.hue_direction.to_string())
+                                            .show_ui(ui, |ui| {
+                                                for direction in [
+                                                    HueDirection::Increasing,
+                                                    HueDirection::Decreasing,
+                                                ] {
+                                                    ui.selectable_value(
+                                                        &mut attr.hue_direction,
+                                                        direction,
+                                                        direction.to_string(),
+                                                    );
+                                                }
+                                            });
+                                    });
+                                    ui.end_row();
+
+                                    ui.label("Saturation");
+                                    ui.add_enabled(
+                                        hsv,
+                                        egui::Slider::new(&mut attr.hue_saturation, 0.0..=1.0),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Show legend");
+                                    ui.checkbox(&mut self.show_legend, "");
+                                    ui.end_row();
+                                })
+                        });
+
+                        ui.collapsing("Lighting settings", |ui| {
+                            egui::Grid::new("lighting_settings_grid")
+                                .striped(true)
+                                .spacing([40.0, 4.0])
+                                .show(ui, |ui| {
+                                    ui.label("Lighting model");
+                                    egui::ComboBox::from_id_source("lighting_model")
+                                        .selected_text(attr.lighting_model.to_string())
+                                        .show_ui(ui, |ui| {
+                                            for lighting_model in LightingModel::ALL {
+                                                ui.selectable_value(
+                                                    &mut attr.lighting_model,
+                                                    lighting_model,
+                                                    lighting_model.to_string(),
+                                                );
+                                            }
+                                        });
+                                    ui.end_row();
+
+                                    ui.label("Ambient");
+                                    ui.add(egui::Slider::new(
+                                        &mut attr.ambient_reflection,
+                                        0.0..=1.0,
+                                    ));
+                                    ui.end_row();
+
+                                    ui.label("Diffuse");
+                                    ui.add(egui::Slider::new(
+                                        &mut attr.diffuse_reflection,
+                                        0.0..=1.0,
+                                    ));
+                                    ui.end_row();
+
+                                    let specular = attr.lighting_model != LightingModel::Lambert;
+                                    ui.label("Specular");
+                                    ui.add_enabled(
+                                        specular,
+                                        egui::Slider::new(&mut attr.specular_reflection, 0.0..=1.0),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Shininess");
+                                    ui.add_enabled(
+                                        specular,
+                                        egui::Slider::new(&mut attr.shininess, 1.0..=256.0)
+                                            .logarithmic(true),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Roughness");
+                                    ui.add_enabled(
+                                        attr.lighting_model == LightingModel::OrenNayar,
+                                        egui::Slider::new(&mut attr.roughness, 0.0..=1.0),
+                                    );
+                                    ui.end_row();
+                                })
+                        });
+
+                        ui.collapsing("Lights", |ui| build_light_editor(ui, &mut attr));
+
+                        ui.collapsing("Curvature settings", |ui| {
+                            egui::Grid::new("curvature_settings_grid")
+                                .striped(true)
+                                .spacing([40.0, 4.0])
+                                .show(ui, |ui| {
+                                    ui.label("Color by");
+                                    egui::ComboBox::from_id_source("curvature_coloring")
+                                        .selected_text(attr.curvature_coloring.to_string())
+                                        .show_ui(ui, |ui| {
+                                            for coloring in CurvatureColoring::ALL {
+                                                ui.selectable_value(
+                                                    &mut attr.curvature_coloring,
+                                                    coloring,
+                                                    coloring.to_string(),
+                                                );
+                                            }
+                                        });
+                                    ui.end_row();
+
+                                    ui.label("Curvature range");
+                                    ui.add_enabled(
+                                        attr.curvature_coloring != CurvatureColoring::None
+                                            && attr.curvature_coloring
+                                                != CurvatureColoring::ShapeIndex,
+                                        egui::Slider::new(&mut attr.curvature_range, 1.0..=1000.0)
+                                            .logarithmic(true),
+                                    )
+                                    .on_hover_text(
+                                        "Curvature at the ends of the colour scale, \
+                                         relative to the model size.",
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Ridge and valley lines");
+                                    ui.checkbox(&mut attr.ridge_valley_lines, "");
+                                    ui.end_row();
+
+                                    ui.label("Ridge threshold");
+                                    ui.add_enabled(
+                                        attr.ridge_valley_lines,
+                                        egui::Slider::new(&mut attr.ridge_threshold, 1.0..=1000.0)
+                                            .logarithmic(true),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Line width");
+                                    ui.add_enabled(
+                                        attr.ridge_valley_lines,
+                                        egui::Slider::new(&mut attr.ridge_line_width, 0.5..=5.0)
+                                            .suffix(" px"),
+                                    );
+                                    ui.end_row();
+                                })
+                        });
+
                         ui.collapsing("Hatching settings", |ui| {
                             egui::Grid::new("hatching_settings_grid")
                                 .striped(true)
@@ -207,8 +620,75 @@ impl UI {
                                     ui.add(egui::Slider::new(&mut attr.hatching_steps, 1..=250));
                                     ui.end_row();
 
-                                    ui.label("Hatching frequency");
-                                    ui.add(egui::Slider::new(&mut attr.hatching_frequency, 1..=15));
+                                    ui.label("Hatching mode");
+                                    egui::ComboBox::from_id_source("hatching_mode")
+                                        .selected_text(attr.hatching_mode.to_string())
+                                        .show_ui(ui, |ui| {
+                                            for mode in HatchingMode::ALL {
+                                                ui.selectable_value(
+                                                    &mut attr.hatching_mode,
+                                                    mode,
+                                                    mode.to_string(),
+                                                );
+                                            }
+                                        });
+                                    ui.end_row();
+
+                                    ui.label("Hatching spacing");
+                                    if attr.hatching_mode == HatchingMode::ScreenSpace {
+                                        ui.add(
+                                            egui::Slider::new(
+                                                &mut attr.hatching_spacing,
+                                                1.0..=15.0,
+                                            )
+                                            .suffix(" px"),
+                                        );
+                                    } else {
+                                        ui.add(
+                                            scaled_slider(
+                                                &mut attr.hatching_object_spacing,
+                                                0.001..=0.05,
+                                                100.0,
+                                            )
+                                            .logarithmic(true)
+                                            .fixed_decimals(1)
+                                            .suffix("% of model"),
+                                        );
+                                    }
+                                    ui.end_row();
+
+                                    ui.label("Cross-hatching");
+                                    ui.checkbox(&mut attr.cross_hatching, "");
+                                    ui.end_row();
+
+                                    ui.label("Cross-hatching tone");
+                                    ui.add_enabled(
+                                        attr.cross_hatching,
+                                        egui::Slider::new(
+                                            &mut attr.cross_hatching_threshold,
+                                            0.0..=1.0,
+                                        ),
+                                    )
+                                    .on_hover_text(
+                                        "Darker tones than this get a second stroke direction.",
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Hatching map resolution");
+                                    egui::ComboBox::from_id_source("hatch_map_scale")
+                                        .selected_text(format!(
+                                            "{}× viewport",
+                                            attr.hatch_map_scale
+                                        ))
+                                        .show_ui(ui, |ui| {
+                                            for scale in [0.5, 1.0, 2.0] {
+                                                ui.selectable_value(
+                                                    &mut attr.hatch_map_scale,
+                                                    scale,
+                                                    format!("{}× viewport", scale),
+                                                );
+                                            }
+                                        });
                                     ui.end_row();
 
                                     ui.label("Hatching intensity");
@@ -233,46 +713,179 @@ impl UI {
                                     ));
                                     ui.end_row();
 
-                                    ui.label("Light follows camera");
-                                    ui.checkbox(&mut attr.shadows_follow, "");
-                                    ui.end_row();
-
-                                    ui.label("Light X");
-                                    ui.scope(|ui| {
-                                        ui.set_enabled(!attr.shadows_follow);
-                                        ui.add(egui::Slider::new(
-                                            &mut attr.light_position[0],
-                                            -1.0..=1.0,
-                                        ))
-                                        .on_disabled_hover_text("Disabled while following camera.");
-                                    });
-                                    ui.end_row();
-                                    ui.label("Light Y");
-                                    ui.scope(|ui| {
-                                        ui.set_enabled(!attr.shadows_follow);
-                                        ui.add(egui::Slider::new(
-                                            &mut attr.light_position[1],
-                                            -1.0..=1.0,
-                                        ))
-                                        .on_disabled_hover_text("Disabled while following camera.");
-                                    });
-                                    ui.end_row();
-                                    ui.label("Light Z");
-                                    ui.scope(|ui| {
-                                        ui.set_enabled(!attr.shadows_follow);
-                                        ui.add(egui::Slider::new(
-                                            &mut attr.light_position[2],
-                                            -1.0..=1.0,
-                                        ))
-                                        .on_disabled_hover_text("Disabled while following camera.");
-                                    });
-                                    ui.end_row();
                                     ui.label("Light orbit distance");
                                     ui.add(egui::Slider::new(
                                         &mut attr.shadows_orbit_radius,
                                         0.0..=100.0,
                                     ));
                                     ui.end_row();
+
+                                    ui.label("Animate light orbit");
+                                    ui.checkbox(&mut attr.light_orbit_animate, "");
+                                    ui.end_row();
+
+                                    ui.label("Orbit period");
+                                    ui.add_enabled(
+                                        attr.light_orbit_animate,
+                                        egui::Slider::new(&mut attr.light_orbit_period, 0.5..=30.0)
+                                            .logarithmic(true)
+                                            .suffix(" s"),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Orbit angle");
+                                    ui.add(
+                                        egui::Slider::new(&mut attr.light_orbit_angle, 0.0..=360.0)
+                                            .suffix("°"),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Shadow map resolution");
+                                    egui::ComboBox::from_id_source("shadow_map_size")
+                                        .selected_text(format!("{0}×{0}", attr.shadow_map_size))
+                                        .show_ui(ui, |ui| {
+                                            for size in [512, 1024, 2048, 4096, 8192] {
+                                                ui.selectable_value(
+                                                    &mut attr.shadow_map_size,
+                                                    size,
+                                                    format!("{0}×{0}", size),
+                                                );
+                                            }
+                                        });
+                                    ui.end_row();
+
+                                    ui.label("Shadow filtering");
+                                    egui::ComboBox::from_id_source("shadow_filtering")
+                                        .selected_text(attr.shadow_filtering.to_string())
+                                        .show_ui(ui, |ui| {
+                                            for filtering in
+                                                [ShadowFiltering::Pcf, ShadowFiltering::Pcss]
+                                            {
+                                                ui.selectable_value(
+                                                    &mut attr.shadow_filtering,
+                                                    filtering,
+                                                    filtering.to_string(),
+                                                );
+                                            }
+                                        });
+                                    ui.end_row();
+
+                                    ui.label("Light size");
+                                    ui.add_enabled(
+                                        attr.shadow_filtering == ShadowFiltering::Pcss,
+                                        egui::Slider::new(&mut attr.shadow_light_size, 0.0..=0.2),
+                                    )
+                                    .on_disabled_hover_text("Only used by soft shadows.");
+                                    ui.end_row();
+                                })
+                        });
+
+                        ui.collapsing("Clipping planes", |ui| {
+                            build_clip_plane_editor(ui, &mut attr)
+                        });
+
+                        ui.collapsing("Transparency settings", |ui| {
+                            egui::Grid::new("transparency_settings_grid")
+                                .striped(true)
+                                .spacing([40.0, 4.0])
+                                .show(ui, |ui| {
+                                    let meshes = model.get_meshes();
+                                    for (i, opacity) in attr.mesh_opacity.iter_mut().enumerate() {
+                                        match meshes.get(i) {
+                                            Some(mesh) if !mesh.name.is_empty() => {
+                                                ui.label(format!("{} opacity", mesh.name))
+                                            }
+                                            _ => ui.label(format!("Object {} opacity", i + 1)),
+                                        };
+                                        ui.add(egui::Slider::new(opacity, 0.0..=1.0))
+                                            .on_hover_text(
+                                            "Transparent objects do not cast shadows or hatching.",
+                                        );
+                                        ui.end_row();
+                                    }
+                                })
+                        });
+
+                        ui.collapsing("Views", |ui| self.build_view_editor(ui, ui_actions));
+
+                        ui.collapsing("Flythrough", |ui| {
+                            self.build_flythrough_editor(ui, ui_actions)
+                        });
+
+                        ui.collapsing("Measurements", |ui| {
+                            self.build_measurement_editor(ui, model, ui_actions)
+                        });
+
+                        ui.collapsing("Annotations", |ui| {
+                            self.build_annotation_editor(ui, ui_actions)
+                        });
+
+                        ui.collapsing("Settings file", |ui| {
+                            self.build_settings_file_editor(ui, ui_actions)
+                        });
+
+                        ui.collapsing("Screenshot", |ui| {
+                            self.build_screenshot_editor(ctx, ui, ui_actions)
+                        });
+
+                        ui.collapsing("Animation export", |ui| {
+                            self.build_sequence_editor(ui, ui_actions, &attr)
+                        });
+
+                        ui.collapsing("Stereo settings", |ui| {
+                            egui::Grid::new("stereo_settings_grid")
+                                .striped(true)
+                                .spacing([40.0, 4.0])
+                                .show(ui, |ui| {
+                                    let stereo = &mut ui_actions.stereo;
+                                    ui.label("Stereo output");
+                                    egui::ComboBox::from_id_source("stereo_mode")
+                                        .selected_text(stereo.mode.to_string())
+                                        .show_ui(ui, |ui| {
+                                            for mode in StereoMode::ALL {
+                                                ui.selectable_value(
+                                                    &mut stereo.mode,
+                                                    mode,
+                                                    mode.to_string(),
+                                                );
+                                            }
+                                        });
+                                    ui.end_row();
+
+                                    let enabled = stereo.mode != StereoMode::Off;
+                                    ui.label("Eye separation");
+                                    ui.add_enabled(
+                                        enabled,
+                                        scaled_slider(&mut stereo.interocular, 0.0..=0.1, 100.0)
+                                            .fixed_decimals(1)
+                                            .suffix("%"),
+                                    )
+                                    .on_hover_text(
+                                        "Interocular distance, relative to the convergence \
+                                        distance. Larger values give stronger depth.",
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Convergence");
+                                    ui.add_enabled(
+                                        enabled,
+                                        scaled_slider(&mut stereo.convergence, 0.25..=2.0, 100.0)
+                                            .fixed_decimals(0)
+                                            .suffix("%"),
+                                    )
+                                    .on_hover_text(
+                                        "Distance to the screen plane, relative to the camera \
+                                        distance. Geometry nearer than this appears in front \
+                                        of the screen.",
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Swap eyes");
+                                    ui.add_enabled(
+                                        enabled,
+                                        egui::Checkbox::new(&mut stereo.swap_eyes, ""),
+                                    );
+                                    ui.end_row();
                                 })
                         });
                     });
@@ -283,7 +896,705 @@ impl UI {
                     });
                     model.set_attributes(attr);
                 }
+
+                ui.checkbox(&mut self.show_key_help, "Show key bindings");
+                ui.checkbox(
+                    &mut ui_actions.surface_readout,
+                    "Show surface info on hover",
+                );
+            });
+
+        if let Some(model) = model {
+            let attr = model.get_attributes();
+            if self.show_legend && attr.distance_shading_channel == DistanceShadingChannel::Hue {
+                build_legend(ctx, attr);
+            }
+        }
+        if self.show_key_help {
+            build_key_help(ctx, key_bindings, &mut self.show_key_help);
+        }
+        if let Some(model) = model {
+            if let Some(point) = ui_actions.surface_click.take() {
+                match ui_actions.click_tool {
+                    ClickTool::Measure => self.add_measurement_point(model, point),
+                    ClickTool::Annotate => {
+                        let kind = self.annotation_kind;
+                        let count = ui_actions
+                            .annotations
+                            .iter()
+                            .filter(|a| a.kind == kind)
+                            .count();
+                        ui_actions.annotations.push(Annotation {
+                            kind,
+                            text: format!("{} {}", kind, count + 1),
+                            position: point.position,
+                        });
+                    }
+                    ClickTool::None => {}
+                }
+            }
+            build_annotation_overlay(
+                ctx,
+                model,
+                &ui_actions.annotations,
+                self.occlude_annotations,
+            );
+            build_measurement_overlay(
+                ctx,
+                model.get_attributes(),
+                &self.measurements,
+                self.measurement_start.as_ref(),
+            );
+        }
+        if let (Some(model), Some(point)) = (model, ui_actions.hovered) {
+            if ui_actions.surface_readout && !ctx.is_pointer_over_area() {
+                build_surface_readout(ctx, model, &point);
+            }
+        }
+    }
+
+    /// Builds the controls for recording keyframes, loading centerlines and playing a flight.
+    fn build_flythrough_editor(&mut self, ui: &mut egui::Ui, ui_actions: &mut UiActions) {
+        egui::Grid::new("flythrough_source_grid")
+            .striped(true)
+            .spacing([40.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("Centerline");
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("centerline_file")
+                        .selected_text(if self.path_file.is_empty() {
+                            "No centerline files"
+                        } else {
+                            &self.path_file
+                        })
+                        .show_ui(ui, |ui| {
+                            for file in &self.path_files {
+                                ui.selectable_value(&mut self.path_file, file.clone(), file);
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Text or CSV files in the assets directory, with one point per line, \
+                            in the obj file's coordinates.",
+                        );
+                    if ui
+                        .add_enabled(!self.path_file.is_empty(), egui::Button::new("Fly"))
+                        .clicked()
+                    {
+                        ui_actions.centerline_to_load = Some(self.path_file.clone());
+                    }
+                });
+                ui.end_row();
+
+                ui.label(format!("{} keyframes", self.flight_keyframes.len()));
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Record")
+                        .on_hover_text("Add the current view to the path.")
+                        .clicked()
+                    {
+                        self.flight_keyframes.push(ui_actions.camera_state);
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.flight_keyframes.clear();
+                    }
+                    let path = CameraPath::from_keyframes(&self.flight_keyframes);
+                    if ui
+                        .add_enabled(path.is_ok(), egui::Button::new("Fly"))
+                        .clicked()
+                    {
+                        ui_actions.flythrough = path.ok().map(Flythrough::new);
+                    }
+                });
+                ui.end_row();
+            });
+
+        let Some(flight) = ui_actions.flythrough.as_mut() else {
+            return;
+        };
+        let length = flight.path.length();
+        let before = (flight.distance, flight.look_ahead, flight.orientation);
+        egui::Grid::new("flythrough_playback_grid")
+            .striped(true)
+            .spacing([40.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("Playback");
+                ui.horizontal(|ui| {
+                    let label = if flight.playing { "Pause" } else { "Play" };
+                    if ui.button(label).clicked() {
+                        if !flight.playing && flight.distance >= length {
+                            flight.distance = 0.0;
+                        }
+                        flight.playing = !flight.playing;
+                    }
+                    ui.checkbox(&mut flight.looping, "Loop");
+                });
+                ui.end_row();
+
+                ui.label("Position");
+                ui.add(egui::Slider::new(&mut flight.distance, 0.0..=length));
+                ui.end_row();
+
+                ui.label("Speed");
+                ui.add(
+                    egui::Slider::new(&mut flight.speed, length / 1000.0..=length / 2.0)
+                        .logarithmic(true)
+                        .suffix("/s"),
+                );
+                ui.end_row();
+
+                ui.label("Orientation");
+                ui.add_enabled_ui(flight.path.has_orientations(), |ui| {
+                    egui::ComboBox::from_id_source("flight_orientation")
+                        .selected_text(flight.orientation.to_string())
+                        .show_ui(ui, |ui| {
+                            for orientation in PathOrientation::ALL {
+                                ui.selectable_value(
+                                    &mut flight.orientation,
+                                    orientation,
+                                    orientation.to_string(),
+                                );
+                            }
+                        });
+                });
+                ui.end_row();
+
+                ui.label("Look-ahead");
+                ui.add_enabled(
+                    flight.orientation == PathOrientation::LookAhead,
+                    egui::Slider::new(&mut flight.look_ahead, 0.0..=length / 4.0),
+                )
+                .on_hover_text("How far along the path the camera looks.");
+                ui.end_row();
             });
+        if (flight.distance, flight.look_ahead, flight.orientation) != before {
+            flight.refresh();
+        }
+        if ui.button("Stop flythrough").clicked() {
+            ui_actions.flythrough = None;
+        }
+    }
+
+    /// Forgets the measurements, which only apply to the model they were made on.
+    pub fn clear_measurements(&mut self) {
+        self.measurements.clear();
+        self.measurement_start = None;
+    }
+
+    /// Starts a measurement at the clicked point, or finishes the one already started.
+    fn add_measurement_point(&mut self, model: &crate::Model, point: SurfacePoint) {
+        let Some(start) = self.measurement_start.take() else {
+            self.measurement_start = Some(point);
+            return;
+        };
+        let geodesic = if self.measure_geodesic {
+            model.geodesic_distance(&start, &point)
+        } else {
+            None
+        };
+        self.measurements.push(Measurement {
+            name: format!("Measurement {}", self.measurements.len() + 1),
+            start: start.position,
+            end: point.position,
+            geodesic,
+        });
+    }
+
+    /// Builds the list of measurements and the controls for making and exporting them.
+    fn build_measurement_editor(
+        &mut self,
+        ui: &mut egui::Ui,
+        model: &crate::Model,
+        ui_actions: &mut UiActions,
+    ) {
+        let mut measuring = ui_actions.click_tool == ClickTool::Measure;
+        if ui
+            .checkbox(&mut measuring, "Measure by clicking")
+            .on_hover_text("Click two points on the model to measure the distance between them.")
+            .changed()
+        {
+            ui_actions.click_tool = if measuring {
+                ClickTool::Measure
+            } else {
+                ClickTool::None
+            };
+        }
+        if ui_actions.click_tool != ClickTool::Measure {
+            self.measurement_start = None;
+        }
+        ui.checkbox(&mut self.measure_geodesic, "Distance along the surface")
+            .on_hover_text(
+                "Also measure the shortest path along the mesh edges, which is slightly longer \
+                than the true distance along the surface.",
+            );
+        if self.measurement_start.is_some() {
+            ui.label("Click the second point.");
+        }
+
+        let mut removed = None;
+        egui::Grid::new("measurements_grid")
+            .striped(true)
+            .spacing([20.0, 4.0])
+            .show(ui, |ui| {
+                for (i, measurement) in self.measurements.iter_mut().enumerate() {
+                    ui.add(egui::TextEdit::singleline(&mut measurement.name).desired_width(120.0));
+                    ui.label(format!("{:.2}", measurement.distance()));
+                    match measurement.geodesic {
+                        Some(geodesic) => ui.label(format!("{:.2} along surface", geodesic)),
+                        None => ui.label(""),
+                    };
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = removed {
+            self.measurements.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            let any = !self.measurements.is_empty();
+            if ui
+                .add_enabled(any, egui::Button::new("Export CSV"))
+                .on_hover_text("Saves the measurements next to the model file.")
+                .clicked()
+            {
+                ui_actions.measurements_to_export =
+                    Some(measurement::to_csv(&self.measurements, model.get_center()));
+            }
+            if ui.add_enabled(any, egui::Button::new("Clear")).clicked() {
+                self.measurements.clear();
+            }
+        });
+    }
+
+    /// Builds the controls for saving the settings to a file and loading them again.
+    fn build_settings_file_editor(&mut self, ui: &mut egui::Ui, ui_actions: &mut UiActions) {
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.text_edit_singleline(&mut self.settings_file)
+                .on_hover_text(
+                    "In the assets directory. Settings can also be loaded from a screenshot. \
+                    Batch rendering can use the same file.",
+                );
+        });
+        ui.checkbox(&mut ui_actions.restore_camera, "Restore the saved camera");
+        ui.horizontal(|ui| {
+            let named = !self.settings_file.trim().is_empty();
+            if ui
+                .add_enabled(named, egui::Button::new("Save"))
+                .on_hover_text("Saves the render settings and the current camera.")
+                .clicked()
+            {
+                ui_actions.settings_to_save = Some(self.settings_file.trim().to_owned());
+            }
+            if ui.add_enabled(named, egui::Button::new("Load")).clicked() {
+                ui_actions.settings_to_load = Some(self.settings_file.trim().to_owned());
+            }
+        });
+    }
+
+    /// Builds the controls for rendering and saving a screenshot.
+    fn build_screenshot_editor(
+        &mut self,
+        ctx: &egui::Context,
+        ui: &mut egui::Ui,
+        ui_actions: &mut UiActions,
+    ) {
+        let options = &mut self.screenshot;
+        egui::Grid::new("screenshot_grid")
+            .striped(true)
+            .spacing([40.0, 4.0])
+            .show(ui, |ui| {
+                let max = self.max_screenshot_size;
+                ui.label("Size");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut options.size.0).clamp_range(1..=max));
+                    ui.label("×");
+                    ui.add_enabled(
+                        !options.include_ui,
+                        egui::DragValue::new(&mut options.size.1).clamp_range(1..=max),
+                    )
+                    .on_disabled_hover_text("Follows the window's shape, to fit the UI.");
+                });
+                ui.end_row();
+
+                ui.label("Transparent background");
+                ui.checkbox(&mut options.transparent, "");
+                ui.end_row();
+
+                ui.label("Include UI");
+                ui.checkbox(&mut options.include_ui, "")
+                    .on_hover_text("Draws the UI over the model, scaled up with the image.");
+                ui.end_row();
+
+                ui.label("Folder");
+                ui.text_edit_singleline(&mut options.folder);
+                ui.end_row();
+            });
+        if options.include_ui {
+            // The UI covers the whole image like it covers the window, so keep the window's shape.
+            let screen = ctx.input().screen_rect();
+            let aspect = screen.height() / screen.width().max(1.0);
+            options.size.1 = ((options.size.0 as f32 * aspect).round() as u32)
+                .clamp(1, self.max_screenshot_size);
+        }
+
+        if ui
+            .button("Save screenshot")
+            .on_hover_text(
+                "Renders the current view at the chosen size and saves it as PNG, with the \
+                camera and settings stored in the file.",
+            )
+            .clicked()
+        {
+            ui_actions.screenshot_request = Some(options.clone());
+        }
+        if let Some(status) = &ui_actions.screenshot_status {
+            ui.label(status);
+        }
+    }
+
+    /// Builds the controls for exporting a turntable or flythrough as an image sequence.
+    fn build_sequence_editor(
+        &mut self,
+        ui: &mut egui::Ui,
+        ui_actions: &mut UiActions,
+        attr: &Attributes,
+    ) {
+        if let Some((frame, frames)) = ui_actions.sequence_progress {
+            ui.add(
+                egui::ProgressBar::new(frame as f32 / frames.max(1) as f32)
+                    .text(format!("Frame {} of {}", frame, frames)),
+            );
+            if ui.button("Cancel").clicked() {
+                ui_actions.cancel_sequence = true;
+            }
+            return;
+        }
+
+        let settings = &mut self.sequence;
+        let max = self.max_screenshot_size;
+        egui::Grid::new("sequence_grid")
+            .striped(true)
+            .spacing([40.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("Animation");
+                egui::ComboBox::from_id_source("animation_kind")
+                    .selected_text(settings.kind.to_string())
+                    .show_ui(ui, |ui| {
+                        for kind in AnimationKind::ALL {
+                            ui.selectable_value(&mut settings.kind, kind, kind.to_string());
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "A turntable turns the camera once around the model. A flythrough \
+                        follows the path set up in the Flythrough section from start to end.",
+                    );
+                ui.end_row();
+
+                ui.label("Frames");
+                ui.add(egui::DragValue::new(&mut settings.frames).clamp_range(1..=100_000));
+                ui.end_row();
+
+                ui.label("Frame rate");
+                ui.add(
+                    egui::DragValue::new(&mut settings.fps)
+                        .clamp_range(1..=240)
+                        .suffix(" fps"),
+                )
+                .on_hover_text("Animated settings advance by one frame's time between frames.");
+                ui.end_row();
+
+                ui.label("Size");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut settings.size.0).clamp_range(1..=max));
+                    ui.label("×");
+                    ui.add(egui::DragValue::new(&mut settings.size.1).clamp_range(1..=max));
+                });
+                ui.end_row();
+
+                ui.label("Transparent background");
+                ui.checkbox(&mut settings.transparent, "");
+                ui.end_row();
+
+                ui.label("Folder");
+                ui.text_edit_singleline(&mut settings.folder);
+                ui.end_row();
+
+                ui.label("Encode video");
+                ui.add_enabled(
+                    self.ffmpeg_available,
+                    egui::Checkbox::new(&mut settings.encode_video, ""),
+                )
+                .on_hover_text("Also makes an mp4 video of the frames with ffmpeg.")
+                .on_disabled_hover_text("ffmpeg was not found.");
+                ui.end_row();
+            });
+
+        ui.separator();
+        ui.label("Keyframes").on_hover_text(
+            "Settings that change over the sequence, interpolated between keyframes.",
+        );
+        let frames = settings.frames;
+        let mut removed_track = None;
+        for (t, track) in settings.tracks.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source(("animated_setting", t))
+                    .selected_text(track.setting.to_string())
+                    .show_ui(ui, |ui| {
+                        for setting in AnimatedSetting::all(attr) {
+                            ui.selectable_value(&mut track.setting, setting, setting.to_string());
+                        }
+                    });
+                if ui.small_button("Remove").clicked() {
+                    removed_track = Some(t);
+                }
+            });
+            let speed = match track.setting {
+                AnimatedSetting::LightAngle => 1.0,
+                _ => 0.01,
+            };
+            let mut removed_key = None;
+            for (k, (frame, value)) in track.keys.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label("Frame");
+                    ui.add(egui::DragValue::new(frame).clamp_range(0..=frames - 1));
+                    ui.label("Value");
+                    ui.add(egui::DragValue::new(value).speed(speed));
+                    if ui.small_button("Remove").clicked() {
+                        removed_key = Some(k);
+                    }
+                });
+            }
+            if let Some(k) = removed_key {
+                track.keys.remove(k);
+            }
+            if ui
+                .button("Add keyframe")
+                .on_hover_text(
+                    "Adds a keyframe with the current value, at the first frame or else the last.",
+                )
+                .clicked()
+            {
+                let frame = if track.keys.is_empty() { 0 } else { frames - 1 };
+                let value = track.setting.get(attr).unwrap_or_default();
+                track.keys.push((frame, value));
+                track.sort();
+            }
+        }
+        if let Some(t) = removed_track {
+            settings.tracks.remove(t);
+        }
+        if ui.button("Animate a setting").clicked() {
+            settings.tracks.push(AttributeTrack::default());
+        }
+        ui.separator();
+
+        let ready = settings.kind != AnimationKind::Flythrough || ui_actions.flythrough.is_some();
+        if ui
+            .add_enabled(ready, egui::Button::new("Export sequence"))
+            .on_disabled_hover_text("Load a centerline or record keyframes to fly along first.")
+            .on_hover_text("Renders the frames one by one as numbered PNG files.")
+            .clicked()
+        {
+            ui_actions.sequence_request = Some(settings.clone());
+        }
+        if let Some(status) = &ui_actions.sequence_status {
+            ui.label(status);
+        }
+    }
+
+    /// Builds the list of annotations and the controls for placing and saving them.
+    fn build_annotation_editor(&mut self, ui: &mut egui::Ui, ui_actions: &mut UiActions) {
+        ui.horizontal(|ui| {
+            let mut placing = ui_actions.click_tool == ClickTool::Annotate;
+            if ui
+                .checkbox(&mut placing, "Place by clicking")
+                .on_hover_text("Click the model to add an annotation there.")
+                .changed()
+            {
+                ui_actions.click_tool = if placing {
+                    ClickTool::Annotate
+                } else {
+                    ClickTool::None
+                };
+            }
+            egui::ComboBox::from_id_source("annotation_kind")
+                .selected_text(self.annotation_kind.to_string())
+                .show_ui(ui, |ui| {
+                    for kind in AnnotationKind::ALL {
+                        ui.selectable_value(&mut self.annotation_kind, kind, kind.to_string());
+                    }
+                });
+        });
+        ui.checkbox(&mut self.occlude_annotations, "Hide behind the model")
+            .on_hover_text("Otherwise, annotations behind the model are drawn faded through it.");
+
+        let mut removed = None;
+        egui::Grid::new("annotations_grid")
+            .striped(true)
+            .spacing([20.0, 4.0])
+            .show(ui, |ui| {
+                for (i, annotation) in ui_actions.annotations.iter_mut().enumerate() {
+                    ui.label(annotation.kind.to_string());
+                    ui.add(egui::TextEdit::singleline(&mut annotation.text).desired_width(160.0));
+                    if ui
+                        .small_button("Orbit")
+                        .on_hover_text("Make the camera orbit around this point.")
+                        .clicked()
+                    {
+                        ui_actions.pivot_request = Some(annotation.position);
+                    }
+                    if ui.small_button("Remove").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = removed {
+            ui_actions.annotations.remove(i);
+        }
+
+        if ui
+            .button("Save")
+            .on_hover_text(
+                "Saves the annotations next to the model file, along with the saved views, to \
+                be loaded with it.",
+            )
+            .clicked()
+        {
+            ui_actions.save_sidecar = true;
+        }
+    }
+
+    /// Carries out a key bound action that changes settings. Camera movement is left to the
+    /// caller.
+    pub fn handle_action(
+        &mut self,
+        action: Action,
+        model: &mut Option<crate::Model>,
+        ui_actions: &mut UiActions,
+    ) {
+        let preset = match action {
+            Action::PresetPlain => Preset::Plain,
+            Action::PresetAerial => Preset::Aerial,
+            Action::PresetColoredDepth => Preset::ChromaDepth,
+            Action::ToggleHatching => {
+                if let Some(model) = model {
+                    let mut attr = model.get_attributes().clone();
+                    attr.replace_shadows_with_hatching = !attr.replace_shadows_with_hatching;
+                    model.set_attributes(attr);
+                }
+                return;
+            }
+            Action::ToggleDebugBuffers => {
+                ui_actions.show_debug = !ui_actions.show_debug;
+                return;
+            }
+            Action::ToggleHelp => {
+                self.show_key_help = !self.show_key_help;
+                return;
+            }
+            _ => return,
+        };
+        self.preset = preset;
+        if let Some(model) = model {
+            self.apply_preset(model);
+        }
+    }
+
+    /// Builds the standard anatomical view buttons and the list of saved views.
+    fn build_view_editor(&mut self, ui: &mut egui::Ui, ui_actions: &mut UiActions) {
+        let current = ui_actions.camera_state;
+        let frame = &mut self.patient_frame;
+        egui::Grid::new("patient_frame_grid")
+            .striped(true)
+            .spacing([40.0, 4.0])
+            .show(ui, |ui| {
+                for (label, axis) in [
+                    ("Anterior axis", &mut frame.anterior),
+                    ("Superior axis", &mut frame.superior),
+                ] {
+                    ui.label(label);
+                    egui::ComboBox::from_id_source(label)
+                        .selected_text(axis.to_string())
+                        .show_ui(ui, |ui| {
+                            for direction in AxisDirection::ALL {
+                                ui.selectable_value(axis, direction, direction.to_string());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Model axis pointing towards this side of the patient.");
+                    ui.end_row();
+                }
+            });
+
+        let valid_frame = frame.left().is_some();
+        if !valid_frame {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                "The anterior and superior axes must be perpendicular.",
+            );
+        }
+        ui.horizontal_wrapped(|ui| {
+            for view in AnatomicalView::ALL {
+                if ui
+                    .add_enabled(valid_frame, egui::Button::new(view.to_string()))
+                    .clicked()
+                {
+                    if let Some(orientation) = frame.orientation(view) {
+                        ui_actions.camera_request = Some(CameraState {
+                            orientation,
+                            ..current
+                        });
+                    }
+                }
+            }
+        });
+        ui.separator();
+
+        let mut removed = None;
+        for (i, view) in ui_actions.saved_views.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.button(&view.name).clicked() {
+                    ui_actions.camera_request = Some(view.state);
+                }
+                if ui.small_button("Remove").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed {
+            ui_actions.saved_views.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.view_name).hint_text("View name"));
+            if ui.button("Save view").clicked() {
+                let name = match self.view_name.trim() {
+                    "" => format!("View {}", ui_actions.saved_views.len() + 1),
+                    name => name.to_owned(),
+                };
+                ui_actions.saved_views.push(SavedView {
+                    name,
+                    state: current,
+                });
+                self.view_name.clear();
+            }
+        });
+        if ui
+            .button("Save with model")
+            .on_hover_text(
+                "Saves the views next to the model file, along with the annotations, to be \
+                loaded with it.",
+            )
+            .clicked()
+        {
+            ui_actions.save_sidecar = true;
+        }
     }
 
     /// Applies a preset to model renderer.
@@ -298,7 +1609,7 @@ impl UI {
                 preset.replace_shadows_with_hatching = true;
                 preset.hatching_depth = 0.75;
                 preset.hatching_steps = 150;
-                preset.hatching_frequency = 4;
+                preset.hatching_spacing = 4.0;
                 preset.hatching_intensity = 0.75;
             }
             Preset::Aerial => {
@@ -310,18 +1621,20 @@ impl UI {
                 preset.replace_shadows_with_hatching = true;
                 preset.hatching_depth = 0.75;
                 preset.hatching_steps = 150;
-                preset.hatching_frequency = 4;
+                preset.hatching_spacing = 4.0;
                 preset.hatching_intensity = 0.75;
             }
             Preset::ChromaDepth => {
                 preset.toon_factor = 0.0;
                 preset.distance_shading_channel = DistanceShadingChannel::Hue;
                 preset.distance_shading_power = 0.6;
+                preset.distance_shading_colormap = Colormap::Hsv;
+                preset.hue_saturation = 0.6;
 
                 preset.replace_shadows_with_hatching = true;
                 preset.hatching_depth = 0.75;
                 preset.hatching_steps = 150;
-                preset.hatching_frequency = 4;
+                preset.hatching_spacing = 4.0;
                 preset.hatching_intensity = 0.75;
             }
         };
@@ -351,3 +1664,386 @@ impl UI {
         Ok(cursor)
     }
 }
+
+/// Builds the editor for the list of lights, and the choice of which light casts the shadow map.
+fn build_light_editor(ui: &mut egui::Ui, attr: &mut Attributes) {
+    let mut removed = None;
+    for (i, light) in attr.lights.iter_mut().enumerate() {
+        egui::Grid::new(("light_grid", i))
+            .striped(true)
+            .spacing([40.0, 4.0])
+            .show(ui, |ui| {
+                ui.strong(format!("Light {}", i + 1));
+                if ui.button("Remove").clicked() {
+                    removed = Some(i);
+                }
+                ui.end_row();
+
+                ui.label("Type");
+                egui::ComboBox::from_id_source(("light_kind", i))
+                    .selected_text(light.kind.to_string())
+                    .show_ui(ui, |ui| {
+                        for kind in [LightKind::Directional, LightKind::Point] {
+                            ui.selectable_value(&mut light.kind, kind, kind.to_string());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Color");
+                ui.color_edit_button_rgb(&mut light.color);
+                ui.end_row();
+
+                ui.label("Intensity");
+                ui.add(egui::Slider::new(&mut light.intensity, 0.0..=2.0));
+                ui.end_row();
+
+                ui.label("Attached to camera");
+                ui.checkbox(&mut light.camera_attached, "")
+                    .on_hover_text("Camera-attached lights are placed relative to the view.");
+                ui.end_row();
+
+                let (label, speed) = match light.kind {
+                    LightKind::Directional => ("Direction", 0.01),
+                    LightKind::Point => ("Position", attr.model_size as f64 * 0.005),
+                };
+                ui.label(label);
+                ui.horizontal(|ui| {
+                    for value in light.position.iter_mut() {
+                        ui.add(egui::DragValue::new(value).speed(speed));
+                    }
+                });
+                ui.end_row();
+            });
+        ui.separator();
+    }
+
+    if let Some(i) = removed {
+        attr.lights.remove(i);
+        if attr.shadow_light > i || attr.shadow_light >= attr.lights.len() {
+            attr.shadow_light = attr.shadow_light.saturating_sub(1);
+        }
+    }
+
+    egui::Grid::new("light_editor_grid")
+        .striped(true)
+        .spacing([40.0, 4.0])
+        .show(ui, |ui| {
+            ui.label("Shadow light");
+            let light_name = |i: usize| format!("Light {}", i + 1);
+            egui::ComboBox::from_id_source("shadow_light")
+                .selected_text(match attr.lights.is_empty() {
+                    true => "Camera".to_owned(),
+                    false => light_name(attr.shadow_light),
+                })
+                .show_ui(ui, |ui| {
+                    for i in 0..attr.lights.len() {
+                        ui.selectable_value(&mut attr.shadow_light, i, light_name(i));
+                    }
+                })
+                .response
+                .on_hover_text("Lights with zero intensity still cast shadows.");
+            ui.end_row();
+
+            ui.label("");
+            if ui
+                .add_enabled(
+                    attr.lights.len() < MAX_LIGHTS,
+                    egui::Button::new("Add light"),
+                )
+                .clicked()
+            {
+                attr.lights
+                    .push(Light::directional(na::Vector3::new(0.0, 1.0, 0.0), 0.5));
+            }
+            ui.end_row();
+        });
+}
+
+/// Lists the clipping planes with controls for moving, flipping and removing them.
+fn build_clip_plane_editor(ui: &mut egui::Ui, attr: &mut Attributes) {
+    let mut removed = None;
+    for (i, plane) in attr.clip_planes.iter_mut().enumerate() {
+        egui::Grid::new(("clip_plane_grid", i))
+            .striped(true)
+            .spacing([40.0, 4.0])
+            .show(ui, |ui| {
+                ui.checkbox(&mut plane.enabled, format!("Plane {}", i + 1));
+                if ui.button("Remove").clicked() {
+                    removed = Some(i);
+                }
+                ui.end_row();
+
+                ui.label("Axis");
+                egui::ComboBox::from_id_source(("clip_plane_axis", i))
+                    .selected_text(plane.axis.to_string())
+                    .show_ui(ui, |ui| {
+                        for axis in ClipAxis::ALL {
+                            ui.selectable_value(&mut plane.axis, axis, axis.to_string());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Position");
+                ui.add(
+                    egui::Slider::from_get_set(0.0..=100.0, |shown| {
+                        if let Some(shown) = shown {
+                            plane.offset = shown as f32 / 100.0 - 0.5;
+                        }
+                        ((plane.offset + 0.5) * 100.0) as f64
+                    })
+                    .fixed_decimals(0)
+                    .suffix("%"),
+                )
+                .on_hover_text("Position across the model's bounding box along the axis.");
+                ui.end_row();
+
+                ui.label("Flip");
+                ui.checkbox(&mut plane.flipped, "")
+                    .on_hover_text("Cut away the other side of the plane.");
+                ui.end_row();
+            });
+        ui.separator();
+    }
+
+    if let Some(i) = removed {
+        attr.clip_planes.remove(i);
+    }
+
+    egui::Grid::new("clip_plane_editor_grid")
+        .striped(true)
+        .spacing([40.0, 4.0])
+        .show(ui, |ui| {
+            ui.label("Cap color");
+            ui.color_edit_button_rgb(&mut attr.clip_cap_color)
+                .on_hover_text("Fills the cut cross-sections of closed meshes.");
+            ui.end_row();
+
+            ui.label("");
+            if ui
+                .add_enabled(
+                    attr.clip_planes.len() < MAX_CLIP_PLANES,
+                    egui::Button::new("Add plane"),
+                )
+                .clicked()
+            {
+                let axis = ClipAxis::ALL[attr.clip_planes.len() % ClipAxis::ALL.len()];
+                attr.clip_planes.push(ClipPlane::new(axis));
+            }
+            ui.end_row();
+        });
+}
+
+/// Draws a strip showing the colours used by colored depth shading from near to far.
+fn build_legend(ctx: &egui::Context, attr: &Attributes) {
+    const STEPS: usize = 64;
+    egui::Area::new("depth_legend")
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_max_width(256.0);
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::vec2(256.0, 16.0), egui::Sense::hover());
+                let step_width = rect.width() / STEPS as f32;
+                for i in 0..STEPS {
+                    let t = i as f32 / (STEPS - 1) as f32;
+                    let [r, g, b] = match attr.distance_shading_colormap {
+                        Colormap::Hsv => {
+                            let hue = colormap::hue_at(
+                                attr.hue_near,
+                                attr.hue_far,
+                                attr.hue_direction,
+                                t,
+                            );
+                            colormap::hsv_to_rgb(hue, attr.hue_saturation, 1.0)
+                        }
+                        map => map.sample(1.0 - t),
+                    };
+                    let color = egui::Color32::from_rgb(
+                        colormap::to_u8(r),
+                        colormap::to_u8(g),
+                        colormap::to_u8(b),
+                    );
+                    let min = rect.min + egui::vec2(step_width * i as f32, 0.0);
+                    let step =
+                        egui::Rect::from_min_size(min, egui::vec2(step_width, rect.height()));
+                    ui.painter().rect_filled(step.expand(0.5), 0.0, color);
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Near");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label("Far");
+                    });
+                });
+            });
+        });
+}
+
+/// Builds the overlay listing every key binding.
+fn build_key_help(ctx: &egui::Context, key_bindings: &KeyBindings, open: &mut bool) {
+    egui::Window::new("Key bindings")
+        .open(open)
+        .auto_sized()
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("key_bindings_grid")
+                .striped(true)
+                .spacing([40.0, 4.0])
+                .show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.label(action.to_string());
+                        let keys = key_bindings.keys(action);
+                        if keys.is_empty() {
+                            ui.weak("Unbound");
+                        } else {
+                            ui.monospace(keys.join(", "));
+                        }
+                        ui.end_row();
+                    }
+                });
+            ui.label(format!(
+                "Edit {} in the assets directory to change the bindings.",
+                keybindings::CONFIG_FILE
+            ));
+        });
+}
+
+/// Builds the tooltip describing the surface point under the mouse cursor.
+fn build_surface_readout(ctx: &egui::Context, model: &crate::Model, point: &SurfacePoint) {
+    let vector = |v: na::Vector3<f32>| format!("{:.3}, {:.3}, {:.3}", v.x, v.y, v.z);
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("surface_readout"), |ui| {
+        egui::Grid::new("surface_readout_grid")
+            .spacing([20.0, 2.0])
+            .show(ui, |ui| {
+                if let Some(mesh) = model.get_meshes().get(point.mesh) {
+                    ui.label("Object");
+                    ui.label(&mesh.name);
+                    ui.end_row();
+                }
+                ui.label("Position")
+                    .on_hover_text("In the coordinates of the obj file.");
+                ui.monospace(vector(point.position.coords + model.get_center()));
+                ui.end_row();
+                ui.label("Triangle");
+                ui.monospace(point.triangle.to_string());
+                ui.end_row();
+                ui.label("Normal");
+                ui.monospace(vector(point.normal));
+                ui.end_row();
+                if let Some(value) = point.curvature {
+                    ui.label(model.get_attributes().curvature_coloring.to_string());
+                    ui.monospace(format!("{:.4}", value));
+                    ui.end_row();
+                }
+            });
+    });
+}
+
+/// Projects a point in the scene to the screen, or returns `None` if it is behind the camera.
+fn project_to_screen(
+    ctx: &egui::Context,
+    attributes: &Attributes,
+    point: &na::Point3<f32>,
+) -> Option<egui::Pos2> {
+    let clip = attributes.projection_matrix * point.to_homogeneous();
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let screen = ctx.input().screen_rect();
+    Some(egui::pos2(
+        screen.left() + (clip.x / clip.w + 1.0) / 2.0 * screen.width(),
+        screen.top() + (1.0 - clip.y / clip.w) / 2.0 * screen.height(),
+    ))
+}
+
+/// Draws the measurements over the scene as lines labelled with their length.
+fn build_measurement_overlay(
+    ctx: &egui::Context,
+    attributes: &Attributes,
+    measurements: &[Measurement],
+    pending: Option<&SurfacePoint>,
+) {
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let color = egui::Color32::YELLOW;
+    let stroke = egui::Stroke::new(2.0, color);
+    let project = |point| project_to_screen(ctx, attributes, point);
+    for measurement in measurements {
+        let (Some(start), Some(end)) = (project(&measurement.start), project(&measurement.end))
+        else {
+            continue;
+        };
+        painter.line_segment([start, end], stroke);
+        painter.circle_filled(start, 3.0, color);
+        painter.circle_filled(end, 3.0, color);
+        let mut label = format!("{:.2}", measurement.distance());
+        if let Some(geodesic) = measurement.geodesic {
+            label.push_str(&format!(" ({:.2} along surface)", geodesic));
+        }
+        let middle = start + (end - start) / 2.0;
+        let text = painter.layout_no_wrap(label, egui::FontId::proportional(14.0), color);
+        let rect =
+            egui::Rect::from_min_size(middle + egui::vec2(6.0, -6.0 - text.size().y), text.size());
+        painter.rect_filled(rect.expand(2.0), 2.0, egui::Color32::from_black_alpha(160));
+        painter.galley(rect.min, text);
+    }
+    if let Some(position) = pending.and_then(|point| project(&point.position)) {
+        painter.circle_filled(position, 3.0, color);
+    }
+}
+
+/// Draws the annotations over the scene as billboards of a constant size, so they stay readable
+/// at any zoom. Those behind the model are left out or drawn faded.
+fn build_annotation_overlay(
+    ctx: &egui::Context,
+    model: &crate::Model,
+    annotations: &[Annotation],
+    occlude: bool,
+) {
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let font = egui::FontId::proportional(14.0);
+    for annotation in annotations {
+        let Some(anchor) = project_to_screen(ctx, model.get_attributes(), &annotation.position)
+        else {
+            continue;
+        };
+        let visible = model.is_visible(&annotation.position);
+        if occlude && !visible {
+            continue;
+        }
+        let alpha = if visible { 1.0 } else { 0.35 };
+        let color = egui::Color32::from_rgb(120, 220, 255).linear_multiply(alpha);
+        let background = egui::Color32::from_black_alpha(170).linear_multiply(alpha);
+        let text = painter.layout_no_wrap(
+            annotation.text.clone(),
+            font.clone(),
+            egui::Color32::WHITE.linear_multiply(alpha),
+        );
+        let offset = match annotation.kind {
+            AnnotationKind::Landmark => {
+                painter.circle(anchor, 5.0, color, egui::Stroke::new(1.5, background));
+                egui::vec2(10.0, -text.size().y / 2.0)
+            }
+            AnnotationKind::Label => {
+                let corner = anchor + egui::vec2(24.0, -24.0);
+                painter.line_segment([anchor, corner], egui::Stroke::new(1.5, color));
+                painter.circle_filled(anchor, 2.5, color);
+                egui::vec2(24.0, -24.0 - text.size().y)
+            }
+        };
+        let rect = egui::Rect::from_min_size(anchor + offset, text.size());
+        painter.rect_filled(rect.expand(3.0), 3.0, background);
+        painter.galley(rect.min, text);
+    }
+}
+
+/// Returns a slider over `value` shown multiplied by `scale`. Values typed into it are read in
+/// the units shown, which isn't the case when only the formatting is scaled.
+fn scaled_slider(value: &mut f32, range: RangeInclusive<f32>, scale: f32) -> egui::Slider<'_> {
+    let range = (range.start() * scale) as f64..=(range.end() * scale) as f64;
+    egui::Slider::from_get_set(range, move |shown| {
+        if let Some(shown) = shown {
+            *value = shown as f32 / scale;
+        }
+        (*value * scale) as f64
+    })
+}
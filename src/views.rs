@@ -0,0 +1,175 @@
+//! Contains saved camera views and the standard anatomical views, which are defined relative to
+//! a [PatientFrame] mapping patient directions onto the model axes.
+
+use crate::camera::CameraState;
+use nalgebra as na;
+use serde::{Deserialize, Serialize};
+
+/// Represents a signed model axis.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(C)]
+pub enum AxisDirection {
+    PosX = 0,
+    NegX = 1,
+    #[default]
+    PosY = 2,
+    NegY = 3,
+    PosZ = 4,
+    NegZ = 5,
+}
+
+impl AxisDirection {
+    pub const ALL: [AxisDirection; 6] = [
+        AxisDirection::PosX,
+        AxisDirection::NegX,
+        AxisDirection::PosY,
+        AxisDirection::NegY,
+        AxisDirection::PosZ,
+        AxisDirection::NegZ,
+    ];
+
+    /// Returns the unit vector along the axis.
+    pub fn vector(&self) -> na::Vector3<f32> {
+        match self {
+            AxisDirection::PosX => na::Vector3::x(),
+            AxisDirection::NegX => -na::Vector3::x(),
+            AxisDirection::PosY => na::Vector3::y(),
+            AxisDirection::NegY => -na::Vector3::y(),
+            AxisDirection::PosZ => na::Vector3::z(),
+            AxisDirection::NegZ => -na::Vector3::z(),
+        }
+    }
+}
+
+impl std::fmt::Display for AxisDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AxisDirection::PosX => write!(f, "+X"),
+            AxisDirection::NegX => write!(f, "-X"),
+            AxisDirection::PosY => write!(f, "+Y"),
+            AxisDirection::NegY => write!(f, "-Y"),
+            AxisDirection::PosZ => write!(f, "+Z"),
+            AxisDirection::NegZ => write!(f, "-Z"),
+        }
+    }
+}
+
+/// Describes which model axes point towards the front and the head of the patient. The
+/// patient's left follows from these in a right-handed frame.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PatientFrame {
+    pub anterior: AxisDirection,
+    pub superior: AxisDirection,
+}
+
+impl Default for PatientFrame {
+    /// Matches the usual OBJ convention of Y up, with the patient facing +Z.
+    fn default() -> Self {
+        Self {
+            anterior: AxisDirection::PosZ,
+            superior: AxisDirection::PosY,
+        }
+    }
+}
+
+impl PatientFrame {
+    /// Returns the model space direction towards the patient's left, or `None` if the anterior
+    /// and superior axes are parallel.
+    pub fn left(&self) -> Option<na::Vector3<f32>> {
+        let left = self.superior.vector().cross(&self.anterior.vector());
+        (left.norm() > 0.5).then_some(left)
+    }
+
+    /// Returns the camera orientation for a standard view, or `None` if the frame is invalid.
+    pub fn orientation(&self, view: AnatomicalView) -> Option<na::UnitQuaternion<f32>> {
+        let left = self.left()?;
+        let anterior = self.anterior.vector();
+        let superior = self.superior.vector();
+        // Direction from the target towards the camera, and the direction shown as up.
+        let (direction, up) = match view {
+            AnatomicalView::Anterior => (anterior, superior),
+            AnatomicalView::Posterior => (-anterior, superior),
+            AnatomicalView::Left => (left, superior),
+            AnatomicalView::Right => (-left, superior),
+            AnatomicalView::Superior => (superior, anterior),
+            AnatomicalView::Inferior => (-superior, anterior),
+        };
+        Some(na::UnitQuaternion::face_towards(&direction, &up))
+    }
+}
+
+/// The standard views of anatomy, named by the side of the patient facing the camera.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnatomicalView {
+    Anterior,
+    Posterior,
+    Left,
+    Right,
+    Superior,
+    Inferior,
+}
+
+impl AnatomicalView {
+    pub const ALL: [AnatomicalView; 6] = [
+        AnatomicalView::Anterior,
+        AnatomicalView::Posterior,
+        AnatomicalView::Left,
+        AnatomicalView::Right,
+        AnatomicalView::Superior,
+        AnatomicalView::Inferior,
+    ];
+}
+
+impl std::fmt::Display for AnatomicalView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AnatomicalView::Anterior => write!(f, "Anterior"),
+            AnatomicalView::Posterior => write!(f, "Posterior"),
+            AnatomicalView::Left => write!(f, "Left"),
+            AnatomicalView::Right => write!(f, "Right"),
+            AnatomicalView::Superior => write!(f, "Superior"),
+            AnatomicalView::Inferior => write!(f, "Inferior"),
+        }
+    }
+}
+
+/// A named camera bookmark.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedView {
+    pub name: String,
+    pub state: CameraState,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anterior_view_faces_patient() {
+        let frame = PatientFrame {
+            anterior: AxisDirection::NegY,
+            superior: AxisDirection::PosZ,
+        };
+        // Left, posterior, superior is right-handed, as in DICOM patient coordinates.
+        assert_eq!(frame.left(), Some(na::Vector3::x()));
+        let orientation = frame.orientation(AnatomicalView::Anterior).unwrap();
+        let towards_camera = orientation * na::Vector3::z();
+        let up = orientation * na::Vector3::y();
+        assert!((towards_camera + na::Vector3::y()).norm() < 1e-6);
+        assert!((up - na::Vector3::z()).norm() < 1e-6);
+        // The patient's left is on the right of the screen when facing them.
+        let screen_right = orientation * na::Vector3::x();
+        assert!((screen_right - na::Vector3::x()).norm() < 1e-6);
+    }
+
+    #[test]
+    fn parallel_axes_are_invalid() {
+        let frame = PatientFrame {
+            anterior: AxisDirection::PosZ,
+            superior: AxisDirection::NegZ,
+        };
+        assert_eq!(frame.orientation(AnatomicalView::Left), None);
+    }
+}